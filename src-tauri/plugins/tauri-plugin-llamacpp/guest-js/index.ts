@@ -7,6 +7,7 @@ export interface SessionInfo {
   model_id: string;
   model_path: string;
   api_key: string;
+  mmproj_path?: string | null;
 }
 
 export interface DeviceInfo {
@@ -0,0 +1,257 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde_json::{json, Map, Value};
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio::{
+    sync::{oneshot, Mutex},
+    time::timeout,
+};
+
+use super::{
+    constants::{
+        MCP_TOOL_APPROVAL_REQUESTED_EVENT, MCP_TOOL_APPROVAL_RESOLVED_EVENT,
+        MCP_TOOL_APPROVAL_TIMEOUT, MCP_TOOL_POLICY_FILE,
+    },
+    models::{
+        ArgumentCondition, ToolApprovalRequest, ToolPolicy, ToolPolicyAction, ToolPolicyRule,
+    },
+};
+
+/// The user's answer to a pending tool call
+#[derive(Debug)]
+pub struct ApprovalDecision {
+    pub approved: bool,
+    pub reason: Option<String>,
+}
+
+pub struct PendingApproval {
+    pub request: ToolApprovalRequest,
+    sender: oneshot::Sender<ApprovalDecision>,
+}
+
+/// Tool calls waiting for approval, keyed by request id
+pub type PendingApprovals = Arc<Mutex<HashMap<String, PendingApproval>>>;
+
+fn policy_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(MCP_TOOL_POLICY_FILE)
+}
+
+/// Reads the tool policy, using the defaults when there is none yet
+pub fn load_policy(data_dir: &Path) -> ToolPolicy {
+    let path = policy_path(data_dir);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return ToolPolicy::default();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        log::error!("Invalid tool policy {:?}, using defaults: {}", path, e);
+        ToolPolicy::default()
+    })
+}
+
+pub fn save_policy(data_dir: &Path, policy: &ToolPolicy) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(policy).map_err(|e| e.to_string())?;
+    fs::write(policy_path(data_dir), content)
+        .map_err(|e| format!("Failed to save tool policy: {}", e))
+}
+
+/// Email addresses in an argument, from strings like `"Ann <ann@x.com>, bob@y.com"`,
+/// arrays and recipient objects
+fn email_addresses(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => s
+            .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+            .map(|part| part.trim_matches(|c: char| matches!(c, '<' | '>' | '"' | '\'')))
+            .filter(|part| part.contains('@'))
+            .map(str::to_lowercase)
+            .collect(),
+        Value::Array(items) => items.iter().flat_map(email_addresses).collect(),
+        Value::Object(fields) => fields.values().flat_map(email_addresses).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn email_in_domains(address: &str, domains: &[String]) -> bool {
+    let Some((_, domain)) = address.rsplit_once('@') else {
+        return false;
+    };
+    domains.iter().any(|allowed| {
+        let allowed = allowed.trim_start_matches('@').to_lowercase();
+        domain == allowed || domain.ends_with(&format!(".{}", allowed))
+    })
+}
+
+fn condition_holds(condition: &ArgumentCondition, arguments: Option<&Map<String, Value>>) -> bool {
+    let Some(value) = arguments.and_then(|args| args.get(&condition.argument)) else {
+        return false;
+    };
+    if let Some(values) = &condition.one_of {
+        if !values.contains(value) {
+            return false;
+        }
+    }
+    if let Some(domains) = &condition.email_domains {
+        let addresses = email_addresses(value);
+        if addresses.is_empty() || !addresses.iter().all(|a| email_in_domains(a, domains)) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Decides what to do with a tool call: tool rules first, then server rules, then the
+/// default action. Rules whose conditions do not hold are skipped.
+pub fn evaluate_policy(
+    policy: &ToolPolicy,
+    server: Option<&str>,
+    tool: &str,
+    arguments: Option<&Map<String, Value>>,
+) -> ToolPolicyAction {
+    let server_matches = |rule: &&ToolPolicyRule| {
+        rule.server
+            .as_deref()
+            .map_or(true, |name| Some(name) == server)
+    };
+    let applies = |rule: &&ToolPolicyRule| {
+        rule.conditions
+            .iter()
+            .all(|condition| condition_holds(condition, arguments))
+    };
+
+    let tool_rule = policy
+        .rules
+        .iter()
+        .filter(|rule| rule.tool.as_deref() == Some(tool))
+        .filter(server_matches)
+        .find(applies);
+    let server_rule = || {
+        policy
+            .rules
+            .iter()
+            .filter(|rule| rule.tool.is_none())
+            .filter(server_matches)
+            .find(applies)
+    };
+    tool_rule
+        .or_else(server_rule)
+        .map_or(policy.default_action, |rule| rule.action)
+}
+
+/// Records an unconditional rule for the tool on `server` so the decision sticks. It goes
+/// in front of the other rules for the tool, but after the conditional ones that apply to
+/// the server, so restrictions such as `emailDomains` keep deciding first.
+pub fn remember_decision(
+    policy: &mut ToolPolicy,
+    server: &str,
+    tool: &str,
+    action: ToolPolicyAction,
+) {
+    let for_tool_on_server = |rule: &ToolPolicyRule| {
+        rule.tool.as_deref() == Some(tool) && rule.server.as_deref().map_or(true, |s| s == server)
+    };
+    policy.rules.retain(|rule| {
+        !(rule.tool.as_deref() == Some(tool)
+            && rule.server.as_deref() == Some(server)
+            && rule.conditions.is_empty())
+    });
+    let position = policy
+        .rules
+        .iter()
+        .rposition(|rule| for_tool_on_server(rule) && !rule.conditions.is_empty())
+        .map_or(0, |index| index + 1);
+    policy.rules.insert(
+        position,
+        ToolPolicyRule {
+            server: Some(server.to_string()),
+            tool: Some(tool.to_string()),
+            action,
+            conditions: Vec::new(),
+        },
+    );
+}
+
+/// Queues a tool call, asks the frontend to approve it and waits for the decision.
+/// Calls left unanswered are rejected after `MCP_TOOL_APPROVAL_TIMEOUT`.
+pub async fn request_approval<R: Runtime>(
+    app: &AppHandle<R>,
+    pending: &PendingApprovals,
+    request: ToolApprovalRequest,
+) -> ApprovalDecision {
+    let id = request.id.clone();
+    let (sender, receiver) = oneshot::channel();
+    pending.lock().await.insert(
+        id.clone(),
+        PendingApproval {
+            request: request.clone(),
+            sender,
+        },
+    );
+    log::info!(
+        "Tool call {} ({}) is waiting for approval",
+        id,
+        request.tool
+    );
+    if let Err(e) = app.emit(MCP_TOOL_APPROVAL_REQUESTED_EVENT, &request) {
+        log::warn!(
+            "Failed to emit {}: {}",
+            MCP_TOOL_APPROVAL_REQUESTED_EVENT,
+            e
+        );
+    }
+
+    let decision = match timeout(MCP_TOOL_APPROVAL_TIMEOUT, receiver).await {
+        Ok(Ok(decision)) => decision,
+        Ok(Err(_)) => ApprovalDecision {
+            approved: false,
+            reason: Some("approval was abandoned".to_string()),
+        },
+        Err(_) => {
+            pending.lock().await.remove(&id);
+            ApprovalDecision {
+                approved: false,
+                reason: Some(format!(
+                    "no decision within {} seconds",
+                    MCP_TOOL_APPROVAL_TIMEOUT.as_secs()
+                )),
+            }
+        }
+    };
+
+    log::info!(
+        "Tool call {} ({}) was {}",
+        id,
+        request.tool,
+        if decision.approved {
+            "approved"
+        } else {
+            "rejected"
+        }
+    );
+    if let Err(e) = app.emit(
+        MCP_TOOL_APPROVAL_RESOLVED_EVENT,
+        json!({ "id": id, "approved": decision.approved, "reason": decision.reason }),
+    ) {
+        log::warn!("Failed to emit {}: {}", MCP_TOOL_APPROVAL_RESOLVED_EVENT, e);
+    }
+    decision
+}
+
+/// Answers a pending tool call, returning the request it belonged to
+pub async fn resolve_approval(
+    pending: &PendingApprovals,
+    id: &str,
+    decision: ApprovalDecision,
+) -> Result<ToolApprovalRequest, String> {
+    let entry = pending
+        .lock()
+        .await
+        .remove(id)
+        .ok_or_else(|| format!("No tool call {} is waiting for approval", id))?;
+    // The caller may have given up already, the decision still counts for the policy
+    let _ = entry.sender.send(decision);
+    Ok(entry.request)
+}
@@ -0,0 +1,155 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use jan_utils::{redact_json, RotatingJsonl, TimeRange};
+use rmcp::model::CallToolResult;
+use serde_json::{Map, Value};
+use tokio::sync::Mutex;
+
+use super::constants::{
+    MCP_TOOL_CALL_LOG_DEFAULT_LIMIT, MCP_TOOL_CALL_LOG_FILE, MCP_TOOL_CALL_LOG_MAX_FILES,
+    MCP_TOOL_CALL_LOG_MAX_FILE_BYTES, MCP_TOOL_CALL_REDACT_FIELDS,
+};
+use super::models::{ToolCallLogEntry, ToolCallLogQuery};
+
+/// What happened while dispatching a tool call, filled in by `call_tool`
+#[derive(Debug, Default)]
+pub struct ToolCallTrace {
+    pub server: Option<String>,
+    pub http_fallback: bool,
+    pub cancelled: bool,
+}
+
+/// Serializes appends to the tool call log. Like the proxy audit log, entries are only
+/// ever appended; once the file reaches `max_file_bytes` it is rotated and the oldest of
+/// `max_files` files is dropped.
+pub struct ToolCallLog {
+    write_lock: Mutex<()>,
+    max_file_bytes: u64,
+    max_files: usize,
+}
+
+impl Default for ToolCallLog {
+    fn default() -> Self {
+        Self::new(
+            MCP_TOOL_CALL_LOG_MAX_FILE_BYTES,
+            MCP_TOOL_CALL_LOG_MAX_FILES,
+        )
+    }
+}
+
+impl ToolCallLog {
+    pub fn new(max_file_bytes: u64, max_files: usize) -> Self {
+        Self {
+            write_lock: Mutex::new(()),
+            max_file_bytes,
+            // Reads only look at the files a default log keeps
+            max_files: max_files.clamp(1, MCP_TOOL_CALL_LOG_MAX_FILES),
+        }
+    }
+
+    pub async fn append(&self, dir: &Path, entry: &ToolCallLogEntry) {
+        let line = match serde_json::to_string(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                log::warn!("Failed to serialize tool call log entry: {}", e);
+                return;
+            }
+        };
+        let _guard = self.write_lock.lock().await;
+        let files = RotatingJsonl::new(
+            dir,
+            MCP_TOOL_CALL_LOG_FILE,
+            self.max_file_bytes,
+            self.max_files,
+        );
+        if let Err(e) = files.append_line_blocking(line).await {
+            log::warn!("Failed to write tool call log entry: {}", e);
+        }
+    }
+}
+
+fn tool_call_log_files(dir: &Path) -> RotatingJsonl {
+    RotatingJsonl::new(
+        dir,
+        MCP_TOOL_CALL_LOG_FILE,
+        MCP_TOOL_CALL_LOG_MAX_FILE_BYTES,
+        MCP_TOOL_CALL_LOG_MAX_FILES,
+    )
+}
+
+/// Path of the n-th rotated log file (0 is the active file)
+pub fn tool_call_log_path(dir: &Path, index: usize) -> PathBuf {
+    tool_call_log_files(dir).path(index)
+}
+
+impl ToolCallLogEntry {
+    pub fn new(
+        tool: &str,
+        arguments: &Option<Map<String, Value>>,
+        cancellation_token: Option<String>,
+        trace: ToolCallTrace,
+        duration: Duration,
+        result: &Result<CallToolResult, String>,
+    ) -> Self {
+        let redact_fields: Vec<String> = MCP_TOOL_CALL_REDACT_FIELDS
+            .iter()
+            .map(|field| field.to_string())
+            .collect();
+        let (result_bytes, error) = match result {
+            Ok(result) => (
+                serde_json::to_vec(&result.content).ok().map(|b| b.len()),
+                (result.is_error == Some(true)).then(|| {
+                    result
+                        .content
+                        .iter()
+                        .filter_map(|content| content.raw.as_text().map(|t| t.text.clone()))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                }),
+            ),
+            Err(e) => (None, Some(e.clone())),
+        };
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            server: trace.server,
+            tool: tool.to_string(),
+            arguments: arguments
+                .as_ref()
+                .map(|args| redact_json(&Value::Object(args.clone()), &redact_fields)),
+            duration_ms: duration.as_millis() as u64,
+            result_bytes,
+            error,
+            http_fallback: trace.http_fallback,
+            cancellation_token,
+            cancelled: trace.cancelled,
+        }
+    }
+}
+
+impl ToolCallLogQuery {
+    fn matches(&self, entry: &ToolCallLogEntry, range: &TimeRange) -> bool {
+        if self.errors_only && entry.error.is_none() {
+            return false;
+        }
+        range.contains(&entry.timestamp)
+            && self
+                .server
+                .as_ref()
+                .map_or(true, |server| entry.server.as_ref() == Some(server))
+            && self.tool.as_ref().map_or(true, |tool| &entry.tool == tool)
+    }
+}
+
+/// Reads the tool call log in `dir`, rotated files included, returning the newest
+/// matching entries first
+pub fn read_tool_call_log(
+    dir: &Path,
+    query: &ToolCallLogQuery,
+) -> Result<Vec<ToolCallLogEntry>, String> {
+    let range = TimeRange::parse(query.since.as_deref(), query.until.as_deref())?;
+    let limit = query.limit.unwrap_or(MCP_TOOL_CALL_LOG_DEFAULT_LIMIT);
+    tool_call_log_files(dir).read_newest(limit, |entry: &ToolCallLogEntry| {
+        query.matches(entry, &range)
+    })
+}
@@ -0,0 +1,176 @@
+use std::{collections::HashMap, sync::Arc};
+
+use rmcp::{
+    model::{
+        ClientInfo, LoggingMessageNotificationParam, NumberOrString, ProgressNotificationParam,
+        ResourceUpdatedNotificationParam,
+    },
+    service::NotificationContext,
+    ClientHandler, RoleClient,
+};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use super::constants::{
+    MCP_PROMPTS_CHANGED_EVENT, MCP_RESOURCES_CHANGED_EVENT, MCP_RESOURCE_UPDATED_EVENT,
+    MCP_TOOL_LOG_EVENT, MCP_TOOL_PROGRESS_EVENT, MCP_UPDATE_EVENT,
+};
+use crate::core::state::ToolCache;
+
+/// Sends an event to the frontend, hiding the Tauri runtime type
+pub type EventEmitter = Arc<dyn Fn(&str, Value) + Send + Sync>;
+
+/// A running tool call that has a cancellation token
+#[derive(Debug, Clone)]
+pub struct InFlightToolCall {
+    pub server: String,
+    pub tool: String,
+}
+
+/// Running tool calls keyed by cancellation token, which doubles as their MCP progress token
+pub type InFlightToolCalls = Arc<Mutex<HashMap<String, InFlightToolCall>>>;
+
+/// Client side of an MCP connection, forwarding server notifications as events
+#[derive(Clone)]
+pub struct McpClientHandler {
+    server: String,
+    info: ClientInfo,
+    emit: EventEmitter,
+    tool_cache: ToolCache,
+    tool_calls: InFlightToolCalls,
+}
+
+impl McpClientHandler {
+    pub fn new(
+        server: &str,
+        info: ClientInfo,
+        emit: EventEmitter,
+        tool_cache: ToolCache,
+        tool_calls: InFlightToolCalls,
+    ) -> Self {
+        Self {
+            server: server.to_string(),
+            info,
+            emit,
+            tool_cache,
+            tool_calls,
+        }
+    }
+
+    fn notify(&self, event: &str, mut payload: Value) {
+        payload["server"] = Value::String(self.server.clone());
+        (self.emit)(event, payload);
+    }
+}
+
+impl ClientHandler for McpClientHandler {
+    async fn on_progress(
+        &self,
+        params: ProgressNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) {
+        let NumberOrString::String(token) = &params.progress_token.0 else {
+            return; // Only calls with a cancellation token get a progress token of ours
+        };
+        let token = token.to_string();
+        let Some(call) = self.tool_calls.lock().await.get(&token).cloned() else {
+            return;
+        };
+        if call.server != self.server {
+            return;
+        }
+        self.notify(
+            MCP_TOOL_PROGRESS_EVENT,
+            json!({
+                "cancellationToken": token,
+                "tool": call.tool,
+                "progress": params.progress,
+                "total": params.total,
+                "message": params.message,
+            }),
+        );
+    }
+
+    async fn on_logging_message(
+        &self,
+        params: LoggingMessageNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) {
+        log::debug!(
+            "MCP server {} logged ({:?}): {}",
+            self.server,
+            params.level,
+            params.data
+        );
+        // Log messages name no request, so they are attributed only when one call is running
+        let token = {
+            let calls = self.tool_calls.lock().await;
+            let mut tokens = calls
+                .iter()
+                .filter(|(_, call)| call.server == self.server)
+                .map(|(token, _)| token.clone());
+            match (tokens.next(), tokens.next()) {
+                (Some(token), None) => Some(token),
+                _ => None,
+            }
+        };
+        self.notify(
+            MCP_TOOL_LOG_EVENT,
+            json!({
+                "cancellationToken": token,
+                "level": params.level,
+                "logger": params.logger,
+                "data": params.data,
+            }),
+        );
+    }
+
+    async fn on_resource_updated(
+        &self,
+        params: ResourceUpdatedNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) {
+        log::debug!("MCP server {} updated resource {}", self.server, params.uri);
+        self.notify(MCP_RESOURCE_UPDATED_EVENT, json!({ "uri": params.uri }));
+    }
+
+    async fn on_resource_list_changed(&self, _context: NotificationContext<RoleClient>) {
+        log::debug!("MCP server {} changed its resource list", self.server);
+        self.notify(MCP_RESOURCES_CHANGED_EVENT, json!({}));
+    }
+
+    async fn on_tool_list_changed(&self, context: NotificationContext<RoleClient>) {
+        match context.peer.list_all_tools().await {
+            Ok(tools) => {
+                log::info!(
+                    "MCP server {} changed its tools, re-cached {} tools",
+                    self.server,
+                    tools.len()
+                );
+                self.tool_cache
+                    .lock()
+                    .await
+                    .insert(self.server.clone(), tools);
+            }
+            Err(e) => {
+                // Dropping the entry makes the next tool call fetch the list again
+                log::warn!(
+                    "MCP server {} changed its tools but listing them failed: {}",
+                    self.server,
+                    e
+                );
+                self.tool_cache.lock().await.remove(&self.server);
+            }
+        }
+        (self.emit)(MCP_UPDATE_EVENT, json!("MCP servers updated"));
+    }
+
+    async fn on_prompt_list_changed(&self, _context: NotificationContext<RoleClient>) {
+        log::debug!("MCP server {} changed its prompt list", self.server);
+        self.notify(MCP_PROMPTS_CHANGED_EVENT, json!({}));
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        self.info.clone()
+    }
+}
@@ -1,19 +1,44 @@
-use rmcp::model::{CallToolRequestParam, CallToolResult, Content};
+use rmcp::model::{
+    CallToolRequestParam, CallToolResult, Content, GetPromptRequestParam, GetPromptResult,
+    ReadResourceRequestParam, ReadResourceResult, SubscribeRequestParam, UnsubscribeRequestParam,
+};
+use rmcp::{service::Peer, RoleClient};
 use serde_json::{Map, Value};
 use tauri::{AppHandle, Emitter, Runtime, State};
 use tokio::time::timeout;
 use tokio::sync::oneshot;
 
 use super::{
-    constants::{DEFAULT_MCP_CONFIG, MCP_TOOL_CALL_TIMEOUT},
-    helpers::{restart_active_mcp_servers, start_mcp_server_with_restart, stop_mcp_servers, schedule_mcp_reconnect},
+    approval::{
+        evaluate_policy, load_policy, remember_decision, request_approval, resolve_approval,
+        save_policy, ApprovalDecision,
+    },
+    call_log::{read_tool_call_log, ToolCallTrace},
+    client::InFlightToolCall,
+    constants::{DEFAULT_MCP_CONFIG, MCP_UPDATE_EVENT},
+    helpers::{
+        cancel_mcp_request, extract_coerce_arguments, list_server_prompts, list_server_resources,
+        order_servers_by_priority, precache_server_tools, qualified_tool_name,
+        request_with_timeout, restart_active_mcp_servers, schedule_mcp_reconnect, server_peer,
+        server_peers, server_timeouts, split_qualified_tool_name, start_mcp_server_with_restart,
+        stop_mcp_servers, validate_tool_arguments,
+    },
+    status::{self, server_status},
+};
+use crate::core::{
+    app::commands::get_jan_data_folder_path,
+    state::{send_tool_call, tool_call_result, AppState},
 };
-use crate::core::{app::commands::get_jan_data_folder_path, state::AppState};
 use crate::core::{
-    mcp::models::ToolWithServer,
+    mcp::models::{
+        McpServerState, McpServerStatus, PromptWithServer, ResourceWithServer, ToolApprovalRequest,
+        ToolCallLogEntry, ToolCallLogQuery, ToolPolicy, ToolPolicyAction, ToolWithServer,
+    },
     state::{RunningServiceEnum, SharedMcpServers},
 };
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::time::Instant;
 
 #[tauri::command]
 pub async fn activate_mcp_server<R: Runtime>(
@@ -54,6 +79,9 @@ pub async fn deactivate_mcp_server(state: State<'_, AppState>, name: String) ->
         log::info!("Reset restart count for MCP server {}", name);
     }
 
+    state.mcp_tool_cache.lock().await.remove(&name);
+    status::set_state(&state.mcp_server_health, &name, McpServerState::Deactivated).await;
+
     // Now remove and stop the server
     let servers = state.mcp_servers.clone();
     let mut servers_map = servers.lock().await;
@@ -88,7 +116,7 @@ pub async fn restart_mcp_servers(app: AppHandle, state: State<'_, AppState>) ->
     // Restart only previously active servers (like cortex)
     restart_active_mcp_servers(&app, servers).await?;
 
-    app.emit("mcp-update", "MCP servers updated")
+    app.emit(MCP_UPDATE_EVENT, "MCP servers updated")
         .map_err(|e| format!("Failed to emit event: {}", e))?;
 
     Ok(())
@@ -188,6 +216,53 @@ pub async fn get_connected_servers(
     Ok(servers_map.keys().cloned().collect())
 }
 
+/// Reports the health of every configured MCP server, including servers the app starts
+/// itself such as salesboxai-builtin, ordered by name
+#[tauri::command]
+pub async fn get_mcp_server_status(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<McpServerStatus>, String> {
+    let path = get_jan_data_folder_path(app).join("mcp_config.json");
+    let mut configs: BTreeMap<String, Value> = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| {
+            serde_json::from_str::<Value>(&content)
+                .inspect_err(|e| log::warn!("Failed to parse {:?}: {}", path, e))
+                .ok()
+        })
+        .and_then(|config| config.get("mcpServers").and_then(Value::as_object).cloned())
+        .map(|servers| servers.into_iter().collect())
+        .unwrap_or_default();
+    for (name, config) in state.mcp_active_servers.lock().await.iter() {
+        configs
+            .entry(name.clone())
+            .or_insert_with(|| config.clone());
+    }
+
+    let restart_counts = state.mcp_restart_counts.lock().await.clone();
+    let tool_counts: HashMap<String, usize> = state
+        .mcp_tool_cache
+        .lock()
+        .await
+        .iter()
+        .map(|(name, tools)| (name.clone(), tools.len()))
+        .collect();
+    let health = state.mcp_server_health.lock().await;
+    Ok(configs
+        .iter()
+        .map(|(name, config)| {
+            server_status(
+                name,
+                config,
+                health.get(name),
+                restart_counts.get(name).copied().unwrap_or(0),
+                tool_counts.get(name).copied(),
+            )
+        })
+        .collect())
+}
+
 /// Retrieves all available tools from all MCP servers with server information
 ///
 /// # Arguments
@@ -200,23 +275,27 @@ pub async fn get_connected_servers(
 /// 1. Locks the MCP servers mutex to access server connections
 /// 2. Iterates through all connected servers
 /// 3. Gets the list of tools from each server
-/// 4. Associates each tool with its parent server name
-/// 5. Combines all tools into a single vector
+/// 4. Associates each tool with its parent server name and qualified `server__tool` name
+/// 5. Combines all tools into a single vector, ordered by server priority
 /// 6. Returns the combined list of all available tools with server information
 #[tauri::command]
 pub async fn get_tools(state: State<'_, AppState>) -> Result<Vec<ToolWithServer>, String> {
+    let configs = state.mcp_active_servers.lock().await.clone();
     let servers = state.mcp_servers.lock().await;
     let mut all_tools: Vec<ToolWithServer> = Vec::new();
 
-    for (server_name, service) in servers.iter() {
-        // List tools with timeout
+    for server_name in order_servers_by_priority(servers.keys().cloned(), &configs) {
+        let service = &servers[&server_name];
+        // List tools with the server's list timeout
+        let list_timeout = server_timeouts(&configs, &server_name).list;
         let tools_future = service.list_all_tools();
-        let tools = match timeout(MCP_TOOL_CALL_TIMEOUT, tools_future).await {
+        let tools = match timeout(list_timeout, tools_future).await {
             Ok(result) => result.map_err(|e| e.to_string())?,
             Err(_) => {
                 log::warn!(
-                    "Listing tools timed out after {} seconds",
-                    MCP_TOOL_CALL_TIMEOUT.as_secs()
+                    "Listing tools of {} timed out after {:?}",
+                    server_name,
+                    list_timeout
                 );
                 continue; // Skip this server and continue with others
             }
@@ -228,6 +307,7 @@ pub async fn get_tools(state: State<'_, AppState>) -> Result<Vec<ToolWithServer>
                 description: tool.description.as_ref().map(|d| d.to_string()),
                 input_schema: serde_json::Value::Object((*tool.input_schema).clone()),
                 server: server_name.clone(),
+                qualified_name: qualified_tool_name(&server_name, &tool.name),
             });
         }
     }
@@ -235,11 +315,131 @@ pub async fn get_tools(state: State<'_, AppState>) -> Result<Vec<ToolWithServer>
     Ok(all_tools)
 }
 
+/// Fetches the tool list of one server, or of all connected servers, into the tool cache
+/// and notifies the frontend with `mcp-update`
+#[tauri::command]
+pub async fn refresh_mcp_tools(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    server: Option<String>,
+) -> Result<(), String> {
+    let server_names: Vec<String> = {
+        let servers = state.mcp_servers.lock().await;
+        match server {
+            Some(name) if servers.contains_key(&name) => vec![name],
+            Some(name) => return Err(format!("Server {} not found", name)),
+            None => servers.keys().cloned().collect(),
+        }
+    };
+
+    for name in &server_names {
+        // A failed fetch leaves no entry, so the next tool call lists the tools again
+        state.mcp_tool_cache.lock().await.remove(name);
+        precache_server_tools(&app, name).await;
+    }
+
+    app.emit(MCP_UPDATE_EVENT, "MCP servers updated")
+        .map_err(|e| format!("Failed to emit event: {}", e))
+}
+
+/// Retrieves all resources exposed by the connected MCP servers with server information.
+/// Servers that do not support resources or time out are skipped.
+#[tauri::command]
+pub async fn list_mcp_resources(
+    state: State<'_, AppState>,
+) -> Result<Vec<ResourceWithServer>, String> {
+    let configs = state.mcp_active_servers.lock().await.clone();
+    let peers = server_peers(&state.mcp_servers).await;
+    Ok(list_server_resources(&peers, &configs).await)
+}
+
+/// Reads a resource from the given MCP server
+#[tauri::command]
+pub async fn read_mcp_resource(
+    state: State<'_, AppState>,
+    server: String,
+    uri: String,
+) -> Result<ReadResourceResult, String> {
+    let call_timeout = server_timeouts(&*state.mcp_active_servers.lock().await, &server).call;
+    let peer = server_peer(&state.mcp_servers, &server).await?;
+    request_with_timeout(
+        call_timeout,
+        &format!("Reading resource {}", uri),
+        peer.read_resource(ReadResourceRequestParam { uri: uri.clone() }),
+    )
+    .await
+}
+
+/// Subscribes to updates of a resource; changes are emitted as `mcp-resource-updated`
+#[tauri::command]
+pub async fn subscribe_mcp_resource(
+    state: State<'_, AppState>,
+    server: String,
+    uri: String,
+) -> Result<(), String> {
+    let call_timeout = server_timeouts(&*state.mcp_active_servers.lock().await, &server).call;
+    let peer = server_peer(&state.mcp_servers, &server).await?;
+    request_with_timeout(
+        call_timeout,
+        &format!("Subscribing to resource {}", uri),
+        peer.subscribe(SubscribeRequestParam { uri: uri.clone() }),
+    )
+    .await
+}
+
+/// Stops receiving updates of a resource
+#[tauri::command]
+pub async fn unsubscribe_mcp_resource(
+    state: State<'_, AppState>,
+    server: String,
+    uri: String,
+) -> Result<(), String> {
+    let call_timeout = server_timeouts(&*state.mcp_active_servers.lock().await, &server).call;
+    let peer = server_peer(&state.mcp_servers, &server).await?;
+    request_with_timeout(
+        call_timeout,
+        &format!("Unsubscribing from resource {}", uri),
+        peer.unsubscribe(UnsubscribeRequestParam { uri: uri.clone() }),
+    )
+    .await
+}
+
+/// Retrieves all prompts exposed by the connected MCP servers with server information.
+/// Servers that do not support prompts or time out are skipped.
+#[tauri::command]
+pub async fn list_mcp_prompts(state: State<'_, AppState>) -> Result<Vec<PromptWithServer>, String> {
+    let configs = state.mcp_active_servers.lock().await.clone();
+    let peers = server_peers(&state.mcp_servers).await;
+    Ok(list_server_prompts(&peers, &configs).await)
+}
+
+/// Renders a prompt of the given MCP server with optional arguments
+#[tauri::command]
+pub async fn get_mcp_prompt(
+    state: State<'_, AppState>,
+    server: String,
+    name: String,
+    arguments: Option<Map<String, Value>>,
+) -> Result<GetPromptResult, String> {
+    let call_timeout = server_timeouts(&*state.mcp_active_servers.lock().await, &server).call;
+    let peer = server_peer(&state.mcp_servers, &server).await?;
+    let params = GetPromptRequestParam {
+        name: name.clone(),
+        arguments,
+    };
+    request_with_timeout(
+        call_timeout,
+        &format!("Getting prompt {}", name),
+        peer.get_prompt(params),
+    )
+    .await
+}
+
 /// Calls a tool on an MCP server by name with optional arguments
 ///
 /// # Arguments
 /// * `state` - Application state containing MCP server connections
-/// * `tool_name` - Name of the tool to call
+/// * `tool_name` - Name of the tool to call, either bare or qualified as `server__tool`
 /// * `arguments` - Optional map of argument names to values
 /// * `cancellation_token` - Optional token to allow cancellation from JS side
 ///
@@ -248,18 +448,76 @@ pub async fn get_tools(state: State<'_, AppState>) -> Result<Vec<ToolWithServer>
 ///
 /// This function:
 /// 1. Locks the MCP servers mutex to access server connections
-/// 2. Searches through all servers for one containing the named tool
+/// 2. Searches the servers in priority order for one containing the named tool, or only
+///    the named server for qualified names
 /// 3. When found, calls the tool on that server with the provided arguments
 /// 4. If the call fails with a transport error, triggers reconnection and retries once
 /// 5. Supports cancellation via cancellation_token
 /// 6. Returns error if no server has the requested tool
+///
+/// Before dispatching, the tool policy may deny the call or hold it until the user
+/// approves it through `approve_tool_call`.
+///
+/// Every call, including rejected ones, is recorded in the tool call log.
 #[tauri::command]
-pub async fn call_tool(
-    app: AppHandle,
+pub async fn call_tool<R: Runtime>(
+    app: AppHandle<R>,
     state: State<'_, AppState>,
     tool_name: String,
     arguments: Option<Map<String, Value>>,
     cancellation_token: Option<String>,
+) -> Result<CallToolResult, String> {
+    let mut trace = ToolCallTrace::default();
+    let mut started = Instant::now();
+    let result = match check_tool_policy(
+        &app,
+        &state,
+        &tool_name,
+        &arguments,
+        cancellation_token.as_deref(),
+    )
+    .await
+    {
+        Ok(()) => {
+            // Time spent waiting for approval is not part of the call
+            started = Instant::now();
+            dispatch_tool_call(
+                &app,
+                &state,
+                &tool_name,
+                &arguments,
+                &cancellation_token,
+                &mut trace,
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+
+    let duration = started.elapsed();
+    if let (Some(server), false) = (&trace.server, trace.cancelled) {
+        status::record_call(&state.mcp_server_health, server, duration).await;
+    }
+    let entry = ToolCallLogEntry::new(
+        &tool_name,
+        &arguments,
+        cancellation_token,
+        trace,
+        duration,
+        &result,
+    );
+    let log_dir = get_jan_data_folder_path(app.clone()).join("logs");
+    state.mcp_tool_call_log.append(&log_dir, &entry).await;
+    result
+}
+
+async fn dispatch_tool_call<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &State<'_, AppState>,
+    tool_name: &str,
+    arguments: &Option<Map<String, Value>>,
+    cancellation_token: &Option<String>,
+    trace: &mut ToolCallTrace,
 ) -> Result<CallToolResult, String> {
     // Use a loop to handle retry without recursion (Rust async recursion requires boxing)
     let max_attempts = 2; // Initial attempt + 1 retry
@@ -272,9 +530,16 @@ pub async fn call_tool(
 
         // If we need to reconnect from a previous failed attempt
         if let Some(server_name) = reconnect_server.take() {
-            log::info!("Attempting reconnection for server {} before retry", server_name);
-            if let Err(reconnect_err) = schedule_mcp_reconnect(&app, &state, &server_name).await {
-                log::error!("Failed to reconnect MCP server {}: {}", server_name, reconnect_err);
+            log::info!(
+                "Attempting reconnection for server {} before retry",
+                server_name
+            );
+            if let Err(reconnect_err) = schedule_mcp_reconnect(app, state, &server_name).await {
+                log::error!(
+                    "Failed to reconnect MCP server {}: {}",
+                    server_name,
+                    reconnect_err
+                );
                 return Err(format!(
                     "Error calling tool {}: {} (reconnection failed: {})",
                     tool_name,
@@ -286,7 +551,7 @@ pub async fn call_tool(
         }
 
         // Set up cancellation if token is provided (only on first attempt)
-        let cancel_rx = if attempt == 1 {
+        let mut cancel_rx = if attempt == 1 {
             if let Some(token) = &cancellation_token {
                 let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
                 let mut cancellations = state.tool_call_cancellations.lock().await;
@@ -299,14 +564,35 @@ pub async fn call_tool(
             None
         };
 
-        let servers = state.mcp_servers.lock().await;
-        log::info!("Attempt {}: servers in map: {:?}", attempt, servers.keys().collect::<Vec<_>>());
+        let configs = state.mcp_active_servers.lock().await.clone();
+        let (target_server, bare_name, candidates) = {
+            let servers = state.mcp_servers.lock().await;
+            log::info!(
+                "Attempt {}: servers in map: {:?}",
+                attempt,
+                servers.keys().collect::<Vec<_>>()
+            );
+
+            // A qualified name pins the server, a bare name goes to the highest priority server
+            let (target_server, bare_name) = split_qualified_tool_name(tool_name, servers.keys());
+            let names = match &target_server {
+                Some(server) => vec![server.clone()],
+                None => order_servers_by_priority(servers.keys().cloned(), &configs),
+            };
+            // Peers are cloned so the server map is not locked while listing or calling
+            let candidates: Vec<(String, Peer<RoleClient>)> = names
+                .into_iter()
+                .filter_map(|name| servers.get(&name).map(|service| (name, service.peer())))
+                .collect();
+            (target_server, bare_name, candidates)
+        };
 
         // Iterate through servers and find the first one that contains the tool
-        for (server_name, service) in servers.iter() {
-            log::info!("Checking server {} for tool {}", server_name, tool_name);
+        for (server_name, peer) in &candidates {
+            let timeouts = server_timeouts(&configs, server_name);
+            log::info!("Checking server {} for tool {}", server_name, bare_name);
 
-            // Check cache first for this server's tools (permanent cache, never expires)
+            // Check cache first for this server's tools
             let cached_tools = {
                 let cache = state.mcp_tool_cache.lock().await;
                 cache.get(server_name).cloned()
@@ -316,23 +602,29 @@ pub async fn call_tool(
                 log::debug!("Using cached tool list for server {} ({} tools)", server_name, cached.len());
                 cached
             } else {
-                // First time: fetch and cache permanently
-                match service.list_all_tools().await {
+                // Not cached yet or invalidated: fetch and cache
+                let listed = match timeout(timeouts.list, peer.list_all_tools()).await {
+                    Ok(result) => result.map_err(|e| e.to_string()),
+                    Err(_) => Err(format!("Listing tools timed out after {:?}", timeouts.list)),
+                };
+                match listed {
                     Ok(tools) => {
                         let mut cache = state.mcp_tool_cache.lock().await;
                         cache.insert(server_name.clone(), tools.clone());
-                        log::info!("Cached {} tools for server {} (permanent)", tools.len(), server_name);
+                        log::info!("Cached {} tools for server {}", tools.len(), server_name);
                         tools
                     }
-                    Err(e) => {
-                        let err_str = e.to_string();
-                        log::warn!("Failed to list tools from server {}: {}", server_name, err_str);
+                    Err(err_str) => {
+                        log::warn!(
+                            "Failed to list tools from server {}: {}",
+                            server_name,
+                            err_str
+                        );
                         // If this looks like a transport error and we can retry, schedule reconnect
                         if attempt < max_attempts && is_transport_error(&err_str) {
                             log::info!("Transport error detected for server {}, will attempt reconnection", server_name);
                             reconnect_server = Some(server_name.clone());
                             last_error = Some(err_str);
-                            drop(servers); // Release lock before reconnect loop
                             break; // Break inner loop to trigger reconnect in outer loop
                         }
                         continue; // Skip this server if we can't list tools
@@ -340,54 +632,79 @@ pub async fn call_tool(
                 }
             };
 
-            if !tools.iter().any(|t| t.name == tool_name) {
+            let Some(tool) = tools.iter().find(|t| t.name == bare_name) else {
                 continue; // Tool not found in this server, try next
-            }
+            };
 
-            log::info!("Found tool {} in server {}", tool_name, server_name);
+            log::info!("Found tool {} in server {}", bare_name, server_name);
+            trace.server = Some(server_name.clone());
 
             // Clone for potential retry
-            let arguments_clone = arguments.clone();
+            let mut arguments_clone = arguments.clone();
+
+            // Bad arguments go back to the caller instead of to the server
+            let coerce = configs
+                .get(server_name)
+                .map_or(true, extract_coerce_arguments);
+            if let Err(invalid) = validate_tool_arguments(tool, &mut arguments_clone, coerce) {
+                if let Some(token) = &cancellation_token {
+                    state.tool_call_cancellations.lock().await.remove(token);
+                }
+                return Ok(invalid);
+            }
             let server_name_for_retry = server_name.clone();
 
-            // Call the tool with timeout and cancellation support
-            let tool_call = service.call_tool(CallToolRequestParam {
-                name: tool_name.clone().into(),
+            // Call the tool with its timeout and cancellation support
+            let call_timeout = timeouts.for_tool(&bare_name);
+            let params = CallToolRequestParam {
+                name: bare_name.clone().into(),
                 arguments: arguments_clone,
-            });
+            };
+            // Calls with a cancellation token report their progress under it
+            if let Some(token) = &cancellation_token {
+                state.mcp_tool_calls_in_flight.lock().await.insert(
+                    token.clone(),
+                    InFlightToolCall {
+                        server: server_name.clone(),
+                        tool: bare_name.clone(),
+                    },
+                );
+            }
 
-            // Race between timeout, tool call, and cancellation
-            let result = if let Some(cancel_rx) = cancel_rx {
-                tokio::select! {
-                    result = timeout(MCP_TOOL_CALL_TIMEOUT, tool_call) => {
-                        match result {
-                            Ok(call_result) => call_result.map_err(|e| e.to_string()),
-                            Err(_) => Err(format!(
-                                "Tool call '{}' timed out after {} seconds",
-                                tool_name,
-                                MCP_TOOL_CALL_TIMEOUT.as_secs()
-                            )),
+            // Race between timeout, tool call, and cancellation. A call we stop waiting for
+            // is cancelled on the server too, by its request id.
+            let result = match send_tool_call(peer, params, cancellation_token.as_deref()).await {
+                Ok(handle) => {
+                    let (peer, request_id) = (handle.peer.clone(), handle.id.clone());
+                    tokio::select! {
+                        result = timeout(call_timeout, tool_call_result(handle)) => {
+                            match result {
+                                Ok(call_result) => call_result.map_err(|e| e.to_string()),
+                                Err(_) => {
+                                    cancel_mcp_request(&peer, request_id, "timed out").await;
+                                    Err(format!(
+                                        "Tool call '{}' timed out after {:?}",
+                                        tool_name,
+                                        call_timeout
+                                    ))
+                                }
+                            }
+                        }
+                        _ = cancelled(cancel_rx.take()) => {
+                            trace.cancelled = true;
+                            cancel_mcp_request(&peer, request_id, "cancelled by the user").await;
+                            Err(format!("Tool call '{}' was cancelled", tool_name))
                         }
                     }
-                    _ = cancel_rx => {
-                        Err(format!("Tool call '{}' was cancelled", tool_name))
-                    }
-                }
-            } else {
-                match timeout(MCP_TOOL_CALL_TIMEOUT, tool_call).await {
-                    Ok(call_result) => call_result.map_err(|e| e.to_string()),
-                    Err(_) => Err(format!(
-                        "Tool call '{}' timed out after {} seconds",
-                        tool_name,
-                        MCP_TOOL_CALL_TIMEOUT.as_secs()
-                    )),
                 }
+                Err(e) => Err(e.to_string()),
             };
 
             // Clean up cancellation token
             if let Some(token) = &cancellation_token {
                 let mut cancellations = state.tool_call_cancellations.lock().await;
                 cancellations.remove(token);
+                state.mcp_tool_calls_in_flight.lock().await.remove(token);
             }
 
             // Check if result is a transport error that warrants retry
@@ -396,7 +713,6 @@ pub async fn call_tool(
                     log::info!("Transport error on tool call, will attempt reconnection for server {}", server_name_for_retry);
                     reconnect_server = Some(server_name_for_retry);
                     last_error = Some(err.clone());
-                    drop(servers); // Release lock before reconnect loop
                     break; // Break inner loop to trigger reconnect in outer loop
                 }
             }
@@ -405,17 +721,43 @@ pub async fn call_tool(
         }
 
         // If no reconnect scheduled (tool not found via MCP), try HTTP fallback
+        let builtin_target = target_server
+            .as_deref()
+            .map_or(true, |server| server == "salesboxai-builtin");
         if reconnect_server.is_none() {
             // Try HTTP fallback for built-in tools before giving up
-            log::info!("Tool {} not found via MCP, attempting HTTP fallback", tool_name);
-            match http_fallback_tool_call(&tool_name, &arguments, &state).await {
-                Ok(result) => {
-                    log::info!("HTTP fallback succeeded for tool {}", tool_name);
-                    return Ok(result);
+            if builtin_target {
+                log::info!(
+                    "Tool {} not found via MCP, attempting HTTP fallback",
+                    tool_name
+                );
+                trace.server = Some("salesboxai-builtin".to_string());
+                trace.http_fallback = true;
+                // Dropping the request future on cancellation aborts the HTTP request
+                let fallback = tokio::select! {
+                    result = http_fallback_tool_call(&bare_name, arguments, state) => result,
+                    _ = cancelled(cancel_rx.take()) => {
+                        trace.cancelled = true;
+                        Err(format!("Tool call '{}' was cancelled", tool_name))
+                    }
+                };
+                if let Some(token) = &cancellation_token {
+                    state.tool_call_cancellations.lock().await.remove(token);
                 }
-                Err(fallback_err) => {
-                    log::warn!("HTTP fallback also failed for tool {}: {}", tool_name, fallback_err);
-                    // Continue to error below
+                match fallback {
+                    Ok(result) => {
+                        log::info!("HTTP fallback succeeded for tool {}", tool_name);
+                        return Ok(result);
+                    }
+                    Err(e) if trace.cancelled => return Err(e),
+                    Err(fallback_err) => {
+                        log::warn!(
+                            "HTTP fallback also failed for tool {}: {}",
+                            tool_name,
+                            fallback_err
+                        );
+                        // Continue to error below
+                    }
                 }
             }
             break;
@@ -425,6 +767,86 @@ pub async fn call_tool(
     Err(format!("Tool {} not found", tool_name))
 }
 
+/// Resolves once the call is cancelled, never for calls without a cancellation token
+async fn cancelled(cancel_rx: Option<oneshot::Receiver<()>>) {
+    match cancel_rx {
+        Some(cancel_rx) => {
+            let _ = cancel_rx.await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Server a tool call would be dispatched to according to the tool cache, and the bare
+/// tool name
+async fn find_tool_server(state: &AppState, tool_name: &str) -> (Option<String>, String) {
+    let configs = state.mcp_active_servers.lock().await.clone();
+    let server_names: Vec<String> = state.mcp_servers.lock().await.keys().cloned().collect();
+    let (target_server, tool) = split_qualified_tool_name(tool_name, &server_names);
+    if target_server.is_some() {
+        return (target_server, tool);
+    }
+
+    let cache = state.mcp_tool_cache.lock().await;
+    let server = order_servers_by_priority(server_names, &configs)
+        .into_iter()
+        .find(|server| {
+            cache
+                .get(server)
+                .is_some_and(|tools| tools.iter().any(|t| t.name == tool))
+        })
+        .or_else(|| get_http_fallback_endpoint(&tool).map(|_| "salesboxai-builtin".to_string()));
+    (server, tool)
+}
+
+/// Applies the tool policy, waiting for the user's decision when the call needs approval
+async fn check_tool_policy<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &AppState,
+    tool_name: &str,
+    arguments: &Option<Map<String, Value>>,
+    cancellation_token: Option<&str>,
+) -> Result<(), String> {
+    let (server, tool) = find_tool_server(state, tool_name).await;
+    let policy = load_policy(&get_jan_data_folder_path(app.clone()));
+
+    match evaluate_policy(&policy, server.as_deref(), &tool, arguments.as_ref()) {
+        ToolPolicyAction::Allow => Ok(()),
+        ToolPolicyAction::Deny => {
+            log::warn!("Tool call {} blocked by the tool policy", tool_name);
+            Err(format!(
+                "Tool call '{}' is blocked by the tool policy",
+                tool_name
+            ))
+        }
+        ToolPolicyAction::Ask => {
+            let request = ToolApprovalRequest {
+                // Reusing the cancellation token lets cancel_tool_call withdraw the request
+                id: cancellation_token
+                    .map(str::to_string)
+                    .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+                server,
+                tool,
+                arguments: arguments.clone(),
+                requested_at: chrono::Utc::now().to_rfc3339(),
+            };
+            let decision = request_approval(app, &state.mcp_pending_approvals, request).await;
+            if decision.approved {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Tool call '{}' was rejected{}",
+                    tool_name,
+                    decision
+                        .reason
+                        .map(|reason| format!(": {}", reason))
+                        .unwrap_or_default()
+                ))
+            }
+        }
+    }
+}
+
 /// Check if an error string indicates a transport/connection error
 fn is_transport_error(error: &str) -> bool {
     let transport_indicators = [
@@ -579,6 +1001,9 @@ async fn http_fallback_tool_call(
 
 /// Cancels a running tool call by its cancellation token
 ///
+/// MCP servers receive `notifications/cancelled` for the call so they stop running the
+/// tool, and HTTP fallback requests are aborted.
+///
 /// # Arguments
 /// * `state` - Application state containing cancellation tokens
 /// * `cancellation_token` - Token identifying the tool call to cancel
@@ -595,13 +1020,134 @@ pub async fn cancel_tool_call(
     if let Some(cancel_tx) = cancellations.remove(&cancellation_token) {
         // Send cancellation signal - ignore if receiver is already dropped
         let _ = cancel_tx.send(());
-        println!("Tool call with token {} cancelled", cancellation_token);
+        log::info!("Tool call with token {} cancelled", cancellation_token);
+        Ok(())
+    } else if resolve_approval(
+        &state.mcp_pending_approvals,
+        &cancellation_token,
+        ApprovalDecision {
+            approved: false,
+            reason: Some("cancelled".to_string()),
+        },
+    )
+    .await
+    .is_ok()
+    {
+        log::info!(
+            "Tool call with token {} cancelled while waiting for approval",
+            cancellation_token
+        );
         Ok(())
     } else {
         Err(format!("Cancellation token {} not found", cancellation_token))
     }
 }
 
+/// Reads the tool call log, newest entries first
+#[tauri::command]
+pub async fn list_tool_calls(
+    app: AppHandle,
+    query: Option<ToolCallLogQuery>,
+) -> Result<Vec<ToolCallLogEntry>, String> {
+    let log_dir = get_jan_data_folder_path(app).join("logs");
+    let query = query.unwrap_or_default();
+    tokio::task::spawn_blocking(move || read_tool_call_log(&log_dir, &query))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Lists the tool calls waiting for approval
+#[tauri::command]
+pub async fn list_pending_tool_calls(
+    state: State<'_, AppState>,
+) -> Result<Vec<ToolApprovalRequest>, String> {
+    let pending = state.mcp_pending_approvals.lock().await;
+    let mut requests: Vec<ToolApprovalRequest> =
+        pending.values().map(|p| p.request.clone()).collect();
+    requests.sort_by(|a, b| a.requested_at.cmp(&b.requested_at));
+    Ok(requests)
+}
+
+/// Lets a pending tool call run. With `always`, the tool is allowed on its server from now on.
+#[tauri::command]
+pub async fn approve_tool_call(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+    always: Option<bool>,
+) -> Result<(), String> {
+    let request = resolve_approval(
+        &state.mcp_pending_approvals,
+        &id,
+        ApprovalDecision {
+            approved: true,
+            reason: None,
+        },
+    )
+    .await?;
+    if always.unwrap_or(false) {
+        remember_tool_decision(&app, &request, ToolPolicyAction::Allow)?;
+    }
+    Ok(())
+}
+
+/// Rejects a pending tool call. With `always`, the tool is denied on its server from now on.
+#[tauri::command]
+pub async fn reject_tool_call(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+    reason: Option<String>,
+    always: Option<bool>,
+) -> Result<(), String> {
+    let request = resolve_approval(
+        &state.mcp_pending_approvals,
+        &id,
+        ApprovalDecision {
+            approved: false,
+            reason,
+        },
+    )
+    .await?;
+    if always.unwrap_or(false) {
+        remember_tool_decision(&app, &request, ToolPolicyAction::Deny)?;
+    }
+    Ok(())
+}
+
+fn remember_tool_decision(
+    app: &AppHandle,
+    request: &ToolApprovalRequest,
+    action: ToolPolicyAction,
+) -> Result<(), String> {
+    let server = request.server.as_deref().ok_or_else(|| {
+        format!(
+            "Cannot remember a decision for {}: no server provides it",
+            request.tool
+        )
+    })?;
+    let data_dir = get_jan_data_folder_path(app.clone());
+    let mut policy = load_policy(&data_dir);
+    remember_decision(&mut policy, server, &request.tool, action);
+    log::info!(
+        "Tool policy now has {:?} for {} on {}",
+        action,
+        request.tool,
+        server
+    );
+    save_policy(&data_dir, &policy)
+}
+
+#[tauri::command]
+pub async fn get_mcp_tool_policy(app: AppHandle) -> Result<ToolPolicy, String> {
+    Ok(load_policy(&get_jan_data_folder_path(app)))
+}
+
+#[tauri::command]
+pub async fn save_mcp_tool_policy(app: AppHandle, policy: ToolPolicy) -> Result<(), String> {
+    save_policy(&get_jan_data_folder_path(app), &policy)
+}
+
 #[tauri::command]
 pub async fn get_mcp_configs(app: AppHandle) -> Result<String, String> {
     let mut path = get_jan_data_folder_path(app);
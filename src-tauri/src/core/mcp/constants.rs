@@ -1,11 +1,52 @@
 use std::time::Duration;
 
 // MCP Constants
+// Default timeouts, overridable per server and per tool with `timeouts` in mcp_config.json
 pub const MCP_TOOL_CALL_TIMEOUT: Duration = Duration::from_secs(120); // 2 minutes
+pub const MCP_LIST_TIMEOUT: Duration = Duration::from_secs(120); // 2 minutes
 pub const MCP_BASE_RESTART_DELAY_MS: u64 = 10000; // Start with 10 seconds (core restart takes 45+ seconds)
 pub const MCP_MAX_RESTART_DELAY_MS: u64 = 60000; // Cap at 60 seconds
 pub const MCP_BACKOFF_MULTIPLIER: f64 = 1.5; // 1.5x delay each time (10s -> 15s -> 22s -> 33s -> 50s)
 
+/// Separates the server from the tool in qualified tool names (`server__tool`)
+pub const MCP_TOOL_NAME_SEPARATOR: &str = "__";
+pub const MCP_TOOL_COLLISION_EVENT: &str = "mcp-tool-collision";
+pub const MCP_UPDATE_EVENT: &str = "mcp-update";
+
+// Tool call approval
+pub const MCP_TOOL_POLICY_FILE: &str = "mcp_tool_policy.json";
+pub const MCP_TOOL_APPROVAL_TIMEOUT: Duration = Duration::from_secs(600); // 10 minutes
+pub const MCP_TOOL_APPROVAL_REQUESTED_EVENT: &str = "mcp-tool-approval-requested";
+pub const MCP_TOOL_APPROVAL_RESOLVED_EVENT: &str = "mcp-tool-approval-resolved";
+
+/// Lines of stderr kept per server for the status view
+pub const MCP_STDERR_TAIL_LINES: usize = 50;
+
+// Tool call log, written to the `logs` folder of the data folder
+pub const MCP_TOOL_CALL_LOG_FILE: &str = "mcp_tool_calls.jsonl";
+pub const MCP_TOOL_CALL_LOG_DEFAULT_LIMIT: usize = 200;
+pub const MCP_TOOL_CALL_LOG_MAX_FILE_BYTES: u64 = 10 * 1024 * 1024; // 10 MB per file
+pub const MCP_TOOL_CALL_LOG_MAX_FILES: usize = 5;
+pub const MCP_TOOL_CALL_REDACT_FIELDS: &[&str] = &[
+    "api_key",
+    "apiKey",
+    "authorization",
+    "password",
+    "secret",
+    "token",
+    "accessToken",
+    "cookie",
+];
+
+// Events forwarded from MCP server notifications
+pub const MCP_RESOURCE_UPDATED_EVENT: &str = "mcp-resource-updated";
+pub const MCP_RESOURCES_CHANGED_EVENT: &str = "mcp-resources-changed";
+pub const MCP_PROMPTS_CHANGED_EVENT: &str = "mcp-prompts-changed";
+/// Progress of a tool call, keyed by its cancellation token
+pub const MCP_TOOL_PROGRESS_EVENT: &str = "mcp-tool-progress";
+/// Server log messages, keyed by the cancellation token of the call they most likely belong to
+pub const MCP_TOOL_LOG_EVENT: &str = "mcp-tool-log";
+
 pub const DEFAULT_MCP_CONFIG: &str = r#"{
   "mcpServers": {
     "browsermcp": {
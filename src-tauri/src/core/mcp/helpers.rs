@@ -1,13 +1,24 @@
 use rmcp::{
-    model::{ClientCapabilities, ClientInfo, Implementation},
+    model::{
+        CallToolResult, CancelledNotificationParam, ClientCapabilities, ClientInfo, Content,
+        Implementation, RequestId, Tool,
+    },
+    service::Peer,
     transport::{
         streamable_http_client::StreamableHttpClientTransportConfig, SseClientTransport,
         StreamableHttpClientTransport, TokioChildProcess,
     },
-    ServiceExt,
+    RoleClient, ServiceExt,
+};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    future::Future,
+    process::Stdio,
+    sync::Arc,
+    time::Duration,
 };
-use serde_json::Value;
-use std::{collections::HashMap, env, process::Stdio, sync::Arc, time::Duration};
 use tauri::{AppHandle, Emitter, Manager, Runtime, State};
 use tauri_plugin_http::reqwest;
 use tokio::{
@@ -19,13 +30,46 @@ use tokio::{
 
 use super::constants::{
     MCP_BACKOFF_MULTIPLIER, MCP_BASE_RESTART_DELAY_MS, MCP_MAX_RESTART_DELAY_MS,
+    MCP_TOOL_COLLISION_EVENT, MCP_TOOL_NAME_SEPARATOR,
 };
 use crate::core::{
     app::commands::get_jan_data_folder_path,
-    mcp::models::McpServerConfig,
+    mcp::{
+        client::McpClientHandler,
+        models::{
+            McpServerConfig, McpServerState, McpTimeouts, PromptWithServer, ResourceWithServer,
+            ToolCollision,
+        },
+        status::{self, ServerHealthMap},
+    },
     state::{AppState, RunningServiceEnum, SharedMcpServers},
 };
-use jan_utils::can_override_npx;
+use jan_utils::{can_override_npx, coerce_json_types, validate_json_schema};
+
+/// Builds the client handler for a server, emitting its notifications through the app
+fn client_handler<R: Runtime>(
+    app: &AppHandle<R>,
+    name: &str,
+    info: ClientInfo,
+) -> McpClientHandler {
+    let app = app.clone();
+    let state = app.state::<AppState>();
+    let (tool_cache, tool_calls) = (
+        state.mcp_tool_cache.clone(),
+        state.mcp_tool_calls_in_flight.clone(),
+    );
+    McpClientHandler::new(
+        name,
+        info,
+        Arc::new(move |event: &str, payload: Value| {
+            if let Err(e) = app.emit(event, payload) {
+                log::warn!("Failed to emit {}: {}", event, e);
+            }
+        }),
+        tool_cache,
+        tool_calls,
+    )
+}
 
 /// Pre-cache tools for a server immediately after connection.
 /// This ensures the tool cache is populated before any tool calls are made.
@@ -45,15 +89,25 @@ pub async fn precache_server_tools<R: Runtime>(
     }
 
     // Fetch tools from the server
+    let list_timeout =
+        server_timeouts(&*app_state.mcp_active_servers.lock().await, server_name).list;
     let tools = {
         let servers = app_state.mcp_servers.lock().await;
         if let Some(service) = servers.get(server_name) {
-            match service.list_all_tools().await {
-                Ok(tools) => Some(tools),
-                Err(e) => {
+            match timeout(list_timeout, service.list_all_tools()).await {
+                Ok(Ok(tools)) => Some(tools),
+                Ok(Err(e)) => {
                     log::warn!("Failed to precache tools for server {}: {}", server_name, e);
                     None
                 }
+                Err(_) => {
+                    log::warn!(
+                        "Precaching tools for server {} timed out after {:?}",
+                        server_name,
+                        list_timeout
+                    );
+                    None
+                }
             }
         } else {
             None
@@ -62,12 +116,104 @@ pub async fn precache_server_tools<R: Runtime>(
 
     // Cache the tools if we got them
     if let Some(tools) = tools {
-        let mut cache = app_state.mcp_tool_cache.lock().await;
-        log::info!("Pre-cached {} tools for server {}", tools.len(), server_name);
-        cache.insert(server_name.to_string(), tools);
+        let configs = app_state.mcp_active_servers.lock().await.clone();
+        let collisions: Vec<ToolCollision> = {
+            let mut cache = app_state.mcp_tool_cache.lock().await;
+            log::info!(
+                "Pre-cached {} tools for server {}",
+                tools.len(),
+                server_name
+            );
+            cache.insert(server_name.to_string(), tools);
+            find_tool_collisions(&cache, &configs)
+                .into_iter()
+                .filter(|collision| collision.servers.iter().any(|s| s == server_name))
+                .collect()
+        };
+
+        if !collisions.is_empty() {
+            for collision in &collisions {
+                log::warn!(
+                    "Tool {} is exposed by servers {:?}; unqualified calls go to {}",
+                    collision.name,
+                    collision.servers,
+                    collision.servers[0]
+                );
+            }
+            if let Err(e) = app.emit(
+                MCP_TOOL_COLLISION_EVENT,
+                json!({ "server": server_name, "collisions": collisions }),
+            ) {
+                log::warn!("Failed to emit {}: {}", MCP_TOOL_COLLISION_EVENT, e);
+            }
+        }
     }
 }
 
+/// Builds the `server__tool` name of a tool
+pub fn qualified_tool_name(server: &str, tool: &str) -> String {
+    format!("{}{}{}", server, MCP_TOOL_NAME_SEPARATOR, tool)
+}
+
+/// Splits a qualified tool name into server and tool. Names that do not start with
+/// a known server are returned as they are.
+pub fn split_qualified_tool_name<S: AsRef<str>>(
+    name: &str,
+    servers: impl IntoIterator<Item = S>,
+) -> (Option<String>, String) {
+    servers
+        .into_iter()
+        .filter_map(|server| {
+            let server = server.as_ref();
+            let tool = name
+                .strip_prefix(server)?
+                .strip_prefix(MCP_TOOL_NAME_SEPARATOR)?;
+            (!tool.is_empty()).then(|| (server.to_string(), tool.to_string()))
+        })
+        // Prefer the longest match when one server name prefixes another
+        .max_by_key(|(server, _)| server.len())
+        .map_or((None, name.to_string()), |(server, tool)| {
+            (Some(server), tool)
+        })
+}
+
+/// Orders servers for tool dispatch: higher `priority` first, then by name
+pub fn order_servers_by_priority(
+    servers: impl IntoIterator<Item = String>,
+    configs: &HashMap<String, Value>,
+) -> Vec<String> {
+    let mut servers: Vec<(i64, String)> = servers
+        .into_iter()
+        .map(|name| (configs.get(&name).map_or(0, extract_priority), name))
+        .collect();
+    servers.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    servers.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Tool names exposed by more than one server, with the servers in dispatch order
+pub fn find_tool_collisions(
+    tools: &HashMap<String, Vec<Tool>>,
+    configs: &HashMap<String, Value>,
+) -> Vec<ToolCollision> {
+    let mut owners: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (server, server_tools) in tools {
+        for tool in server_tools {
+            let servers = owners.entry(tool.name.to_string()).or_default();
+            if !servers.contains(server) {
+                servers.push(server.clone());
+            }
+        }
+    }
+    owners
+        .into_iter()
+        .filter(|(_, servers)| servers.len() > 1)
+        .map(|(name, servers)| ToolCollision {
+            name,
+            servers: order_servers_by_priority(servers, configs),
+        })
+        .collect()
+}
+
 /// Calculate exponential backoff delay with jitter
 ///
 /// # Arguments
@@ -224,6 +370,7 @@ pub async fn run_mcp_commands<R: Runtime>(
 pub async fn monitor_mcp_server_handle(
     servers_state: SharedMcpServers,
     name: String,
+    health: ServerHealthMap,
 ) -> Option<rmcp::service::QuitReason> {
     log::info!("Monitoring MCP server {} health", name);
 
@@ -233,22 +380,22 @@ pub async fn monitor_mcp_server_handle(
         sleep(Duration::from_secs(15)).await;
 
         // Check if server is still healthy by trying to list tools
-        let health_check_result = {
+        let health_check_error = {
             let servers = servers_state.lock().await;
             if let Some(service) = servers.get(&name) {
                 // Try to list tools as a health check with a longer timeout to allow for concurrent tool calls
                 match timeout(Duration::from_secs(45), service.list_all_tools()).await {
                     Ok(Ok(_)) => {
                         // Server responded successfully
-                        true
+                        None
                     }
                     Ok(Err(e)) => {
                         log::warn!("MCP server {} health check failed: {}", name, e);
-                        false
+                        Some(format!("Health check failed: {}", e))
                     }
                     Err(_) => {
                         log::warn!("MCP server {} health check timed out", name);
-                        false
+                        Some("Health check timed out".to_string())
                     }
                 }
             } else {
@@ -258,8 +405,9 @@ pub async fn monitor_mcp_server_handle(
             }
         };
 
-        if !health_check_result {
+        if let Some(error) = health_check_error {
             // Server failed health check - remove it and return
+            status::mark_failed(&health, &name, &error).await;
             log::error!(
                 "MCP server {} failed health check, removing from active servers",
                 name
@@ -367,6 +515,7 @@ pub async fn start_restart_loop<R: Runtime>(
     restart_counts: Arc<Mutex<HashMap<String, u32>>>,
     successfully_connected: Arc<Mutex<HashMap<String, bool>>>,
 ) {
+    let health = app.state::<AppState>().mcp_server_health.clone();
     loop {
         let current_restart_count = {
             let mut counts = restart_counts.lock().await;
@@ -381,6 +530,7 @@ pub async fn start_restart_loop<R: Runtime>(
                 name,
                 max_restarts
             );
+            status::set_state(&health, &name, McpServerState::Failed).await;
             if let Err(e) = app.emit(
                 "mcp_max_restarts_reached",
                 serde_json::json!({
@@ -399,6 +549,7 @@ pub async fn start_restart_loop<R: Runtime>(
             current_restart_count,
             max_restarts
         );
+        status::set_state(&health, &name, McpServerState::Restarting).await;
 
         // Calculate exponential backoff delay
         let delay_ms = calculate_exponential_backoff_delay(current_restart_count);
@@ -454,7 +605,8 @@ pub async fn start_restart_loop<R: Runtime>(
 
                 // Monitor the server again
                 let quit_reason =
-                    monitor_mcp_server_handle(servers_state.clone(), name.clone()).await;
+                    monitor_mcp_server_handle(servers_state.clone(), name.clone(), health.clone())
+                        .await;
 
                 log::info!("MCP server {} quit with reason: {:?}", name, quit_reason);
 
@@ -513,11 +665,28 @@ pub async fn start_restart_loop<R: Runtime>(
     }
 }
 
+/// Connects a server, recording the attempt and its outcome in the server health
 async fn schedule_mcp_start_task<R: Runtime>(
     app: tauri::AppHandle<R>,
     servers: SharedMcpServers,
     name: String,
     config: Value,
+) -> Result<(), String> {
+    let health = app.state::<AppState>().mcp_server_health.clone();
+    status::mark_starting(&health, &name).await;
+    let result = connect_mcp_server(app, servers, name.clone(), config).await;
+    match &result {
+        Ok(()) => status::set_state(&health, &name, McpServerState::Connected).await,
+        Err(e) => status::mark_failed(&health, &name, e).await,
+    }
+    result
+}
+
+async fn connect_mcp_server<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    servers: SharedMcpServers,
+    name: String,
+    config: Value,
 ) -> Result<(), String> {
     let app_path = get_jan_data_folder_path(app.clone());
     let exe_path = env::current_exe().expect("Failed to get current exe path");
@@ -529,6 +698,13 @@ async fn schedule_mcp_start_task<R: Runtime>(
     let config_params = extract_command_args(&config)
         .ok_or_else(|| format!("Failed to extract command args from config for {name}"))?;
 
+    // A reconnected server may come back with different tools
+    app.state::<AppState>()
+        .mcp_tool_cache
+        .lock()
+        .await
+        .remove(&name);
+
     if config_params.transport_type.as_deref() == Some("http") && config_params.url.is_some() {
         let transport = StreamableHttpClientTransport::with_client(
             reqwest::Client::builder()
@@ -552,7 +728,7 @@ async fn schedule_mcp_start_task<R: Runtime>(
                     }
                     headers
                 })
-                .connect_timeout(config_params.timeout.unwrap_or(Duration::MAX))
+                .connect_timeout(config_params.timeouts.connect.unwrap_or(Duration::MAX))
                 .build()
                 .unwrap(),
             StreamableHttpClientTransportConfig {
@@ -569,8 +745,14 @@ async fn schedule_mcp_start_task<R: Runtime>(
                 version: "0.0.1".to_string(),
             },
         };
-        let client = client_info.serve(transport).await.inspect_err(|e| {
-            log::error!("client error: {:?}", e);
+        let client = with_connect_timeout(
+            &name,
+            config_params.timeouts.connect,
+            client_handler(&app, &name, client_info).serve(transport),
+        )
+        .await
+        .inspect_err(|e| {
+            log::error!("client error: {}", e);
         });
 
         match client {
@@ -621,7 +803,7 @@ async fn schedule_mcp_start_task<R: Runtime>(
                     }
                     headers
                 })
-                .connect_timeout(config_params.timeout.unwrap_or(Duration::MAX))
+                .connect_timeout(config_params.timeouts.connect.unwrap_or(Duration::MAX))
                 .build()
                 .unwrap(),
             rmcp::transport::sse_client::SseClientConfig {
@@ -643,9 +825,14 @@ async fn schedule_mcp_start_task<R: Runtime>(
                 version: "0.0.1".to_string(),
             },
         };
-        let client = client_info.serve(transport).await.map_err(|e| {
-            log::error!("client error: {:?}", e);
-            e.to_string()
+        let client = with_connect_timeout(
+            &name,
+            config_params.timeouts.connect,
+            client_handler(&app, &name, client_info).serve(transport),
+        )
+        .await
+        .inspect_err(|e| {
+            log::error!("client error: {}", e);
         });
 
         match client {
@@ -721,11 +908,16 @@ async fn schedule_mcp_start_task<R: Runtime>(
                 log::error!("Failed to run command {name}: {e}");
                 format!("Failed to run command {name}: {e}")
             })?;
+        let health = app.state::<AppState>().mcp_server_health.clone();
+        status::set_pid(&health, &name, process.id()).await;
 
-        let service = ()
-            .serve(process)
-            .await
-            .map_err(|e| format!("Failed to start MCP server {name}: {e}"));
+        let service = with_connect_timeout(
+            &name,
+            config_params.timeouts.connect,
+            client_handler(&app, &name, ClientInfo::default()).serve(process),
+        )
+        .await
+        .map_err(|e| format!("Failed to start MCP server {name}: {e}"));
 
         match service {
             Ok(server) => {
@@ -735,6 +927,10 @@ async fn schedule_mcp_start_task<R: Runtime>(
                     .await
                     .insert(name.clone(), RunningServiceEnum::NoInit(server));
                 log::info!("Server {name} started successfully.");
+                // Draining stderr keeps its tail for the status view
+                if let Some(stderr) = stderr {
+                    status::capture_stderr(health, name.clone(), stderr);
+                }
             }
             Err(_) => {
                 let mut buffer = String::new();
@@ -743,7 +939,10 @@ async fn schedule_mcp_start_task<R: Runtime>(
                     .read_to_string(&mut buffer)
                     .await
                 {
-                    Ok(_) => format!("Failed to start MCP server {name}: {buffer}"),
+                    Ok(_) => {
+                        status::push_stderr(&health, &name, &buffer).await;
+                        format!("Failed to start MCP server {name}: {buffer}")
+                    }
                     Err(_) => format!("Failed to read MCP server {name} stderr"),
                 };
                 log::error!("{error}");
@@ -782,6 +981,24 @@ async fn schedule_mcp_start_task<R: Runtime>(
     Ok(())
 }
 
+/// Bounds the MCP initialize handshake by the server's connect timeout, if it has one
+async fn with_connect_timeout<T, E: std::fmt::Display>(
+    name: &str,
+    limit: Option<Duration>,
+    connect: impl Future<Output = Result<T, E>>,
+) -> Result<T, String> {
+    let result = match limit {
+        Some(limit) => timeout(limit, connect).await.map_err(|_| {
+            format!(
+                "MCP server {} did not finish connecting within {:?}",
+                name, limit
+            )
+        })?,
+        None => connect.await,
+    };
+    result.map_err(|e| e.to_string())
+}
+
 pub fn extract_command_args(config: &Value) -> Option<McpServerConfig> {
     let obj = config.as_object()?;
     let transport_type = obj.get("type").and_then(|t| t.as_str()).map(String::from);
@@ -801,10 +1018,6 @@ pub fn extract_command_args(config: &Value) -> Option<McpServerConfig> {
         return None;
     }
 
-    let timeout = obj
-        .get("timeout")
-        .and_then(|t| t.as_u64())
-        .map(Duration::from_secs);
     let headers = obj
         .get("headers")
         .unwrap_or(&Value::Object(serde_json::Map::new()))
@@ -816,7 +1029,7 @@ pub fn extract_command_args(config: &Value) -> Option<McpServerConfig> {
         .as_object()?
         .clone();
     Some(McpServerConfig {
-        timeout,
+        timeouts: extract_timeouts(config),
         transport_type,
         url,
         command,
@@ -832,6 +1045,212 @@ pub fn extract_active_status(config: &Value) -> Option<bool> {
     Some(active)
 }
 
+/// Timeouts of a server in seconds, e.g.
+/// `"timeouts": { "connect": 30, "list": 20, "call": 300, "tools": { "find_leads": 900 } }`.
+/// The older `"timeout"` still sets the connect and call timeouts.
+pub fn extract_timeouts(config: &Value) -> McpTimeouts {
+    let seconds = |value: Option<&Value>| {
+        value
+            .and_then(Value::as_f64)
+            .filter(|secs| *secs > 0.0)
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+    };
+    let legacy = seconds(config.get("timeout"));
+    let timeouts = config.get("timeouts");
+    let field = |name: &str| seconds(timeouts.and_then(|t| t.get(name)));
+    let defaults = McpTimeouts::default();
+
+    McpTimeouts {
+        connect: field("connect").or(legacy),
+        list: field("list").unwrap_or(defaults.list),
+        call: field("call").or(legacy).unwrap_or(defaults.call),
+        tools: timeouts
+            .and_then(|t| t.get("tools"))
+            .and_then(Value::as_object)
+            .map(|tools| {
+                tools
+                    .iter()
+                    .filter_map(|(tool, secs)| seconds(Some(secs)).map(|d| (tool.clone(), d)))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+/// Timeouts of a server by name, the defaults when it has no config
+pub fn server_timeouts(configs: &HashMap<String, Value>, server: &str) -> McpTimeouts {
+    configs
+        .get(server)
+        .map(extract_timeouts)
+        .unwrap_or_default()
+}
+
+/// Dispatch priority of a server when several expose the same tool, defaults to 0
+pub fn extract_priority(config: &Value) -> i64 {
+    config.get("priority").and_then(Value::as_i64).unwrap_or(0)
+}
+
+/// Sends `notifications/cancelled` for a request we stopped waiting for, so the server
+/// can stop working on it
+pub async fn cancel_mcp_request(peer: &Peer<RoleClient>, request_id: RequestId, reason: &str) {
+    log::info!("Cancelling MCP request {:?}: {}", request_id, reason);
+    let params = CancelledNotificationParam {
+        request_id,
+        reason: Some(reason.to_string()),
+    };
+    if let Err(e) = peer.notify_cancelled(params).await {
+        log::warn!("Failed to send the cancellation to the MCP server: {}", e);
+    }
+}
+
+/// Request handle of a connected server, cloned so the server map is not held while a
+/// request is awaited
+pub async fn server_peer(
+    servers: &SharedMcpServers,
+    server: &str,
+) -> Result<Peer<RoleClient>, String> {
+    servers
+        .lock()
+        .await
+        .get(server)
+        .map(RunningServiceEnum::peer)
+        .ok_or_else(|| format!("Server {} not found", server))
+}
+
+/// Request handles of all connected servers, ordered by name
+pub async fn server_peers(servers: &SharedMcpServers) -> Vec<(String, Peer<RoleClient>)> {
+    let servers = servers.lock().await;
+    let mut peers: Vec<(String, Peer<RoleClient>)> = servers
+        .iter()
+        .map(|(name, service)| (name.clone(), service.peer()))
+        .collect();
+    peers.sort_by(|a, b| a.0.cmp(&b.0));
+    peers
+}
+
+/// Awaits a request, failing with `action` in the message when it errors or takes
+/// longer than `limit`
+pub async fn request_with_timeout<T>(
+    limit: Duration,
+    action: &str,
+    request: impl Future<Output = Result<T, rmcp::ServiceError>>,
+) -> Result<T, String> {
+    match timeout(limit, request).await {
+        Ok(result) => result.map_err(|e| format!("{} failed: {}", action, e)),
+        Err(_) => Err(format!("{} timed out after {:?}", action, limit)),
+    }
+}
+
+/// Lists the resources of every server. Servers that do not support resources or
+/// exceed their list timeout are skipped.
+pub async fn list_server_resources(
+    peers: &[(String, Peer<RoleClient>)],
+    configs: &HashMap<String, Value>,
+) -> Vec<ResourceWithServer> {
+    let mut all_resources = Vec::new();
+    for (server_name, peer) in peers {
+        let list_timeout = server_timeouts(configs, server_name).list;
+        let action = format!("Listing resources of {}", server_name);
+        let resources =
+            match request_with_timeout(list_timeout, &action, peer.list_all_resources()).await {
+                Ok(resources) => resources,
+                Err(e) => {
+                    log::debug!("{}", e);
+                    continue;
+                }
+            };
+
+        all_resources.extend(resources.into_iter().map(|resource| ResourceWithServer {
+            uri: resource.uri.clone(),
+            name: resource.name.clone(),
+            description: resource.description.clone(),
+            mime_type: resource.mime_type.clone(),
+            server: server_name.clone(),
+        }));
+    }
+    all_resources
+}
+
+/// Lists the prompts of every server. Servers that do not support prompts or exceed
+/// their list timeout are skipped.
+pub async fn list_server_prompts(
+    peers: &[(String, Peer<RoleClient>)],
+    configs: &HashMap<String, Value>,
+) -> Vec<PromptWithServer> {
+    let mut all_prompts = Vec::new();
+    for (server_name, peer) in peers {
+        let list_timeout = server_timeouts(configs, server_name).list;
+        let action = format!("Listing prompts of {}", server_name);
+        let prompts =
+            match request_with_timeout(list_timeout, &action, peer.list_all_prompts()).await {
+                Ok(prompts) => prompts,
+                Err(e) => {
+                    log::debug!("{}", e);
+                    continue;
+                }
+            };
+
+        all_prompts.extend(prompts.into_iter().map(|prompt| PromptWithServer {
+            name: prompt.name,
+            description: prompt.description,
+            arguments: prompt.arguments.unwrap_or_default(),
+            server: server_name.clone(),
+        }));
+    }
+    all_prompts
+}
+
+/// Whether string arguments may be converted to the types a tool's schema expects, defaults to on
+pub fn extract_coerce_arguments(config: &Value) -> bool {
+    config
+        .get("coerceArguments")
+        .and_then(Value::as_bool)
+        .unwrap_or(true)
+}
+
+/// Checks arguments against the tool's input schema, after optionally converting strings
+/// such as `"5"` or `"true"` to the expected types. Violations are returned as an error
+/// result listing each one, so the model can correct the call.
+pub fn validate_tool_arguments(
+    tool: &Tool,
+    arguments: &mut Option<Map<String, Value>>,
+    coerce: bool,
+) -> Result<(), CallToolResult> {
+    let schema = Value::Object((*tool.input_schema).clone());
+    let mut value = Value::Object(arguments.clone().unwrap_or_default());
+    if coerce && coerce_json_types(&schema, &mut value) > 0 {
+        log::debug!("Coerced arguments of tool {} to {}", tool.name, value);
+        if let Value::Object(coerced) = &value {
+            *arguments = Some(coerced.clone());
+        }
+    }
+
+    let violations = validate_json_schema(&schema, &value);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    log::info!(
+        "Rejected call to tool {} with {} invalid arguments",
+        tool.name,
+        violations.len()
+    );
+    let details = violations
+        .iter()
+        .map(|v| format!("- {}: {}", v.path, v.message))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut result = CallToolResult::error(vec![Content::text(format!(
+        "Invalid arguments for tool {}:\n{}\nCorrect the arguments and call the tool again.",
+        tool.name, details
+    ))]);
+    result.structured_content = Some(json!({
+        "error": "invalid_arguments",
+        "tool": tool.name,
+        "violations": violations,
+    }));
+    Err(result)
+}
+
 /// Restart only servers that were previously active (like cortex restart behavior)
 pub async fn restart_active_mcp_servers<R: Runtime>(
     app: &AppHandle<R>,
@@ -950,8 +1369,9 @@ pub async fn spawn_server_monitoring_task<R: Runtime>(
 
     tauri::async_runtime::spawn(async move {
         // Monitor the server using RunningService's JoinHandle<QuitReason>
+        let health = app_clone.state::<AppState>().mcp_server_health.clone();
         let quit_reason =
-            monitor_mcp_server_handle(servers_clone.clone(), name_clone.clone()).await;
+            monitor_mcp_server_handle(servers_clone.clone(), name_clone.clone(), health).await;
 
         log::info!(
             "MCP server {} quit with reason: {:?}",
@@ -1048,6 +1468,12 @@ pub async fn schedule_mcp_reconnect<R: Runtime>(
         let mut connected = state.mcp_successfully_connected.lock().await;
         connected.insert(server_name.to_string(), false);
     }
+    status::set_state(
+        &state.mcp_server_health,
+        server_name,
+        McpServerState::Restarting,
+    )
+    .await;
 
     // Remove existing server connection
     {
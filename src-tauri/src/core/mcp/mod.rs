@@ -1,7 +1,11 @@
+pub mod approval;
+pub mod call_log;
+pub mod client;
 pub mod commands;
 mod constants;
 pub mod helpers;
 pub mod models;
+pub mod status;
 
 #[cfg(test)]
 mod tests;
@@ -1,8 +1,11 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
+use rmcp::model::PromptArgument;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::constants::{MCP_LIST_TIMEOUT, MCP_TOOL_CALL_TIMEOUT};
+
 /// Configuration parameters extracted from MCP server config
 #[derive(Debug, Clone)]
 pub struct McpServerConfig {
@@ -11,10 +14,40 @@ pub struct McpServerConfig {
     pub command: Option<String>,
     pub args: Option<Vec<Value>>,
     pub envs: serde_json::Map<String, Value>,
-    pub timeout: Option<Duration>,
+    pub timeouts: McpTimeouts,
     pub headers: serde_json::Map<String, Value>,
 }
 
+/// Timeouts of one MCP server, see `extract_timeouts`
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpTimeouts {
+    /// Connecting and the initialize handshake, unlimited when unset
+    pub connect: Option<Duration>,
+    /// Listing tools, resources and prompts
+    pub list: Duration,
+    /// Tool calls, resource reads and prompt rendering
+    pub call: Duration,
+    /// Call timeouts of single tools, overriding `call`
+    pub tools: HashMap<String, Duration>,
+}
+
+impl Default for McpTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: None,
+            list: MCP_LIST_TIMEOUT,
+            call: MCP_TOOL_CALL_TIMEOUT,
+            tools: HashMap::new(),
+        }
+    }
+}
+
+impl McpTimeouts {
+    pub fn for_tool(&self, tool: &str) -> Duration {
+        self.tools.get(tool).copied().unwrap_or(self.call)
+    }
+}
+
 /// Tool with server information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolWithServer {
@@ -23,4 +56,181 @@ pub struct ToolWithServer {
     #[serde(rename = "inputSchema")]
     pub input_schema: serde_json::Value,
     pub server: String,
+    /// `server__tool`, accepted by `call_tool` to pick the server explicitly
+    #[serde(rename = "qualifiedName")]
+    pub qualified_name: String,
+}
+
+/// A tool name exposed by more than one connected server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCollision {
+    pub name: String,
+    /// Servers exposing the tool, in dispatch order
+    pub servers: Vec<String>,
+}
+
+/// Resource with server information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceWithServer {
+    pub uri: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(rename = "mimeType")]
+    pub mime_type: Option<String>,
+    pub server: String,
+}
+
+/// Prompt with server information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptWithServer {
+    pub name: String,
+    pub description: Option<String>,
+    pub arguments: Vec<PromptArgument>,
+    pub server: String,
+}
+
+/// What happens when a tool call matches a policy rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolPolicyAction {
+    Allow,
+    Ask,
+    Deny,
+}
+
+/// Condition on a tool call argument, a rule only applies while all its conditions hold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArgumentCondition {
+    pub argument: String,
+    /// Every email address in the argument must belong to one of these domains
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_domains: Option<Vec<String>>,
+    /// The argument must equal one of these values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub one_of: Option<Vec<Value>>,
+}
+
+/// Policy rule for a tool, a whole server, or a tool on a specific server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolPolicyRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    pub action: ToolPolicyAction,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<ArgumentCondition>,
+}
+
+/// Approval policy for MCP tool calls, stored in `mcp_tool_policy.json`.
+/// Tool rules take precedence over server rules; within each, the first match wins.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolPolicy {
+    pub default_action: ToolPolicyAction,
+    #[serde(default)]
+    pub rules: Vec<ToolPolicyRule>,
+}
+
+impl Default for ToolPolicy {
+    fn default() -> Self {
+        // Tools acting on the user's behalf ask first, everything else runs
+        let ask = |tool: &str| ToolPolicyRule {
+            server: None,
+            tool: Some(tool.to_string()),
+            action: ToolPolicyAction::Ask,
+            conditions: Vec::new(),
+        };
+        Self {
+            default_action: ToolPolicyAction::Allow,
+            rules: vec![
+                ask("send_email"),
+                ask("send_linkedin_message"),
+                ask("comment_on_linkedin_post"),
+                ask("react_to_linkedin_post"),
+                ask("delete_job"),
+            ],
+        }
+    }
+}
+
+/// A tool call waiting for the user's approval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolApprovalRequest {
+    pub id: String,
+    pub server: Option<String>,
+    pub tool: String,
+    pub arguments: Option<serde_json::Map<String, Value>>,
+    pub requested_at: String,
+}
+
+/// One `call_tool` invocation as recorded in `logs/mcp_tool_calls.jsonl`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ToolCallLogEntry {
+    pub timestamp: String,
+    /// Server the call was dispatched to, unset when none was found
+    pub server: Option<String>,
+    pub tool: String,
+    /// Arguments with secrets such as tokens and passwords redacted
+    pub arguments: Option<Value>,
+    pub duration_ms: u64,
+    /// Size of the serialized result content in bytes
+    pub result_bytes: Option<usize>,
+    /// Failure message, including results the tool itself flagged as errors
+    pub error: Option<String>,
+    /// Whether the call went to the REST API instead of the MCP server
+    pub http_fallback: bool,
+    pub cancellation_token: Option<String>,
+    pub cancelled: bool,
+}
+
+/// Filters for reading the tool call log; entries are returned newest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ToolCallLogQuery {
+    /// RFC 3339 timestamps bounding the entries returned
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub server: Option<String>,
+    pub tool: Option<String>,
+    pub errors_only: bool,
+    pub limit: Option<usize>,
+}
+
+/// Lifecycle state of a configured MCP server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum McpServerState {
+    #[default]
+    Starting,
+    Connected,
+    Restarting,
+    Failed,
+    Deactivated,
+}
+
+/// Health of one configured MCP server, returned by `get_mcp_server_status`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServerStatus {
+    pub name: String,
+    pub state: McpServerState,
+    /// `stdio`, `http` or `sse`
+    pub transport: String,
+    /// Process id of stdio servers
+    pub pid: Option<u32>,
+    /// Seconds since the current connection was established
+    pub uptime_secs: Option<u64>,
+    pub restart_count: u32,
+    pub last_error: Option<String>,
+    /// Last lines the server wrote to stderr, oldest first
+    pub stderr_tail: Vec<String>,
+    /// Number of cached tools, unset until the tool list was fetched
+    pub tool_count: Option<usize>,
+    pub call_count: u64,
+    pub average_latency_ms: Option<u64>,
 }
@@ -0,0 +1,157 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    sync::Mutex,
+};
+
+use super::{
+    constants::MCP_STDERR_TAIL_LINES,
+    models::{McpServerState, McpServerStatus},
+};
+
+/// What is known about a server's connections, kept across restarts
+#[derive(Debug, Default)]
+pub struct ServerHealth {
+    pub state: McpServerState,
+    pub pid: Option<u32>,
+    pub connected_at: Option<Instant>,
+    pub last_error: Option<String>,
+    pub stderr_tail: VecDeque<String>,
+    pub call_count: u64,
+    pub total_call_time: Duration,
+}
+
+/// Health of every server that was started, keyed by server name
+pub type ServerHealthMap = Arc<Mutex<HashMap<String, ServerHealth>>>;
+
+impl ServerHealth {
+    fn push_stderr(&mut self, text: &str) {
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            if self.stderr_tail.len() == MCP_STDERR_TAIL_LINES {
+                self.stderr_tail.pop_front();
+            }
+            self.stderr_tail.push_back(line.to_string());
+        }
+    }
+
+    fn disconnect(&mut self, state: McpServerState) {
+        self.state = state;
+        self.pid = None;
+        self.connected_at = None;
+    }
+}
+
+/// Moves a server to `state`; anything but `Connected` drops the current connection's details
+pub async fn set_state(health: &ServerHealthMap, name: &str, state: McpServerState) {
+    let mut health = health.lock().await;
+    let server = health.entry(name.to_string()).or_default();
+    if state == McpServerState::Connected {
+        server.state = state;
+        server.connected_at = Some(Instant::now());
+    } else {
+        server.disconnect(state);
+    }
+}
+
+/// Marks a connection attempt, keeping `Restarting` for attempts of the restart loop
+pub async fn mark_starting(health: &ServerHealthMap, name: &str) {
+    let mut health = health.lock().await;
+    let server = health.entry(name.to_string()).or_default();
+    let state = match server.state {
+        McpServerState::Restarting => McpServerState::Restarting,
+        _ => McpServerState::Starting,
+    };
+    server.disconnect(state);
+}
+
+pub async fn mark_failed(health: &ServerHealthMap, name: &str, error: &str) {
+    let mut health = health.lock().await;
+    let server = health.entry(name.to_string()).or_default();
+    server.disconnect(McpServerState::Failed);
+    server.last_error = Some(error.to_string());
+}
+
+/// Records the process of a stdio server, whose stderr starts a new tail
+pub async fn set_pid(health: &ServerHealthMap, name: &str, pid: Option<u32>) {
+    let mut health = health.lock().await;
+    let server = health.entry(name.to_string()).or_default();
+    server.pid = pid;
+    server.stderr_tail.clear();
+}
+
+pub async fn push_stderr(health: &ServerHealthMap, name: &str, text: &str) {
+    let mut health = health.lock().await;
+    health
+        .entry(name.to_string())
+        .or_default()
+        .push_stderr(text);
+}
+
+/// Keeps reading a server's stderr into its tail until the process closes it
+pub fn capture_stderr<S>(health: ServerHealthMap, name: String, stderr: S)
+where
+    S: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            log::debug!("MCP server {} stderr: {}", name, line);
+            push_stderr(&health, &name, &line).await;
+        }
+    });
+}
+
+pub async fn record_call(health: &ServerHealthMap, name: &str, duration: Duration) {
+    let mut health = health.lock().await;
+    let server = health.entry(name.to_string()).or_default();
+    server.call_count += 1;
+    server.total_call_time += duration;
+}
+
+/// Builds the status of a configured server from its config, its recorded health and the
+/// values kept elsewhere in the app state
+pub fn server_status(
+    name: &str,
+    config: &Value,
+    health: Option<&ServerHealth>,
+    restart_count: u32,
+    tool_count: Option<usize>,
+) -> McpServerStatus {
+    let deactivated = config.get("active").and_then(Value::as_bool) == Some(false);
+    let state = if deactivated {
+        McpServerState::Deactivated
+    } else {
+        health.map(|h| h.state).unwrap_or_default()
+    };
+    let connected = state == McpServerState::Connected;
+    let call_count = health.map_or(0, |h| h.call_count);
+
+    McpServerStatus {
+        name: name.to_string(),
+        state,
+        transport: config
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or("stdio")
+            .to_string(),
+        pid: health.and_then(|h| h.pid).filter(|_| connected),
+        uptime_secs: health
+            .and_then(|h| h.connected_at)
+            .filter(|_| connected)
+            .map(|since| since.elapsed().as_secs()),
+        restart_count,
+        last_error: health.and_then(|h| h.last_error.clone()),
+        stderr_tail: health.map_or_else(Vec::new, |h| h.stderr_tail.iter().cloned().collect()),
+        tool_count: tool_count.filter(|_| connected),
+        call_count,
+        average_latency_ms: health
+            .filter(|h| h.call_count > 0)
+            .map(|h| (h.total_call_time.as_millis() / h.call_count as u128) as u64),
+    }
+}
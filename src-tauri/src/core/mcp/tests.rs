@@ -1,11 +1,43 @@
-use super::helpers::run_mcp_commands;
+use super::approval::{evaluate_policy, remember_decision};
+use super::call_log::{read_tool_call_log, tool_call_log_path, ToolCallLog, ToolCallTrace};
+use super::client::{EventEmitter, McpClientHandler};
+use super::commands::{
+    call_tool, cancel_tool_call, get_mcp_prompt, list_mcp_prompts, list_mcp_resources,
+    read_mcp_resource, subscribe_mcp_resource, unsubscribe_mcp_resource,
+};
+use super::constants::{
+    MCP_RESOURCE_UPDATED_EVENT, MCP_TOOL_LOG_EVENT, MCP_TOOL_PROGRESS_EVENT, MCP_UPDATE_EVENT,
+};
+use super::helpers::{
+    extract_coerce_arguments, extract_timeouts, find_tool_collisions, order_servers_by_priority,
+    run_mcp_commands, split_qualified_tool_name, validate_tool_arguments,
+};
+use super::models::{
+    McpServerState, McpTimeouts, ToolCallLogEntry, ToolCallLogQuery, ToolCollision, ToolPolicy,
+    ToolPolicyAction,
+};
+use super::status::{self, server_status, ServerHealthMap};
 use crate::core::app::commands::get_jan_data_folder_path;
-use crate::core::state::SharedMcpServers;
+use crate::core::state::{AppState, RunningServiceEnum, SharedMcpServers};
+use rmcp::model::{
+    AnnotateAble, CallToolRequestParam, CallToolResult, CancelledNotificationParam, ClientInfo,
+    Content, ErrorData, GetPromptRequestParam, GetPromptResult, ListPromptsResult,
+    ListResourcesResult, ListToolsResult, LoggingLevel, LoggingMessageNotificationParam,
+    PaginatedRequestParam, ProgressNotificationParam, Prompt, PromptMessage, PromptMessageRole,
+    RawResource, ReadResourceRequestParam, ReadResourceResult, RequestId, ResourceContents,
+    ResourceUpdatedNotificationParam, SubscribeRequestParam, Tool, UnsubscribeRequestParam,
+};
+use rmcp::service::{NotificationContext, Peer, RequestContext};
+use rmcp::{RoleServer, ServerHandler, ServiceExt};
+use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
 use tauri::test::mock_app;
+use tauri::Manager;
+use tokio::io::AsyncReadExt;
 use tokio::sync::Mutex;
 
 #[tokio::test]
@@ -37,3 +69,873 @@ async fn test_run_mcp_commands() {
     // Clean up the mock config file
     std::fs::remove_file(&config_path).expect("Failed to remove config file");
 }
+
+#[test]
+fn test_qualified_tool_names_and_collisions() {
+    let servers = ["fetch", "fetch__v2", "search"];
+    assert_eq!(
+        split_qualified_tool_name("fetch__get", servers),
+        (Some("fetch".to_string()), "get".to_string())
+    );
+    // The longest server name wins when one prefixes another
+    assert_eq!(
+        split_qualified_tool_name("fetch__v2__get", servers),
+        (Some("fetch__v2".to_string()), "get".to_string())
+    );
+    // Bare names, including ones with the separator, stay untouched
+    assert_eq!(
+        split_qualified_tool_name("read__file", servers),
+        (None, "read__file".to_string())
+    );
+    assert_eq!(
+        split_qualified_tool_name("fetch__", servers),
+        (None, "fetch__".to_string())
+    );
+
+    let configs = HashMap::from([
+        (
+            "search".to_string(),
+            json!({ "command": "npx", "priority": 10 }),
+        ),
+        ("fetch".to_string(), json!({ "command": "uvx" })),
+    ]);
+    assert_eq!(
+        order_servers_by_priority(
+            vec![
+                "fetch__v2".to_string(),
+                "fetch".to_string(),
+                "search".to_string()
+            ],
+            &configs
+        ),
+        vec!["search", "fetch", "fetch__v2"]
+    );
+
+    let tool = |name: &'static str| Tool::new(name, "", Arc::new(serde_json::Map::new()));
+    let tools = HashMap::from([
+        ("fetch".to_string(), vec![tool("get"), tool("search")]),
+        ("search".to_string(), vec![tool("search")]),
+        ("fetch__v2".to_string(), vec![tool("get_v2")]),
+    ]);
+    assert_eq!(
+        find_tool_collisions(&tools, &configs),
+        vec![ToolCollision {
+            name: "search".to_string(),
+            servers: vec!["search".to_string(), "fetch".to_string()],
+        }]
+    );
+}
+
+#[test]
+fn test_tool_arguments_are_validated_and_coerced() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "query": { "type": "string" },
+            "limit": { "type": "integer" },
+            "verified": { "type": "boolean" }
+        },
+        "required": ["query"]
+    });
+    let tool = Tool::new(
+        "find_leads",
+        "",
+        Arc::new(schema.as_object().unwrap().clone()),
+    );
+
+    let mut arguments = json!({ "query": "cto", "limit": "25", "verified": "true" })
+        .as_object()
+        .cloned();
+    assert!(validate_tool_arguments(&tool, &mut arguments, true).is_ok());
+    assert_eq!(
+        arguments,
+        json!({ "query": "cto", "limit": 25, "verified": true })
+            .as_object()
+            .cloned()
+    );
+
+    // Without coercion the same strings are violations, as is a missing argument
+    let mut arguments = json!({ "limit": "25" }).as_object().cloned();
+    let invalid = validate_tool_arguments(&tool, &mut arguments, false).unwrap_err();
+    assert_eq!(invalid.is_error, Some(true));
+    let structured = invalid.structured_content.unwrap();
+    assert_eq!(structured["error"], "invalid_arguments");
+    let paths: Vec<&str> = structured["violations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|v| v["path"].as_str())
+        .collect();
+    assert_eq!(paths.len(), 2);
+    assert!(paths.contains(&"$.limit"));
+
+    // Missing arguments are checked as an empty object
+    assert!(validate_tool_arguments(&tool, &mut None, true).is_err());
+
+    assert!(extract_coerce_arguments(&json!({ "command": "npx" })));
+    assert!(!extract_coerce_arguments(
+        &json!({ "command": "npx", "coerceArguments": false })
+    ));
+}
+
+#[test]
+fn test_timeouts_per_server_and_tool() {
+    let defaults = extract_timeouts(&json!({ "command": "uvx" }));
+    assert_eq!(defaults, McpTimeouts::default());
+    assert_eq!(defaults.connect, None);
+
+    let timeouts = extract_timeouts(&json!({
+        "command": "npx",
+        "timeouts": {
+            "connect": 30,
+            "list": 15,
+            "call": 300,
+            "tools": { "find_leads": 900, "fetch": 2.5, "broken": -1 }
+        }
+    }));
+    assert_eq!(timeouts.connect, Some(Duration::from_secs(30)));
+    assert_eq!(timeouts.list, Duration::from_secs(15));
+    assert_eq!(timeouts.for_tool("find_leads"), Duration::from_secs(900));
+    assert_eq!(timeouts.for_tool("fetch"), Duration::from_millis(2500));
+    assert_eq!(timeouts.for_tool("broken"), Duration::from_secs(300));
+    assert_eq!(timeouts.for_tool("search"), Duration::from_secs(300));
+
+    // The older single timeout covers connecting and calls
+    let legacy = extract_timeouts(&json!({ "type": "http", "url": "http://x", "timeout": 10 }));
+    assert_eq!(legacy.connect, Some(Duration::from_secs(10)));
+    assert_eq!(legacy.call, Duration::from_secs(10));
+    assert_eq!(legacy.list, McpTimeouts::default().list);
+}
+
+#[test]
+fn test_tool_policy_rules_and_conditions() {
+    let mut policy: ToolPolicy = serde_json::from_value(json!({
+        "defaultAction": "allow",
+        "rules": [
+            {
+                "tool": "send_email",
+                "action": "allow",
+                "conditions": [{ "argument": "to", "emailDomains": ["acme.com"] }]
+            },
+            { "tool": "send_email", "action": "ask" },
+            { "server": "filesystem", "action": "deny" },
+            { "server": "filesystem", "tool": "read_file", "action": "allow" }
+        ]
+    }))
+    .unwrap();
+    let args = |value: serde_json::Value| value.as_object().cloned();
+
+    // Allowed while every recipient is inside the allow-listed domain
+    let internal = args(json!({ "to": "Ann <ann@acme.com>, bob@sales.acme.com" }));
+    assert_eq!(
+        evaluate_policy(
+            &policy,
+            Some("salesboxai-builtin"),
+            "send_email",
+            internal.as_ref()
+        ),
+        ToolPolicyAction::Allow
+    );
+    let external = args(json!({ "to": ["ann@acme.com", "eve@acme.com.evil.io"] }));
+    assert_eq!(
+        evaluate_policy(&policy, None, "send_email", external.as_ref()),
+        ToolPolicyAction::Ask
+    );
+    assert_eq!(
+        evaluate_policy(&policy, None, "send_email", None),
+        ToolPolicyAction::Ask
+    );
+
+    // Tool rules win over server rules, which win over the default
+    assert_eq!(
+        evaluate_policy(&policy, Some("filesystem"), "read_file", None),
+        ToolPolicyAction::Allow
+    );
+    assert_eq!(
+        evaluate_policy(&policy, Some("filesystem"), "write_file", None),
+        ToolPolicyAction::Deny
+    );
+    assert_eq!(
+        evaluate_policy(&policy, Some("fetch"), "fetch", None),
+        ToolPolicyAction::Allow
+    );
+
+    // "Always" decisions beat the unconditional rules for the tool, but only on the
+    // server they were made for, and never the conditional rules
+    let builtin = Some("salesboxai-builtin");
+    remember_decision(
+        &mut policy,
+        "salesboxai-builtin",
+        "send_email",
+        ToolPolicyAction::Deny,
+    );
+    assert_eq!(policy.rules[1].server.as_deref(), builtin);
+    assert_eq!(
+        evaluate_policy(&policy, builtin, "send_email", external.as_ref()),
+        ToolPolicyAction::Deny
+    );
+    assert_eq!(
+        evaluate_policy(&policy, builtin, "send_email", internal.as_ref()),
+        ToolPolicyAction::Allow
+    );
+    assert_eq!(
+        evaluate_policy(&policy, Some("gmail"), "send_email", external.as_ref()),
+        ToolPolicyAction::Ask
+    );
+    // A new decision replaces the previous unconditional rule for the tool on that server
+    remember_decision(
+        &mut policy,
+        "salesboxai-builtin",
+        "send_email",
+        ToolPolicyAction::Allow,
+    );
+    assert_eq!(
+        policy
+            .rules
+            .iter()
+            .filter(|rule| rule.tool.as_deref() == Some("send_email"))
+            .count(),
+        3
+    );
+    assert_eq!(
+        evaluate_policy(&policy, builtin, "send_email", external.as_ref()),
+        ToolPolicyAction::Allow
+    );
+    // Without conditional rules the decision goes in front
+    remember_decision(
+        &mut policy,
+        "filesystem",
+        "write_file",
+        ToolPolicyAction::Ask,
+    );
+    assert_eq!(policy.rules[0].tool.as_deref(), Some("write_file"));
+    assert_eq!(
+        evaluate_policy(&policy, Some("filesystem"), "write_file", None),
+        ToolPolicyAction::Ask
+    );
+
+    // The default policy asks before acting on the user's behalf
+    let default_policy = ToolPolicy::default();
+    assert_eq!(
+        evaluate_policy(&default_policy, None, "send_linkedin_message", None),
+        ToolPolicyAction::Ask
+    );
+    assert_eq!(
+        evaluate_policy(&default_policy, None, "list_jobs", None),
+        ToolPolicyAction::Allow
+    );
+}
+
+#[tokio::test]
+async fn test_tool_call_log_records_redacted_calls() {
+    let dir = std::env::temp_dir().join(format!("mcp-tool-calls-{}", uuid::Uuid::new_v4()));
+    let log = ToolCallLog::default();
+    let arguments = json!({ "to": "ann@acme.com", "auth": { "token": "secret-value" } })
+        .as_object()
+        .cloned();
+
+    let sent = ToolCallLogEntry::new(
+        "send_email",
+        &arguments,
+        Some("call-1".to_string()),
+        ToolCallTrace {
+            server: Some("salesboxai-builtin".to_string()),
+            http_fallback: true,
+            cancelled: false,
+        },
+        Duration::from_millis(42),
+        &Ok(CallToolResult::success(vec![Content::text("sent")])),
+    );
+    log.append(&dir, &sent).await;
+    let failed = ToolCallLogEntry::new(
+        "fetch",
+        &None,
+        None,
+        ToolCallTrace {
+            server: Some("fetch".to_string()),
+            ..Default::default()
+        },
+        Duration::from_millis(7),
+        &Ok(CallToolResult::error(vec![Content::text("404")])),
+    );
+    log.append(&dir, &failed).await;
+
+    let entries = read_tool_call_log(&dir, &ToolCallLogQuery::default()).unwrap();
+    assert_eq!(entries.len(), 2);
+    // Newest first
+    assert_eq!(entries[0].tool, "fetch");
+    assert_eq!(entries[0].error.as_deref(), Some("404"));
+    let sent = &entries[1];
+    assert_eq!(sent.duration_ms, 42);
+    assert!(sent.http_fallback);
+    assert!(sent.result_bytes.is_some_and(|bytes| bytes > 0));
+    let logged_arguments = sent.arguments.as_ref().unwrap();
+    assert_eq!(logged_arguments["to"], "ann@acme.com");
+    assert_eq!(logged_arguments["auth"]["token"], "[REDACTED]");
+
+    let errors_only = ToolCallLogQuery {
+        errors_only: true,
+        ..Default::default()
+    };
+    assert_eq!(read_tool_call_log(&dir, &errors_only).unwrap().len(), 1);
+    let by_server = ToolCallLogQuery {
+        server: Some("salesboxai-builtin".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(
+        read_tool_call_log(&dir, &by_server).unwrap()[0].tool,
+        "send_email"
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_tool_call_log_rotates() {
+    let dir = std::env::temp_dir().join(format!("mcp-tool-calls-{}", uuid::Uuid::new_v4()));
+    let log = ToolCallLog::new(300, 3);
+
+    for i in 0..12 {
+        let entry = ToolCallLogEntry::new(
+            &format!("tool_{}", i),
+            &None,
+            None,
+            ToolCallTrace::default(),
+            Duration::from_millis(1),
+            &Ok(CallToolResult::success(vec![Content::text("ok")])),
+        );
+        log.append(&dir, &entry).await;
+    }
+
+    assert!(tool_call_log_path(&dir, 2).exists());
+    assert!(!tool_call_log_path(&dir, 3).exists());
+    let size = |index| {
+        std::fs::metadata(tool_call_log_path(&dir, index))
+            .unwrap()
+            .len()
+    };
+    assert!((0..3).all(|index| size(index) <= 300));
+
+    // The oldest calls were dropped, the rest are read across files, newest first
+    let entries = read_tool_call_log(&dir, &ToolCallLogQuery::default()).unwrap();
+    assert!(entries.len() < 12);
+    assert_eq!(entries[0].tool, "tool_11");
+    let numbers: Vec<usize> = entries
+        .iter()
+        .map(|entry| entry.tool["tool_".len()..].parse().unwrap())
+        .collect();
+    assert!(numbers.windows(2).all(|pair| pair[0] == pair[1] + 1));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_server_status_tracks_lifecycle_and_latency() {
+    let health = ServerHealthMap::default();
+    let config = json!({ "command": "npx", "args": [] });
+
+    status::mark_starting(&health, "leads").await;
+    status::set_pid(&health, "leads", Some(4242)).await;
+    status::push_stderr(&health, "leads", "booting\n\nready\n").await;
+    status::set_state(&health, "leads", McpServerState::Connected).await;
+    status::record_call(&health, "leads", Duration::from_millis(100)).await;
+    status::record_call(&health, "leads", Duration::from_millis(300)).await;
+
+    let report = server_status(
+        "leads",
+        &config,
+        health.lock().await.get("leads"),
+        0,
+        Some(7),
+    );
+    assert_eq!(report.state, McpServerState::Connected);
+    assert_eq!(report.transport, "stdio");
+    assert_eq!(report.pid, Some(4242));
+    assert_eq!(report.uptime_secs, Some(0));
+    assert_eq!(report.stderr_tail, vec!["booting", "ready"]);
+    assert_eq!(report.tool_count, Some(7));
+    assert_eq!(report.call_count, 2);
+    assert_eq!(report.average_latency_ms, Some(200));
+
+    // A failure drops the connection details but keeps the error and the history
+    status::mark_failed(&health, "leads", "Health check timed out").await;
+    status::set_state(&health, "leads", McpServerState::Restarting).await;
+    status::mark_starting(&health, "leads").await;
+    let report = server_status(
+        "leads",
+        &config,
+        health.lock().await.get("leads"),
+        2,
+        Some(7),
+    );
+    assert_eq!(report.state, McpServerState::Restarting);
+    assert_eq!(report.pid, None);
+    assert_eq!(report.uptime_secs, None);
+    assert_eq!(report.tool_count, None);
+    assert_eq!(report.restart_count, 2);
+    assert_eq!(report.last_error.as_deref(), Some("Health check timed out"));
+    assert_eq!(report.average_latency_ms, Some(200));
+
+    let inactive = json!({ "type": "http", "url": "http://localhost", "active": false });
+    let report = server_status("crm", &inactive, None, 0, None);
+    assert_eq!(report.state, McpServerState::Deactivated);
+    assert_eq!(report.transport, "http");
+    assert_eq!(report.average_latency_ms, None);
+}
+
+/// In-process MCP server for exercising the client over real protocol messages
+#[derive(Clone, Default)]
+struct StubServer {
+    subscriptions: Arc<StdMutex<Vec<String>>>,
+    /// Tools it lists, listing fails while this is `None`
+    tools: Arc<StdMutex<Option<Vec<Tool>>>>,
+    /// Request ids of the tool calls it received and of the cancellations
+    calls: Arc<StdMutex<Vec<RequestId>>>,
+    cancelled: Arc<StdMutex<Vec<RequestId>>>,
+    peer: Arc<StdMutex<Option<Peer<RoleServer>>>>,
+}
+
+impl StubServer {
+    fn with_tools(names: &[&str]) -> Self {
+        let server = Self::default();
+        server.set_tools(Some(names));
+        server
+    }
+
+    fn set_tools(&self, names: Option<&[&str]>) {
+        let schema = json!({ "type": "object" }).as_object().cloned().unwrap();
+        *self.tools.lock().unwrap() = names.map(|names| {
+            names
+                .iter()
+                .map(|name| Tool::new(name.to_string(), "", Arc::new(schema.clone())))
+                .collect()
+        });
+    }
+
+    /// Handle for sending notifications to the client, once it finished initializing
+    async fn peer(&self) -> Peer<RoleServer> {
+        for _ in 0..200 {
+            if let Some(peer) = self.peer.lock().unwrap().clone() {
+                return peer;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Client not connected");
+    }
+}
+
+impl ServerHandler for StubServer {
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        *self.peer.lock().unwrap() = Some(context.peer);
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        match self.tools.lock().unwrap().clone() {
+            Some(tools) => Ok(ListToolsResult::with_all_items(tools)),
+            None => Err(ErrorData::internal_error("tools unavailable", None)),
+        }
+    }
+
+    /// Reports progress and a log line, then runs until the call is cancelled
+    async fn call_tool(
+        &self,
+        _request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        self.calls.lock().unwrap().push(context.id.clone());
+        if let Some(progress_token) = context.meta.get_progress_token() {
+            let _ = context
+                .peer
+                .notify_progress(ProgressNotificationParam {
+                    progress_token,
+                    progress: 1.0,
+                    total: Some(4.0),
+                    message: Some("searching".into()),
+                })
+                .await;
+        }
+        let _ = context
+            .peer
+            .notify_logging_message(LoggingMessageNotificationParam {
+                level: LoggingLevel::Info,
+                logger: Some("search".into()),
+                data: json!("started"),
+            })
+            .await;
+        let _ = tokio::time::timeout(Duration::from_secs(60), context.ct.cancelled()).await;
+        Ok(CallToolResult::success(vec![Content::text("too late")]))
+    }
+
+    async fn on_cancelled(
+        &self,
+        notification: CancelledNotificationParam,
+        _context: NotificationContext<RoleServer>,
+    ) {
+        self.cancelled.lock().unwrap().push(notification.request_id);
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, ErrorData> {
+        let notes = RawResource::new("stub://notes", "notes").no_annotation();
+        Ok(ListResourcesResult::with_all_items(vec![notes]))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, ErrorData> {
+        if request.uri == "stub://slow" {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        }
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::text("hello", request.uri)],
+        })
+    }
+
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), ErrorData> {
+        self.subscriptions.lock().unwrap().push(request.uri.clone());
+        let _ = context
+            .peer
+            .notify_resource_updated(ResourceUpdatedNotificationParam { uri: request.uri })
+            .await;
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), ErrorData> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .retain(|uri| *uri != request.uri);
+        Ok(())
+    }
+
+    async fn list_prompts(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, ErrorData> {
+        let topic = serde_json::from_value(json!({ "name": "topic", "required": true })).unwrap();
+        Ok(ListPromptsResult::with_all_items(vec![Prompt::new(
+            "summarize",
+            Some("Summarize a topic"),
+            Some(vec![topic]),
+        )]))
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, ErrorData> {
+        let topic = request
+            .arguments
+            .and_then(|arguments| arguments.get("topic").cloned())
+            .and_then(|topic| topic.as_str().map(str::to_string))
+            .unwrap_or_default();
+        Ok(GetPromptResult {
+            description: None,
+            messages: vec![PromptMessage::new_text(
+                PromptMessageRole::User,
+                format!("Summarize {}", topic),
+            )],
+        })
+    }
+}
+
+type CapturedEvents = Arc<StdMutex<Vec<(String, Value)>>>;
+
+/// Connects `server` as `name` and registers it in the app's server map; events the
+/// client emits are collected in the returned list
+async fn connect_stub_server(state: &AppState, name: &str, server: StubServer) -> CapturedEvents {
+    let (server_transport, client_transport) = tokio::io::duplex(4096);
+    tokio::spawn(async move {
+        if let Ok(running) = server.serve(server_transport).await {
+            let _ = running.waiting().await;
+        }
+    });
+
+    let events = CapturedEvents::default();
+    let captured = events.clone();
+    let emit: EventEmitter = Arc::new(move |event: &str, payload: Value| {
+        captured.lock().unwrap().push((event.to_string(), payload));
+    });
+    let handler = McpClientHandler::new(
+        name,
+        ClientInfo::default(),
+        emit,
+        state.mcp_tool_cache.clone(),
+        state.mcp_tool_calls_in_flight.clone(),
+    );
+    let client = handler
+        .serve(client_transport)
+        .await
+        .expect("Failed to connect to the stub server");
+    state
+        .mcp_servers
+        .lock()
+        .await
+        .insert(name.to_string(), RunningServiceEnum::WithInit(client));
+    events
+}
+
+/// Waits until `count` events with the given name have been emitted and returns the
+/// payload of the last one
+async fn wait_for_events(events: &CapturedEvents, name: &str, count: usize) -> Value {
+    for _ in 0..200 {
+        let found: Vec<Value> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(event, _)| event == name)
+            .map(|(_, payload)| payload.clone())
+            .collect();
+        if found.len() >= count {
+            return found[count - 1].clone();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Fewer than {} {} events were emitted", count, name);
+}
+
+async fn wait_for_event(events: &CapturedEvents, name: &str) -> Value {
+    wait_for_events(events, name, 1).await
+}
+
+/// Waits until `list` holds at least one item
+async fn wait_for_items<T: Clone>(list: &StdMutex<Vec<T>>) -> Vec<T> {
+    for _ in 0..200 {
+        let items = list.lock().unwrap().clone();
+        if !items.is_empty() {
+            return items;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Nothing was recorded");
+}
+
+#[tokio::test]
+async fn test_resource_and_prompt_commands() {
+    let app = mock_app();
+    app.manage(AppState::default());
+    let state = app.state::<AppState>();
+    let stub = StubServer::default();
+    let events = connect_stub_server(&state, "docs", stub.clone()).await;
+
+    let resources = list_mcp_resources(app.state()).await.unwrap();
+    assert_eq!(resources.len(), 1);
+    assert_eq!(resources[0].uri, "stub://notes");
+    assert_eq!(resources[0].server, "docs");
+
+    let read = read_mcp_resource(app.state(), "docs".into(), "stub://notes".into())
+        .await
+        .unwrap();
+    assert!(matches!(
+        &read.contents[0],
+        ResourceContents::TextResourceContents { text, .. } if text == "hello"
+    ));
+
+    // Updates of subscribed resources reach the frontend tagged with their server
+    subscribe_mcp_resource(app.state(), "docs".into(), "stub://notes".into())
+        .await
+        .unwrap();
+    assert_eq!(*stub.subscriptions.lock().unwrap(), vec!["stub://notes"]);
+    let updated = wait_for_event(&events, MCP_RESOURCE_UPDATED_EVENT).await;
+    assert_eq!(updated, json!({ "uri": "stub://notes", "server": "docs" }));
+
+    unsubscribe_mcp_resource(app.state(), "docs".into(), "stub://notes".into())
+        .await
+        .unwrap();
+    assert!(stub.subscriptions.lock().unwrap().is_empty());
+
+    let prompts = list_mcp_prompts(app.state()).await.unwrap();
+    assert_eq!(prompts.len(), 1);
+    assert_eq!(prompts[0].name, "summarize");
+    assert_eq!(prompts[0].arguments[0].name, "topic");
+    assert_eq!(prompts[0].server, "docs");
+
+    let arguments = json!({ "topic": "leads" }).as_object().cloned();
+    let prompt = get_mcp_prompt(app.state(), "docs".into(), "summarize".into(), arguments)
+        .await
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(&prompt.messages[0].content).unwrap()["text"],
+        "Summarize leads"
+    );
+
+    let missing = read_mcp_resource(app.state(), "crm".into(), "stub://notes".into()).await;
+    assert_eq!(missing.unwrap_err(), "Server crm not found");
+}
+
+#[tokio::test]
+async fn test_resource_requests_release_server_lock_and_time_out() {
+    let app = mock_app();
+    app.manage(AppState::default());
+    let state = app.state::<AppState>();
+    connect_stub_server(&state, "docs", StubServer::default()).await;
+    state
+        .mcp_active_servers
+        .lock()
+        .await
+        .insert("docs".into(), json!({ "timeouts": { "call": 0.5 } }));
+
+    let read = read_mcp_resource(app.state(), "docs".into(), "stub://slow".into());
+    tokio::pin!(read);
+    assert!(tokio::time::timeout(Duration::from_millis(100), &mut read)
+        .await
+        .is_err());
+    // Other commands can use the server map while the read is pending
+    assert!(state.mcp_servers.try_lock().is_ok());
+
+    let error = read.await.unwrap_err();
+    assert!(error.contains("timed out"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_cancel_tool_call_notifies_server_and_forwards_progress() {
+    let app = mock_app();
+    app.manage(AppState::default());
+    let state = app.state::<AppState>();
+    let stub = StubServer::with_tools(&["search"]);
+    let events = connect_stub_server(&state, "leads", stub.clone()).await;
+
+    let call = call_tool(
+        app.handle().clone(),
+        app.state(),
+        "search".into(),
+        None,
+        Some("call-1".into()),
+    );
+    let observe_and_cancel = async {
+        // Progress and log lines of the running call are attributed to its token
+        let progress = wait_for_event(&events, MCP_TOOL_PROGRESS_EVENT).await;
+        assert_eq!(progress["cancellationToken"], "call-1");
+        assert_eq!(progress["tool"], "search");
+        assert_eq!(progress["server"], "leads");
+        assert_eq!(progress["progress"], 1.0);
+        assert_eq!(progress["total"], 4.0);
+        assert_eq!(progress["message"], "searching");
+
+        let log = wait_for_event(&events, MCP_TOOL_LOG_EVENT).await;
+        assert_eq!(log["cancellationToken"], "call-1");
+        assert_eq!(log["logger"], "search");
+        assert_eq!(log["data"], "started");
+
+        cancel_tool_call(app.state(), "call-1".into())
+            .await
+            .unwrap();
+    };
+    let (result, ()) = tokio::join!(call, observe_and_cancel);
+    assert_eq!(result.unwrap_err(), "Tool call 'search' was cancelled");
+
+    // The server is told to stop the very request it is running
+    let cancelled = wait_for_items(&stub.cancelled).await;
+    assert_eq!(cancelled, *stub.calls.lock().unwrap());
+    assert!(state.mcp_tool_calls_in_flight.lock().await.is_empty());
+    assert_eq!(
+        cancel_tool_call(app.state(), "call-1".into())
+            .await
+            .unwrap_err(),
+        "Cancellation token call-1 not found"
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_tool_call_aborts_http_fallback() {
+    let app = mock_app();
+    app.manage(AppState::default());
+    let state = app.state::<AppState>();
+
+    // A REST endpoint that accepts the fallback request and never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (accepted_tx, accepted_rx) = tokio::sync::oneshot::channel();
+    let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let _ = accepted_tx.send(());
+        let mut buf = [0u8; 1024];
+        while !matches!(socket.read(&mut buf).await, Ok(0) | Err(_)) {}
+        let _ = closed_tx.send(());
+    });
+    state.mcp_active_servers.lock().await.insert(
+        "salesboxai-builtin".into(),
+        json!({
+            "url": format!("http://127.0.0.1:{}/mcp", port),
+            "headers": { "Authorization": "Bearer test" }
+        }),
+    );
+
+    let call = call_tool(
+        app.handle().clone(),
+        app.state(),
+        "cancel_job".into(),
+        None,
+        Some("call-2".into()),
+    );
+    let cancel = async {
+        accepted_rx.await.unwrap();
+        cancel_tool_call(app.state(), "call-2".into())
+            .await
+            .unwrap();
+    };
+    let (result, ()) = tokio::join!(call, cancel);
+    assert_eq!(result.unwrap_err(), "Tool call 'cancel_job' was cancelled");
+
+    // Dropping the request closes its connection
+    tokio::time::timeout(Duration::from_secs(2), closed_rx)
+        .await
+        .expect("HTTP fallback request was not aborted")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_tool_list_changed_refreshes_or_drops_cached_tools() {
+    let app = mock_app();
+    app.manage(AppState::default());
+    let state = app.state::<AppState>();
+    let stub = StubServer::with_tools(&["search", "enrich"]);
+    let events = connect_stub_server(&state, "leads", stub.clone()).await;
+    state
+        .mcp_tool_cache
+        .lock()
+        .await
+        .insert("leads".into(), Vec::new());
+
+    let cached_names = || async {
+        state
+            .mcp_tool_cache
+            .lock()
+            .await
+            .get("leads")
+            .map(|tools| tools.iter().map(|t| t.name.to_string()).collect::<Vec<_>>())
+    };
+
+    stub.peer().await.notify_tool_list_changed().await.unwrap();
+    wait_for_events(&events, MCP_UPDATE_EVENT, 1).await;
+    assert_eq!(
+        cached_names().await,
+        Some(vec!["search".to_string(), "enrich".to_string()])
+    );
+
+    // When the new list cannot be fetched, the stale one is dropped
+    stub.set_tools(None);
+    stub.peer().await.notify_tool_list_changed().await.unwrap();
+    wait_for_events(&events, MCP_UPDATE_EVENT, 2).await;
+    assert_eq!(cached_names().await, None);
+}
@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use hyper::{Body, Request};
+use jan_utils::{api_key_id, RotatingJsonl, TimeRange};
+use tokio::sync::Mutex;
+
+use super::constants::{
+    PROXY_AUDIT_LOG_DEFAULT_LIMIT, PROXY_AUDIT_LOG_FILE, PROXY_AUDIT_LOG_MAX_FILE_BYTES,
+};
+use super::models::{AuditLogEntry, AuditLogOptions, AuditLogQuery};
+
+/// Appends one line per request to `proxy_audit.jsonl`. Entries are never rewritten;
+/// once the file reaches `max_file_bytes` it is rotated and the oldest of `max_files`
+/// files is dropped.
+pub struct AuditLogger {
+    files: RotatingJsonl,
+    write_lock: Mutex<()>,
+}
+
+impl AuditLogger {
+    pub fn new(dir: &Path, options: &AuditLogOptions) -> Self {
+        Self {
+            files: RotatingJsonl::new(
+                dir,
+                PROXY_AUDIT_LOG_FILE,
+                options.max_file_bytes,
+                options.max_files,
+            ),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub async fn append(&self, entry: &AuditLogEntry) {
+        let line = match serde_json::to_string(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                log::warn!("Failed to serialize proxy audit log entry: {}", e);
+                return;
+            }
+        };
+        let _guard = self.write_lock.lock().await;
+        if let Err(e) = self.files.append_line_blocking(line).await {
+            log::warn!("Failed to write proxy audit log entry: {}", e);
+        }
+    }
+}
+
+impl AuditLogEntry {
+    /// Starts an entry from the request line and the client-supplied headers
+    pub fn from_request(req: &Request<Body>, path: &str, remote_addr: String) -> Self {
+        let header = |name: hyper::header::HeaderName| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            remote_addr,
+            method: req.method().to_string(),
+            path: path.to_string(),
+            host: header(hyper::header::HOST),
+            origin: header(hyper::header::ORIGIN),
+            user_agent: header(hyper::header::USER_AGENT),
+            key_id: header(hyper::header::AUTHORIZATION)
+                .as_deref()
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .map(api_key_id),
+            ..Default::default()
+        }
+    }
+
+    pub fn reject(&mut self, reason: &str) {
+        self.rejection = Some(reason.to_string());
+    }
+}
+
+impl AuditLogQuery {
+    fn matches(&self, entry: &AuditLogEntry, range: &TimeRange) -> bool {
+        if self.rejected_only && entry.rejection.is_none() {
+            return false;
+        }
+        range.contains(&entry.timestamp)
+            && self
+                .remote_addr
+                .as_ref()
+                .map_or(true, |addr| entry.remote_addr.starts_with(addr.as_str()))
+            && self
+                .key_id
+                .as_ref()
+                .map_or(true, |key_id| entry.key_id.as_ref() == Some(key_id))
+            && self
+                .model
+                .as_ref()
+                .map_or(true, |model| entry.model.as_ref() == Some(model))
+            && self
+                .path_prefix
+                .as_ref()
+                .map_or(true, |prefix| entry.path.starts_with(prefix.as_str()))
+    }
+}
+
+/// Reads the audit log in `dir`, rotated files included, returning the newest
+/// matching entries first
+pub fn read_audit_log(
+    dir: &Path,
+    max_files: usize,
+    query: &AuditLogQuery,
+) -> Result<Vec<AuditLogEntry>, String> {
+    let range = TimeRange::parse(query.since.as_deref(), query.until.as_deref())?;
+    let limit = query.limit.unwrap_or(PROXY_AUDIT_LOG_DEFAULT_LIMIT);
+    RotatingJsonl::new(
+        dir,
+        PROXY_AUDIT_LOG_FILE,
+        PROXY_AUDIT_LOG_MAX_FILE_BYTES,
+        max_files,
+    )
+    .read_newest(limit, |entry: &AuditLogEntry| query.matches(entry, &range))
+}
@@ -1,50 +1,266 @@
-use tauri::{AppHandle, Manager, Runtime, State};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::future::BoxFuture;
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
 use tauri_plugin_llamacpp::state::LlamacppState;
 
+use crate::core::app::commands::get_jan_data_folder_path;
+use crate::core::mcp::commands as mcp_commands;
+use crate::core::server::audit_log::read_audit_log;
+use crate::core::server::constants::{
+    PROXY_DRAIN_DEFAULT_TIMEOUT_SECS, PROXY_SESSION_HEALTH_EVENT,
+};
+use crate::core::server::models::{
+    AuditLogEntry, AuditLogOptions, AuditLogQuery, ProxyServerOptions, ReplayResult,
+    RequestLogOptions, ServerConfigSnapshot, ServerConfigUpdate, ServerStatus,
+};
 use crate::core::server::proxy;
+use crate::core::server::request_log::find_request_log_entry;
+use crate::core::server::tool_loop::{ProxyTool, ToolProvider};
 use crate::core::state::AppState;
 
+/// Exposes the connected MCP servers to the proxy's tool-calling mode
+struct McpToolProvider {
+    app: AppHandle,
+}
+
+impl ToolProvider for McpToolProvider {
+    fn list_tools(&self) -> BoxFuture<'_, Result<Vec<ProxyTool>, String>> {
+        Box::pin(async move {
+            let tools = mcp_commands::get_tools(self.app.state::<AppState>()).await?;
+            // Names exposed by several servers are given to the model qualified
+            let ambiguous = |name: &str| tools.iter().filter(|t| t.name == name).count() > 1;
+            Ok(tools
+                .iter()
+                .map(|tool| ProxyTool {
+                    name: if ambiguous(&tool.name) {
+                        tool.qualified_name.clone()
+                    } else {
+                        tool.name.clone()
+                    },
+                    description: tool.description.clone(),
+                    parameters: tool.input_schema.clone(),
+                })
+                .collect())
+        })
+    }
+
+    fn call_tool(
+        &self,
+        name: String,
+        arguments: Option<Map<String, Value>>,
+    ) -> BoxFuture<'_, Result<String, String>> {
+        Box::pin(async move {
+            let result = mcp_commands::call_tool(
+                self.app.clone(),
+                self.app.state::<AppState>(),
+                name,
+                arguments,
+                None,
+            )
+            .await?;
+
+            let text = result
+                .content
+                .iter()
+                .filter_map(|content| content.raw.as_text().map(|t| t.text.clone()))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let output = if text.is_empty() {
+                serde_json::to_string(&result.content).map_err(|e| e.to_string())?
+            } else {
+                text
+            };
+
+            if result.is_error == Some(true) {
+                Err(output)
+            } else {
+                Ok(output)
+            }
+        })
+    }
+}
+
 #[tauri::command]
-pub async fn start_server<R: Runtime>(
-    app_handle: AppHandle<R>,
+#[allow(clippy::too_many_arguments)]
+pub async fn start_server(
+    app_handle: AppHandle,
     state: State<'_, AppState>,
     host: String,
     port: u16,
     prefix: String,
     api_key: String,
     trusted_hosts: Vec<String>,
+    options: Option<ProxyServerOptions>,
 ) -> Result<bool, String> {
     let server_handle = state.server_handle.clone();
     let plugin_state: State<LlamacppState> = app_handle.state();
     let sessions = plugin_state.llama_server_process.clone();
+    let data_dir = get_jan_data_folder_path(app_handle.clone());
 
     proxy::start_server(
-        server_handle,
+        server_handle.clone(),
         sessions,
         host,
         port,
         prefix,
         api_key,
-        vec![trusted_hosts],
+        trusted_hosts,
+        options.unwrap_or_default(),
+        data_dir,
+        Some(Arc::new(McpToolProvider {
+            app: app_handle.clone(),
+        })),
     )
     .await
     .map_err(|e| e.to_string())?;
+
+    if let Some(mut events) = proxy::subscribe_session_health(server_handle).await {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        if let Err(e) = app_handle.emit(PROXY_SESSION_HEALTH_EVENT, &event) {
+                            log::warn!("Failed to emit session health event: {}", e);
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("Dropped {} session health events", skipped);
+                    }
+                    // The server was stopped
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
     Ok(true)
 }
 
+/// Stops the server, letting in-flight responses finish for up to `drain_timeout_secs`
 #[tauri::command]
-pub async fn stop_server(state: State<'_, AppState>) -> Result<(), String> {
+pub async fn stop_server(
+    state: State<'_, AppState>,
+    drain_timeout_secs: Option<u64>,
+) -> Result<(), String> {
     let server_handle = state.server_handle.clone();
+    let drain_timeout =
+        Duration::from_secs(drain_timeout_secs.unwrap_or(PROXY_DRAIN_DEFAULT_TIMEOUT_SECS));
 
-    proxy::stop_server(server_handle)
+    proxy::stop_server(server_handle, drain_timeout)
         .await
         .map_err(|e| e.to_string())?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_server_status(state: State<'_, AppState>) -> Result<bool, String> {
+pub async fn get_server_status(state: State<'_, AppState>) -> Result<ServerStatus, String> {
     let server_handle = state.server_handle.clone();
 
-    Ok(proxy::is_server_running(server_handle).await)
+    Ok(proxy::get_server_status(server_handle).await)
+}
+
+/// Applies API key, trusted host, prefix and option changes without restarting the server
+#[tauri::command]
+pub async fn update_server_config(
+    state: State<'_, AppState>,
+    update: ServerConfigUpdate,
+) -> Result<ServerConfigSnapshot, String> {
+    let server_handle = state.server_handle.clone();
+
+    proxy::update_server_config(server_handle, update).await
+}
+
+/// Re-sends a request from the proxy request log to the session currently serving its model.
+/// Only requests logged with `include_bodies` can be replayed.
+#[tauri::command]
+pub async fn replay_proxy_request<R: Runtime>(
+    app_handle: AppHandle<R>,
+    state: State<'_, AppState>,
+    request_id: String,
+) -> Result<ReplayResult, String> {
+    let log_dir = get_jan_data_folder_path(app_handle.clone()).join("logs");
+    // The running server's settings decide how many rotated files exist
+    let max_files = proxy::get_server_status(state.server_handle.clone())
+        .await
+        .config
+        .map(|config| config.options.request_log.max_files)
+        .unwrap_or_else(|| RequestLogOptions::default().max_files);
+    let entry = find_request_log_entry(&log_dir, max_files, &request_id)?
+        .ok_or_else(|| format!("Request {} not found in proxy log", request_id))?;
+
+    let method = reqwest::Method::from_bytes(entry.method.as_bytes())
+        .map_err(|e| format!("Request {} has an invalid method: {}", request_id, e))?;
+    let body = entry.request_body;
+    if body.is_none() && method != reqwest::Method::GET {
+        return Err(format!(
+            "Request {} was logged without its body",
+            request_id
+        ));
+    }
+    let model_id = entry
+        .model
+        .ok_or_else(|| format!("Request {} has no model", request_id))?;
+
+    let (port, api_key) = {
+        let plugin_state: State<LlamacppState> = app_handle.state();
+        let sessions = plugin_state.llama_server_process.lock().await;
+        let session = sessions
+            .values()
+            .find(|s| s.info.model_id == model_id)
+            .ok_or_else(|| format!("No running session found for model '{}'", model_id))?;
+        (session.info.port, session.info.api_key.clone())
+    };
+
+    let url = format!("http://127.0.0.1:{}{}", port, entry.path);
+    log::info!(
+        "Replaying proxy request {} as {} {}",
+        request_id,
+        entry.method,
+        url
+    );
+
+    let started_at = Instant::now();
+    let mut request = reqwest::Client::new()
+        .request(method, &url)
+        .header("Authorization", format!("Bearer {}", api_key));
+    if let Some(body) = &body {
+        request = request.json(body);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Replay request failed: {}", e))?;
+    let status = response.status().as_u16();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read replay response: {}", e))?;
+
+    Ok(ReplayResult {
+        request_id,
+        status,
+        latency_ms: started_at.elapsed().as_millis() as u64,
+        body,
+    })
+}
+
+/// Reads the proxy audit log, newest entries first
+#[tauri::command]
+pub async fn get_proxy_audit_log<R: Runtime>(
+    app_handle: AppHandle<R>,
+    state: State<'_, AppState>,
+    query: Option<AuditLogQuery>,
+) -> Result<Vec<AuditLogEntry>, String> {
+    let log_dir = get_jan_data_folder_path(app_handle).join("logs");
+    let max_files = proxy::get_server_status(state.server_handle.clone())
+        .await
+        .config
+        .map(|config| config.options.audit_log.max_files)
+        .unwrap_or_else(|| AuditLogOptions::default().max_files);
+    let query = query.unwrap_or_default();
+    tokio::task::spawn_blocking(move || read_audit_log(&log_dir, max_files, &query))
+        .await
+        .map_err(|e| e.to_string())?
 }
@@ -0,0 +1,83 @@
+// Proxy Server Constants
+pub const PROXY_REQUEST_LOG_FILE: &str = "proxy_requests.jsonl";
+pub const PROXY_AUDIT_LOG_FILE: &str = "proxy_audit.jsonl";
+pub const PROXY_AUDIT_LOG_DEFAULT_LIMIT: usize = 500;
+pub const PROXY_AUDIT_LOG_MAX_FILE_BYTES: u64 = 10 * 1024 * 1024; // 10 MB per file
+pub const PROXY_AUDIT_LOG_MAX_FILES: usize = 5;
+pub const PROXY_REQUEST_LOG_MAX_FILE_BYTES: u64 = 10 * 1024 * 1024; // 10 MB per file
+pub const PROXY_REQUEST_LOG_MAX_FILES: usize = 5;
+pub const PROXY_CAPTURED_BODY_MAX_BYTES: usize = 1024 * 1024; // 1 MB
+pub const PROXY_CAPTURED_TAIL_BYTES: usize = 16 * 1024; // enough for the final SSE usage chunk
+pub const PROXY_LATENCY_BUCKETS_SECS: [f64; 11] = [
+    0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+pub const PROXY_METRICS_KNOWN_PATHS: &[&str] = &[
+    "/",
+    "/chat/completions",
+    "/completions",
+    "/embeddings",
+    "/metrics",
+    "/models",
+    "/openapi.json",
+];
+pub const PROXY_QUEUE_DEFAULT_MAX_CONCURRENT: usize = 1;
+pub const PROXY_QUEUE_DEFAULT_MAX_DEPTH: usize = 32;
+pub const PROXY_QUEUE_DEFAULT_TIMEOUT_SECS: u64 = 120;
+pub const PROXY_QUEUE_DEFAULT_RETRY_AFTER_SECS: u64 = 5;
+pub const PROXY_QUEUE_POSITION_HEADER: &str = "X-Queue-Position";
+pub const PROXY_DRAIN_DEFAULT_TIMEOUT_SECS: u64 = 30;
+pub const PROXY_SESSION_HEALTH_DEFAULT_INTERVAL_SECS: u64 = 15;
+pub const PROXY_SESSION_HEALTH_DEFAULT_TIMEOUT_SECS: u64 = 5;
+pub const PROXY_SESSION_HEALTH_EVENT_CAPACITY: usize = 64;
+pub const PROXY_SESSION_HEALTH_EVENT: &str = "proxy-session-health";
+pub const PROXY_UPSTREAM_RETRY_DELAY_MS: u64 = 250;
+pub const PROXY_IMAGE_DEFAULT_MAX_BYTES: usize = 20 * 1024 * 1024; // 20 MB
+pub const PROXY_IMAGE_DEFAULT_FETCH_TIMEOUT_SECS: u64 = 30;
+pub const PROXY_IMAGE_MAX_REDIRECTS: usize = 5;
+pub const PROXY_TOOL_LOOP_DEFAULT_MAX_ITERATIONS: usize = 8;
+pub const PROXY_STRUCTURED_OUTPUT_DEFAULT_MAX_RETRIES: u32 = 1;
+pub const PROXY_EMBEDDING_BATCH_DEFAULT_WINDOW_MS: u64 = 10;
+pub const PROXY_EMBEDDING_BATCH_DEFAULT_MAX_INPUTS: usize = 64;
+pub const PROXY_VECTOR_STORE_DIR: &str = "vector_stores";
+pub const PROXY_VECTOR_STORE_DEFAULT_TOP_K: usize = 5;
+pub const PROXY_CACHE_DIR: &str = "proxy_cache";
+/// Paths served without the host and API key checks
+pub const PROXY_WHITELISTED_PATHS: &[&str] = &["/", "/openapi.json", "/favicon.ico"];
+pub const PROXY_CACHE_STATUS_HEADER: &str = "X-Cache";
+pub const PROXY_CACHE_DEFAULT_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+pub const PROXY_CACHE_DEFAULT_MAX_TOTAL_BYTES: u64 = 512 * 1024 * 1024; // 512 MB
+pub const PROXY_CACHE_FINGERPRINT_SAMPLE_BYTES: u64 = 4 * 1024 * 1024; // 4 MB from each end
+pub const PROXY_TLS_DIR: &str = "certs";
+pub const PROXY_TLS_CERT_FILE: &str = "api-server-cert.pem";
+pub const PROXY_TLS_KEY_FILE: &str = "api-server-key.pem";
+pub const PROXY_CORS_DEFAULT_METHODS: &[&str] =
+    &["GET", "POST", "PUT", "DELETE", "OPTIONS", "PATCH"];
+pub const PROXY_CORS_DEFAULT_HEADERS: &[&str] = &[
+    "accept",
+    "accept-language",
+    "authorization",
+    "cache-control",
+    "connection",
+    "content-type",
+    "dnt",
+    "host",
+    "if-modified-since",
+    "keep-alive",
+    "origin",
+    "user-agent",
+    "x-api-key",
+    "x-csrf-token",
+    "x-forwarded-for",
+    "x-forwarded-host",
+    "x-forwarded-proto",
+    "x-requested-with",
+    "x-stainless-arch",
+    "x-stainless-lang",
+    "x-stainless-os",
+    "x-stainless-package-version",
+    "x-stainless-retry-count",
+    "x-stainless-runtime",
+    "x-stainless-runtime-version",
+    "x-stainless-timeout",
+];
+pub const PROXY_CORS_DEFAULT_MAX_AGE_SECS: u64 = 86400;
@@ -0,0 +1,170 @@
+use hyper::http::response::Builder;
+
+use super::models::CorsPolicy;
+
+/// Why a cross-origin request was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CorsDenial {
+    Origin,
+    Method,
+    Headers,
+}
+
+/// Matches `value` against a pattern where `*` stands for any run of characters,
+/// e.g. `https://*.example.com` or `http://localhost:*`
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    if !pattern.contains('*') {
+        return pattern.eq_ignore_ascii_case(value);
+    }
+
+    let pattern = pattern.to_ascii_lowercase();
+    let value = value.to_ascii_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !value.starts_with(first) || value.len() < first.len() + last.len() {
+        return false;
+    }
+
+    let mut rest = &value[first.len()..value.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    value.ends_with(last)
+}
+
+impl CorsPolicy {
+    pub fn is_origin_allowed(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|pattern| wildcard_match(pattern, origin))
+    }
+
+    fn is_method_allowed(&self, method: &str) -> bool {
+        method.eq_ignore_ascii_case("OPTIONS")
+            || self
+                .allowed_methods
+                .iter()
+                .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(method))
+    }
+
+    fn is_header_allowed(&self, header: &str) -> bool {
+        self.allowed_headers
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(header))
+    }
+
+    /// Whether the policy contains the bare `*` origin
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|o| o == "*")
+    }
+
+    /// Value for `Access-Control-Allow-Origin`, or `None` when the origin is refused.
+    /// Origins matched by a listed pattern are echoed so credentialed requests work;
+    /// origins only admitted by `*` get a literal `*`, which never carries credentials.
+    pub fn allow_origin_value(&self, origin: &str) -> Option<String> {
+        let listed = !origin.is_empty()
+            && self
+                .allowed_origins
+                .iter()
+                .any(|pattern| pattern != "*" && wildcard_match(pattern, origin));
+        if listed && (self.allow_credentials || !self.allows_any_origin()) {
+            Some(origin.to_string())
+        } else if self.allows_any_origin() {
+            Some("*".to_string())
+        } else {
+            None
+        }
+    }
+
+    /// Validates a preflight request against the policy
+    pub fn check_preflight(
+        &self,
+        origin: &str,
+        requested_method: &str,
+        requested_headers: &str,
+    ) -> Result<(), CorsDenial> {
+        if !origin.is_empty() && !self.is_origin_allowed(origin) {
+            return Err(CorsDenial::Origin);
+        }
+        if !requested_method.is_empty() && !self.is_method_allowed(requested_method) {
+            return Err(CorsDenial::Method);
+        }
+        let headers_allowed = requested_headers
+            .split(',')
+            .map(|h| h.trim())
+            .filter(|h| !h.is_empty())
+            .all(|header| self.is_header_allowed(header));
+        if !headers_allowed {
+            return Err(CorsDenial::Headers);
+        }
+        Ok(())
+    }
+
+    /// Validates the `Origin` of a regular (non-preflight) request
+    pub fn check_request(&self, origin: &str) -> Result<(), CorsDenial> {
+        if origin.is_empty() || self.is_origin_allowed(origin) {
+            Ok(())
+        } else {
+            Err(CorsDenial::Origin)
+        }
+    }
+}
+
+/// Adds the CORS headers for a regular response. A refused origin gets no
+/// `Access-Control-Allow-Origin`, so the browser will not expose the response.
+pub fn add_cors_headers(builder: Builder, origin: &str, policy: &CorsPolicy) -> Builder {
+    let mut builder = builder.header("Vary", "Origin");
+    if let Some(allow_origin) = policy.allow_origin_value(origin) {
+        if policy.allow_credentials && allow_origin != "*" {
+            builder = builder.header("Access-Control-Allow-Credentials", "true");
+        }
+        builder = builder
+            .header("Access-Control-Allow-Origin", allow_origin)
+            .header(
+                "Access-Control-Allow-Methods",
+                policy.allowed_methods.join(", "),
+            )
+            .header(
+                "Access-Control-Allow-Headers",
+                policy.allowed_headers.join(", "),
+            );
+    }
+    builder
+}
+
+/// Adds the CORS headers for a successful preflight response
+pub fn add_preflight_headers(
+    builder: Builder,
+    origin: &str,
+    requested_headers: &str,
+    policy: &CorsPolicy,
+) -> Builder {
+    let allow_headers = if policy.allowed_headers.iter().any(|h| h == "*") {
+        requested_headers.to_string()
+    } else {
+        policy.allowed_headers.join(", ")
+    };
+
+    let mut builder = builder
+        .header(
+            "Access-Control-Allow-Methods",
+            policy.allowed_methods.join(", "),
+        )
+        .header("Access-Control-Allow-Headers", allow_headers)
+        .header("Access-Control-Max-Age", policy.max_age_secs.to_string())
+        .header(
+            "Vary",
+            "Origin, Access-Control-Request-Method, Access-Control-Request-Headers",
+        );
+
+    if let Some(allow_origin) = policy.allow_origin_value(origin) {
+        if policy.allow_credentials && allow_origin != "*" {
+            builder = builder.header("Access-Control-Allow-Credentials", "true");
+        }
+        builder = builder.header("Access-Control-Allow-Origin", allow_origin);
+    }
+    builder
+}
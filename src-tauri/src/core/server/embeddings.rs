@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::Client;
+use serde_json::Value;
+use tokio::sync::{oneshot, Mutex};
+
+use super::models::EmbeddingBatchOptions;
+use super::queue::{AdmissionError, AdmissionQueue};
+
+/// The session an embeddings request is sent to
+#[derive(Clone)]
+pub struct EmbeddingTarget {
+    pub client: Client,
+    pub upstream_url: String,
+    pub session_api_key: Option<String>,
+    /// Admission queue and model id, so a batch takes a single slot on the session
+    pub admission: Option<(Arc<AdmissionQueue>, String)>,
+}
+
+struct Waiter {
+    /// The caller's original `input` field, used if the batch has to be split up again
+    input: Value,
+    offset: usize,
+    count: usize,
+    reply: oneshot::Sender<Result<Value, String>>,
+}
+
+struct PendingBatch {
+    id: u64,
+    target: EmbeddingTarget,
+    /// The request body without `input`, shared by every request in the batch
+    template: Value,
+    inputs: Vec<Value>,
+    waiters: Vec<Waiter>,
+}
+
+/// Collects concurrent embedding requests with identical parameters for the same
+/// session and sends them upstream as one request
+pub struct EmbeddingBatcher {
+    options: EmbeddingBatchOptions,
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<String, PendingBatch>>,
+}
+
+/// Splits an `input` field into the individual inputs it contains
+fn split_inputs(input: &Value) -> Result<Vec<Value>, String> {
+    match input {
+        Value::Null => Err("Request body must contain an 'input' field".to_string()),
+        Value::Array(items) if items.is_empty() => Err("'input' must not be empty".to_string()),
+        // A single pre-tokenized input
+        Value::Array(items) if items.iter().all(|i| i.is_number()) => Ok(vec![input.clone()]),
+        Value::Array(items) => Ok(items.clone()),
+        single => Ok(vec![single.clone()]),
+    }
+}
+
+/// Sends an embeddings request to the session and returns the parsed response
+pub async fn send_embeddings(target: &EmbeddingTarget, body: &Value) -> Result<Value, String> {
+    let _admission = match &target.admission {
+        Some((queue, model_id)) => Some(queue.acquire(model_id).await.map_err(|e| match e {
+            AdmissionError::QueueFull => {
+                format!("Too many queued requests for model '{}'", model_id)
+            }
+            AdmissionError::Timeout => {
+                format!("Timed out waiting for a free slot on model '{}'", model_id)
+            }
+        })?),
+        None => None,
+    };
+
+    let mut request = target.client.post(&target.upstream_url).json(body);
+    if let Some(key) = &target.session_api_key {
+        request = request.header("Authorization", format!("Bearer {}", key));
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Proxy request to model failed: {}", e))?;
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read model response: {}", e))?;
+    if !status.is_success() {
+        return Err(format!("Model returned {}: {}", status, text));
+    }
+    serde_json::from_str(&text).map_err(|e| format!("Invalid model response: {}", e))
+}
+
+/// Extracts the embedding vectors from a response, ordered by input
+pub fn embedding_vectors(response: &Value) -> Result<Vec<Vec<f32>>, String> {
+    let mut data: Vec<&Value> = response["data"]
+        .as_array()
+        .ok_or("Embeddings response has no 'data' array")?
+        .iter()
+        .collect();
+    data.sort_by_key(|item| item["index"].as_u64().unwrap_or(0));
+    data.into_iter()
+        .map(|item| {
+            item["embedding"]
+                .as_array()
+                .ok_or_else(|| "Embeddings response item has no 'embedding' array".to_string())
+                .map(|values| {
+                    values
+                        .iter()
+                        .map(|v| v.as_f64().unwrap_or(0.0) as f32)
+                        .collect()
+                })
+        })
+        .collect()
+}
+
+/// Carves one caller's share out of a batched response. Token usage is split
+/// in proportion to the number of inputs.
+fn split_response(response: &Value, offset: usize, count: usize, total: usize) -> Value {
+    let mut data: Vec<(usize, Value)> = response["data"]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    let index = item["index"].as_u64()? as usize;
+                    if index < offset || index >= offset + count {
+                        return None;
+                    }
+                    let mut item = item.clone();
+                    item["index"] = Value::from(index - offset);
+                    Some((index, item))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    data.sort_by_key(|(index, _)| *index);
+
+    let mut share = response.clone();
+    share["data"] = data.into_iter().map(|(_, item)| item).collect();
+    if let Some(usage) = share.get_mut("usage").and_then(|u| u.as_object_mut()) {
+        for value in usage.values_mut() {
+            if let Some(tokens) = value.as_u64() {
+                *value = Value::from(tokens * count as u64 / total.max(1) as u64);
+            }
+        }
+    }
+    share
+}
+
+async fn execute_batch(batch: PendingBatch) {
+    let PendingBatch {
+        target,
+        template,
+        inputs,
+        mut waiters,
+        ..
+    } = batch;
+
+    if waiters.len() == 1 {
+        let waiter = waiters.remove(0);
+        let mut body = template;
+        body["input"] = waiter.input;
+        let _ = waiter.reply.send(send_embeddings(&target, &body).await);
+        return;
+    }
+
+    let total = inputs.len();
+    log::debug!(
+        "Sending embedding batch of {} inputs from {} requests",
+        total,
+        waiters.len()
+    );
+    let mut body = template.clone();
+    body["input"] = Value::Array(inputs);
+
+    match send_embeddings(&target, &body).await {
+        Ok(response) => {
+            for waiter in waiters {
+                let share = split_response(&response, waiter.offset, waiter.count, total);
+                let _ = waiter.reply.send(Ok(share));
+            }
+        }
+        Err(e) => {
+            // One bad input should not fail everyone else's request
+            log::warn!(
+                "Embedding batch failed, retrying requests individually: {}",
+                e
+            );
+            for waiter in waiters {
+                let mut body = template.clone();
+                body["input"] = waiter.input;
+                let target = target.clone();
+                tokio::spawn(async move {
+                    let _ = waiter.reply.send(send_embeddings(&target, &body).await);
+                });
+            }
+        }
+    }
+}
+
+impl EmbeddingBatcher {
+    pub fn new(options: EmbeddingBatchOptions) -> Self {
+        Self {
+            options,
+            next_id: AtomicU64::new(0),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Embeds the request's inputs, sharing an upstream request with other
+    /// requests that arrive within the batch window
+    pub async fn embed(
+        self: &Arc<Self>,
+        target: EmbeddingTarget,
+        body: Value,
+    ) -> Result<Value, String> {
+        let inputs = split_inputs(&body["input"])?;
+        if inputs.len() >= self.options.max_batch_inputs {
+            return send_embeddings(&target, &body).await;
+        }
+
+        let mut template = body;
+        let input = template
+            .as_object_mut()
+            .and_then(|object| object.remove("input"))
+            .unwrap_or_default();
+        let key = format!("{} {}", target.upstream_url, template);
+        let count = inputs.len();
+        let (reply, receiver) = oneshot::channel();
+
+        let mut pending = self.pending.lock().await;
+        if pending
+            .get(&key)
+            .is_some_and(|batch| batch.inputs.len() + count > self.options.max_batch_inputs)
+        {
+            if let Some(full) = pending.remove(&key) {
+                tokio::spawn(execute_batch(full));
+            }
+        }
+
+        match pending.get_mut(&key) {
+            Some(batch) => {
+                batch.waiters.push(Waiter {
+                    input,
+                    offset: batch.inputs.len(),
+                    count,
+                    reply,
+                });
+                batch.inputs.extend(inputs);
+            }
+            None => {
+                let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+                pending.insert(
+                    key.clone(),
+                    PendingBatch {
+                        id,
+                        target,
+                        template,
+                        inputs,
+                        waiters: vec![Waiter {
+                            input,
+                            offset: 0,
+                            count,
+                            reply,
+                        }],
+                    },
+                );
+
+                let batcher = self.clone();
+                let window = Duration::from_millis(self.options.window_ms);
+                tokio::spawn(async move {
+                    tokio::time::sleep(window).await;
+                    let batch = {
+                        let mut pending = batcher.pending.lock().await;
+                        match pending.get(&key) {
+                            // Otherwise it filled up and was already sent
+                            Some(batch) if batch.id == id => pending.remove(&key),
+                            _ => None,
+                        }
+                    };
+                    if let Some(batch) = batch {
+                        execute_batch(batch).await;
+                    }
+                });
+            }
+        }
+        drop(pending);
+
+        receiver
+            .await
+            .map_err(|_| "Embedding batch was dropped".to_string())?
+    }
+}
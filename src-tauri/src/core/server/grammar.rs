@@ -0,0 +1,379 @@
+use std::collections::{BTreeMap, HashSet};
+
+use jan_utils::resolve_schema_ref;
+use serde_json::Value;
+
+/// Primitive rules shared by every generated grammar, adapted from llama.cpp's
+/// json-schema-to-grammar. Whitespace is bounded so models cannot pad forever.
+const PRIMITIVE_RULES: &[(&str, &str)] = &[
+    ("space", r#"| " " | "\n" [ \t]{0,20}"#),
+    ("boolean", r#"("true" | "false") space"#),
+    ("null", r#""null" space"#),
+    ("integral-part", r#"[0] | [1-9] [0-9]{0,15}"#),
+    ("decimal-part", r#"[0-9]{1,16}"#),
+    (
+        "number",
+        r#"("-"? integral-part) ("." decimal-part)? ([eE] [-+]? integral-part)? space"#,
+    ),
+    ("integer", r#"("-"? integral-part) space"#),
+    (
+        "char",
+        r#"[^"\\\x7F\x00-\x1F] | [\\] (["\\bfnrt] | "u" [0-9a-fA-F]{4})"#,
+    ),
+    ("string", r#""\"" char* "\"" space"#),
+    (
+        "value",
+        r#"object | array | string | number | boolean | null"#,
+    ),
+    (
+        "object",
+        r#""{" space ( string ":" space value ("," space string ":" space value)* )? "}" space"#,
+    ),
+    (
+        "array",
+        r#""[" space ( value ("," space value)* )? "]" space"#,
+    ),
+];
+
+/// Compiles a JSON Schema into a GBNF grammar that llama-server can use to
+/// constrain sampling. Fails on schemas that cannot be expressed as a grammar.
+pub fn json_schema_to_gbnf(schema: &Value) -> Result<String, String> {
+    let mut builder = GrammarBuilder {
+        root: schema,
+        rules: BTreeMap::new(),
+        used_primitives: HashSet::new(),
+        refs_in_progress: HashSet::new(),
+    };
+    let root_rule = builder.visit(schema, "root")?;
+    if root_rule != "root" {
+        builder.rules.insert("root".to_string(), root_rule);
+    }
+    Ok(builder.format())
+}
+
+/// Renders a string as a GBNF literal
+fn gbnf_literal(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Rule names may only contain letters, digits and dashes
+fn rule_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    sanitized.trim_matches('-').to_string()
+}
+
+/// `{min,max}` repetition suffix, or `*`/`+` when they suffice
+fn repetition(min: u64, max: Option<u64>) -> String {
+    match (min, max) {
+        (0, None) => "*".to_string(),
+        (1, None) => "+".to_string(),
+        (min, None) => format!("{{{},}}", min),
+        (min, Some(max)) if min == max => format!("{{{}}}", min),
+        (min, Some(max)) => format!("{{{},{}}}", min, max),
+    }
+}
+
+struct GrammarBuilder<'a> {
+    root: &'a Value,
+    rules: BTreeMap<String, String>,
+    used_primitives: HashSet<&'static str>,
+    refs_in_progress: HashSet<String>,
+}
+
+impl GrammarBuilder<'_> {
+    fn primitive(&mut self, name: &'static str) -> String {
+        self.used_primitives.insert(name);
+        name.to_string()
+    }
+
+    fn add_rule(&mut self, name: &str, body: String) -> String {
+        let mut key = rule_name(name);
+        if key.is_empty() {
+            key = "rule".to_string();
+        }
+        // Different schema locations can sanitize to the same name
+        let mut unique = key.clone();
+        let mut counter = 1;
+        while self
+            .rules
+            .get(&unique)
+            .is_some_and(|existing| existing != &body)
+        {
+            unique = format!("{}{}", key, counter);
+            counter += 1;
+        }
+        self.rules.insert(unique.clone(), body);
+        unique
+    }
+
+    fn literal_value(&mut self, value: &Value) -> String {
+        self.used_primitives.insert("space");
+        format!("{} space", gbnf_literal(&value.to_string()))
+    }
+
+    fn visit(&mut self, schema: &Value, name: &str) -> Result<String, String> {
+        let Some(object) = schema.as_object() else {
+            return match schema {
+                Value::Bool(true) => Ok(self.primitive("value")),
+                _ => Err(format!("Schema at '{}' accepts no value", name)),
+            };
+        };
+
+        if let Some(reference) = object.get("$ref").and_then(|r| r.as_str()) {
+            return self.visit_ref(reference);
+        }
+
+        if let Some(value) = object.get("const") {
+            let body = self.literal_value(value);
+            return Ok(self.add_rule(name, body));
+        }
+
+        if let Some(options) = object.get("enum").and_then(|e| e.as_array()) {
+            let body = options
+                .iter()
+                .map(|option| self.literal_value(option))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            return Ok(self.add_rule(name, format!("({})", body)));
+        }
+
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(options) = object.get(keyword).and_then(|a| a.as_array()) {
+                let alternatives = options
+                    .iter()
+                    .enumerate()
+                    .map(|(i, option)| self.visit(option, &format!("{}-{}", name, i)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                return Ok(self.add_rule(name, alternatives.join(" | ")));
+            }
+        }
+
+        if object.contains_key("allOf") {
+            return Err(format!(
+                "'allOf' at '{}' is not supported in structured output schemas",
+                name
+            ));
+        }
+
+        match object.get("type") {
+            Some(Value::Array(types)) => {
+                let alternatives = types
+                    .iter()
+                    .filter_map(|t| t.as_str())
+                    .map(|t| {
+                        let mut single = object.clone();
+                        single.insert("type".to_string(), Value::String(t.to_string()));
+                        self.visit(&Value::Object(single), &format!("{}-{}", name, t))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(self.add_rule(name, alternatives.join(" | ")))
+            }
+            Some(Value::String(kind)) => self.visit_type(object, kind, name),
+            Some(other) => Err(format!("Invalid 'type' at '{}': {}", name, other)),
+            None if object.contains_key("properties") => self.visit_type(object, "object", name),
+            None if object.contains_key("items") => self.visit_type(object, "array", name),
+            None => Ok(self.primitive("value")),
+        }
+    }
+
+    fn visit_ref(&mut self, reference: &str) -> Result<String, String> {
+        // Prefixed so definitions cannot shadow the primitive rules
+        let name = rule_name(&format!(
+            "def-{}",
+            reference.rsplit('/').next().unwrap_or(reference)
+        ));
+        if self.refs_in_progress.contains(reference) || self.rules.contains_key(&name) {
+            // Recursive or already generated definition
+            return Ok(name);
+        }
+        let target = resolve_schema_ref(self.root, reference)
+            .ok_or_else(|| format!("Unresolvable schema reference '{}'", reference))?;
+
+        self.refs_in_progress.insert(reference.to_string());
+        let rule = self.visit(target, &name)?;
+        self.refs_in_progress.remove(reference);
+        if rule != name {
+            self.rules.insert(name.clone(), rule);
+        }
+        Ok(name)
+    }
+
+    fn visit_type(
+        &mut self,
+        object: &serde_json::Map<String, Value>,
+        kind: &str,
+        name: &str,
+    ) -> Result<String, String> {
+        let bound = |key: &str| object.get(key).and_then(|v| v.as_u64());
+        match kind {
+            "string" => {
+                let (min, max) = (bound("minLength"), bound("maxLength"));
+                if min.is_none() && max.is_none() {
+                    return Ok(self.primitive("string"));
+                }
+                self.primitive("char");
+                self.primitive("space");
+                let body = format!(
+                    r#""\"" char{} "\"" space"#,
+                    repetition(min.unwrap_or(0), max)
+                );
+                Ok(self.add_rule(name, body))
+            }
+            "number" | "integer" | "boolean" | "null" => Ok(self.primitive(match kind {
+                "number" => "number",
+                "integer" => "integer",
+                "boolean" => "boolean",
+                _ => "null",
+            })),
+            "array" => {
+                let item = match object.get("items") {
+                    Some(items) => self.visit(items, &format!("{}-item", name))?,
+                    None => self.primitive("value"),
+                };
+                self.primitive("space");
+                let min = bound("minItems").unwrap_or(0);
+                let max = bound("maxItems");
+                let list = match (min, max) {
+                    (_, Some(0)) => String::new(),
+                    (0, max) => format!(
+                        r#"( {item} ("," space {item}){} )?"#,
+                        repetition(0, max.map(|m| m - 1))
+                    ),
+                    (min, max) => format!(
+                        r#"{item} ("," space {item}){}"#,
+                        repetition(min - 1, max.map(|m| m.saturating_sub(1)))
+                    ),
+                };
+                Ok(self.add_rule(name, format!(r#""[" space {} "]" space"#, list)))
+            }
+            "object" => self.visit_object(object, name),
+            other => Err(format!("Unsupported type '{}' at '{}'", other, name)),
+        }
+    }
+
+    fn visit_object(
+        &mut self,
+        object: &serde_json::Map<String, Value>,
+        name: &str,
+    ) -> Result<String, String> {
+        let Some(properties) = object.get("properties").and_then(|p| p.as_object()) else {
+            return match object.get("additionalProperties") {
+                Some(value_schema @ Value::Object(_)) => {
+                    let value = self.visit(value_schema, &format!("{}-value", name))?;
+                    let string = self.primitive("string");
+                    self.primitive("space");
+                    let pair = format!(r#"{string} ":" space {value}"#);
+                    let body = format!(r#""{{" space ( {pair} ("," space {pair})* )? "}}" space"#);
+                    Ok(self.add_rule(name, body))
+                }
+                _ => Ok(self.primitive("object")),
+            };
+        };
+
+        let required: HashSet<&str> = object
+            .get("required")
+            .and_then(|r| r.as_array())
+            .map(|r| r.iter().filter_map(|v| v.as_str()).collect())
+            .unwrap_or_default();
+
+        self.primitive("space");
+        let mut required_pairs = Vec::new();
+        let mut optional_pairs = Vec::new();
+        for (key, property) in properties {
+            let value = self.visit(property, &format!("{}-{}", name, key))?;
+            let pair = format!(
+                r#"{} space ":" space {}"#,
+                gbnf_literal(&Value::String(key.clone()).to_string()),
+                value
+            );
+            if required.contains(key.as_str()) {
+                required_pairs.push(pair);
+            } else {
+                optional_pairs.push(pair);
+            }
+        }
+
+        // Required properties come first in declaration order, each optional one
+        // may follow. Without required properties any optional one can lead.
+        let members = if !required_pairs.is_empty() {
+            let mut members = required_pairs.join(r#" "," space "#);
+            for pair in &optional_pairs {
+                members.push_str(&format!(r#" ( "," space {} )?"#, pair));
+            }
+            members
+        } else if optional_pairs.is_empty() {
+            String::new()
+        } else {
+            let alternatives = (0..optional_pairs.len())
+                .map(|first| {
+                    let mut chain = optional_pairs[first].clone();
+                    for pair in &optional_pairs[first + 1..] {
+                        chain.push_str(&format!(r#" ( "," space {} )?"#, pair));
+                    }
+                    chain
+                })
+                .collect::<Vec<_>>();
+            format!("( {} )?", alternatives.join(" | "))
+        };
+
+        Ok(self.add_rule(name, format!(r#""{{" space {} "}}" space"#, members)))
+    }
+
+    fn format(mut self) -> String {
+        // Pull in the primitives the used ones depend on
+        let dependencies: &[(&str, &[&str])] = &[
+            ("boolean", &["space"]),
+            ("null", &["space"]),
+            ("number", &["integral-part", "decimal-part", "space"]),
+            ("integer", &["integral-part", "space"]),
+            ("string", &["char", "space"]),
+            (
+                "value",
+                &["object", "array", "string", "number", "boolean", "null"],
+            ),
+            ("object", &["string", "value", "space"]),
+            ("array", &["value", "space"]),
+        ];
+        loop {
+            let before = self.used_primitives.len();
+            for (name, deps) in dependencies {
+                if self.used_primitives.contains(name) {
+                    self.used_primitives.extend(deps.iter().copied());
+                }
+            }
+            if self.used_primitives.len() == before {
+                break;
+            }
+        }
+
+        let mut out = String::new();
+        if let Some(root) = self.rules.remove("root") {
+            out.push_str(&format!("root ::= {}\n", root));
+        }
+        for (name, body) in &self.rules {
+            out.push_str(&format!("{} ::= {}\n", name, body));
+        }
+        for (name, body) in PRIMITIVE_RULES {
+            if self.used_primitives.contains(name) && !self.rules.contains_key(*name) {
+                out.push_str(&format!("{} ::= {}\n", name, body));
+            }
+        }
+        out
+    }
+}
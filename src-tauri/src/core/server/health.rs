@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use tauri_plugin_llamacpp::state::SessionInfo;
+use tauri_plugin_llamacpp::LLamaBackendSession;
+use tokio::sync::{broadcast, Mutex};
+
+use super::constants::PROXY_SESSION_HEALTH_EVENT_CAPACITY;
+use super::models::{SessionHealthEvent, SessionHealthOptions, SessionHealthState};
+
+/// Tracks the health of the llama-server sessions and broadcasts changes
+pub struct SessionHealth {
+    states: StdMutex<HashMap<i32, SessionHealthState>>,
+    events: broadcast::Sender<SessionHealthEvent>,
+}
+
+impl Default for SessionHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionHealth {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(PROXY_SESSION_HEALTH_EVENT_CAPACITY);
+        Self {
+            states: StdMutex::new(HashMap::new()),
+            events,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionHealthEvent> {
+        self.events.subscribe()
+    }
+
+    /// Records the session's state, broadcasting it when it changed. A session
+    /// first seen healthy is not announced.
+    pub fn report(&self, info: &SessionInfo, state: SessionHealthState, detail: Option<String>) {
+        let previous = {
+            let mut states = self.states.lock().unwrap();
+            if state == SessionHealthState::Exited {
+                states.remove(&info.pid)
+            } else {
+                states.insert(info.pid, state)
+            }
+        };
+        if previous == Some(state) || (previous.is_none() && state == SessionHealthState::Healthy) {
+            return;
+        }
+
+        match state {
+            SessionHealthState::Healthy | SessionHealthState::Loading => log::info!(
+                "Session {} ({}) is now {:?}",
+                info.pid,
+                info.model_id,
+                state
+            ),
+            _ => log::warn!(
+                "Session {} ({}) is now {:?}: {}",
+                info.pid,
+                info.model_id,
+                state,
+                detail.as_deref().unwrap_or("no details")
+            ),
+        }
+        // No receivers is fine, the event is only informational
+        let _ = self.events.send(SessionHealthEvent {
+            pid: info.pid,
+            model_id: info.model_id.clone(),
+            port: info.port,
+            state,
+            previous,
+            detail,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+
+    /// Removes sessions whose llama-server process has exited and returns them
+    pub fn reap_exited(
+        &self,
+        sessions: &mut HashMap<i32, LLamaBackendSession>,
+    ) -> Vec<SessionInfo> {
+        let mut exited = Vec::new();
+        sessions.retain(|_, session| {
+            let status = match session.child.try_wait() {
+                Ok(None) => return true,
+                Ok(Some(status)) => format!("llama-server exited with {}", status),
+                Err(e) => format!("Failed to check llama-server process: {}", e),
+            };
+            exited.push((session.info.clone(), status));
+            false
+        });
+        exited
+            .into_iter()
+            .map(|(info, status)| {
+                self.report(&info, SessionHealthState::Exited, Some(status));
+                info
+            })
+            .collect()
+    }
+
+    /// Reaps exited sessions, then pings `/health` on the remaining ones
+    pub async fn check_sessions(
+        &self,
+        client: &Client,
+        sessions: &Mutex<HashMap<i32, LLamaBackendSession>>,
+        timeout: Duration,
+    ) {
+        let running: Vec<SessionInfo> = {
+            let mut sessions = sessions.lock().await;
+            self.reap_exited(&mut sessions);
+            sessions.values().map(|s| s.info.clone()).collect()
+        };
+        self.states
+            .lock()
+            .unwrap()
+            .retain(|pid, _| running.iter().any(|info| info.pid == *pid));
+
+        for info in running {
+            let (state, detail) = ping_session(client, &info, timeout).await;
+            self.report(&info, state, detail);
+        }
+    }
+}
+
+async fn ping_session(
+    client: &Client,
+    info: &SessionInfo,
+    timeout: Duration,
+) -> (SessionHealthState, Option<String>) {
+    let mut request = client
+        .get(format!("http://127.0.0.1:{}/health", info.port))
+        .timeout(timeout);
+    if !info.api_key.is_empty() {
+        request = request.bearer_auth(&info.api_key);
+    }
+    match request.send().await {
+        Ok(response) if response.status().is_success() => (SessionHealthState::Healthy, None),
+        Ok(response) if response.status() == StatusCode::SERVICE_UNAVAILABLE => {
+            (SessionHealthState::Loading, None)
+        }
+        Ok(response) => (
+            SessionHealthState::Unhealthy,
+            Some(format!("/health returned {}", response.status())),
+        ),
+        Err(e) => (
+            SessionHealthState::Unhealthy,
+            Some(format!("/health request failed: {}", e)),
+        ),
+    }
+}
+
+/// Whether a failed upstream request can be sent again without side effects
+pub fn is_retryable(method: &hyper::Method, path: &str, error: &reqwest::Error) -> bool {
+    // Nothing reached the session if the connection was never made
+    error.is_connect()
+        || matches!(*method, hyper::Method::GET | hyper::Method::HEAD)
+        || path == "/embeddings"
+}
+
+/// Runs `check_sessions` every interval, picking up option changes between runs
+pub fn spawn_health_checker<F>(
+    health: Arc<SessionHealth>,
+    client: Client,
+    sessions: Arc<Mutex<HashMap<i32, LLamaBackendSession>>>,
+    options: F,
+) -> tokio::task::JoinHandle<()>
+where
+    F: Fn() -> SessionHealthOptions + Send + 'static,
+{
+    tokio::spawn(async move {
+        loop {
+            let options = options();
+            tokio::time::sleep(Duration::from_secs(options.interval_secs.max(1))).await;
+            if options.health_checks {
+                health
+                    .check_sessions(
+                        &client,
+                        &sessions,
+                        Duration::from_secs(options.timeout_secs.max(1)),
+                    )
+                    .await;
+            }
+        }
+    })
+}
@@ -0,0 +1,259 @@
+use std::fs;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hyper::server::conn::Http;
+use hyper::service::Service;
+use hyper::{Body, Request, Response};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::{self, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+
+use super::constants::{PROXY_TLS_CERT_FILE, PROXY_TLS_DIR, PROXY_TLS_KEY_FILE};
+use super::models::TlsOptions;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Counts open client connections and coordinates their shutdown
+#[derive(Clone, Default)]
+pub struct ConnectionTracker {
+    active: Arc<AtomicUsize>,
+    /// Asks connections to finish their current response and close
+    draining: CancellationToken,
+    /// Closes connections immediately, used once the drain timeout expires
+    terminate: CancellationToken,
+}
+
+struct ConnectionGuard {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl ConnectionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    fn track(&self) -> ConnectionGuard {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard {
+            active: self.active.clone(),
+        }
+    }
+
+    /// Lets open connections finish their in-flight responses, closing whatever
+    /// is left once `timeout` expires. Returns false if connections had to be cut off.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.draining.cancel();
+        let deadline = Instant::now() + timeout;
+        while self.active() > 0 {
+            if Instant::now() >= deadline {
+                self.terminate.cancel();
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        true
+    }
+}
+
+/// Builds the rustls configuration for the HTTPS listener.
+/// Uses the configured certificate and key, or a self-signed pair kept in the data folder.
+pub fn load_tls_acceptor(
+    options: &TlsOptions,
+    data_dir: &Path,
+    hostnames: &[String],
+) -> Result<TlsAcceptor, String> {
+    let (cert_path, key_path) = match (&options.cert_path, &options.key_path) {
+        (Some(cert), Some(key)) => (PathBuf::from(cert), PathBuf::from(key)),
+        (None, None) => ensure_self_signed_cert(&data_dir.join(PROXY_TLS_DIR), hostnames)?,
+        _ => return Err("Both cert_path and key_path must be provided for TLS".to_string()),
+    };
+
+    let certs = CertificateDer::pem_file_iter(&cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read certificate {}: {}", cert_path.display(), e))?;
+    let key = PrivateKeyDer::from_pem_file(&key_path)
+        .map_err(|e| format!("Failed to read private key {}: {}", key_path.display(), e))?;
+
+    let mut config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("Invalid TLS protocol configuration: {}", e))?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| format!("Invalid TLS certificate or key: {}", e))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Returns the self-signed certificate paths, generating the pair on first use
+fn ensure_self_signed_cert(dir: &Path, hostnames: &[String]) -> Result<(PathBuf, PathBuf), String> {
+    let cert_path = dir.join(PROXY_TLS_CERT_FILE);
+    let key_path = dir.join(PROXY_TLS_KEY_FILE);
+    if cert_path.exists() && key_path.exists() {
+        return Ok((cert_path, key_path));
+    }
+
+    let mut subject_alt_names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+    for host in hostnames {
+        if !host.is_empty() && host != "0.0.0.0" && !subject_alt_names.contains(host) {
+            subject_alt_names.push(host.clone());
+        }
+    }
+    log::info!(
+        "Generating self-signed certificate for the API server ({:?})",
+        subject_alt_names
+    );
+
+    let certified = rcgen::generate_simple_self_signed(subject_alt_names)
+        .map_err(|e| format!("Failed to generate self-signed certificate: {}", e))?;
+
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    fs::write(&cert_path, certified.cert.pem()).map_err(|e| e.to_string())?;
+    fs::write(&key_path, certified.key_pair.serialize_pem()).map_err(|e| e.to_string())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(&key_path, fs::Permissions::from_mode(0o600));
+    }
+
+    Ok((cert_path, key_path))
+}
+
+/// Serves HTTP on a single accepted connection until it closes or the server shuts down
+async fn serve_connection<IO, S, F>(io: IO, service: S, tracker: ConnectionTracker)
+where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Service<Request<Body>, Response = Response<Body>, Error = hyper::Error, Future = F>
+        + Send
+        + 'static,
+    F: Future<Output = Result<Response<Body>, hyper::Error>> + Send + 'static,
+{
+    let _guard = tracker.track();
+    let conn = Http::new().serve_connection(io, service);
+    tokio::pin!(conn);
+
+    let result = tokio::select! {
+        result = conn.as_mut() => result,
+        _ = tracker.draining.cancelled() => {
+            conn.as_mut().graceful_shutdown();
+            tokio::select! {
+                result = conn.as_mut() => result,
+                _ = tracker.terminate.cancelled() => Ok(()),
+            }
+        }
+    };
+    if let Err(e) = result {
+        log::debug!("Connection closed with error: {}", e);
+    }
+}
+
+/// Accepts TCP connections (optionally wrapped in TLS) until the task is aborted
+pub async fn serve_tcp<S, F, M>(
+    listener: TcpListener,
+    tls: Option<TlsAcceptor>,
+    tracker: ConnectionTracker,
+    make_service: M,
+) -> Result<(), BoxError>
+where
+    M: Fn(SocketAddr) -> S,
+    S: Service<Request<Body>, Response = Response<Body>, Error = hyper::Error, Future = F>
+        + Send
+        + 'static,
+    F: Future<Output = Result<Response<Body>, hyper::Error>> + Send + 'static,
+{
+    loop {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::warn!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let service = make_service(remote_addr);
+        let tracker = tracker.clone();
+
+        match tls.clone() {
+            Some(acceptor) => {
+                tokio::spawn(async move {
+                    match acceptor.accept(stream).await {
+                        Ok(tls_stream) => serve_connection(tls_stream, service, tracker).await,
+                        Err(e) => log::debug!("TLS handshake with {} failed: {}", remote_addr, e),
+                    }
+                });
+            }
+            None => {
+                tokio::spawn(serve_connection(stream, service, tracker));
+            }
+        }
+    }
+}
+
+/// Binds a Unix domain socket that only the current user can connect to
+#[cfg(unix)]
+pub fn bind_unix_socket(path: &Path) -> Result<tokio::net::UnixListener, String> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(format!(
+                "{} already exists and is not a socket",
+                path.display()
+            ));
+        }
+        // Stale socket from a previous run
+        fs::remove_file(path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .map_err(|e| format!("Failed to bind unix socket {}: {}", path.display(), e))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+    Ok(listener)
+}
+
+/// Accepts Unix socket connections until the task is aborted
+#[cfg(unix)]
+pub async fn serve_unix<S, F, M>(
+    listener: tokio::net::UnixListener,
+    tracker: ConnectionTracker,
+    make_service: M,
+) -> Result<(), BoxError>
+where
+    M: Fn() -> S,
+    S: Service<Request<Body>, Response = Response<Body>, Error = hyper::Error, Future = F>
+        + Send
+        + 'static,
+    F: Future<Output = Result<Response<Body>, hyper::Error>> + Send + 'static,
+{
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::warn!("Failed to accept unix socket connection: {}", e);
+                continue;
+            }
+        };
+        tokio::spawn(serve_connection(stream, make_service(), tracker.clone()));
+    }
+}
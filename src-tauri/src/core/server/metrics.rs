@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::constants::{PROXY_LATENCY_BUCKETS_SECS, PROXY_METRICS_KNOWN_PATHS};
+
+#[derive(Default, Clone)]
+struct Histogram {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; PROXY_LATENCY_BUCKETS_SECS.len()];
+        }
+        for (bucket, bound) in self.buckets.iter_mut().zip(PROXY_LATENCY_BUCKETS_SECS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Default, Clone)]
+struct ModelTokens {
+    completion_tokens: u64,
+    generation_seconds: f64,
+    last_tokens_per_second: f64,
+}
+
+/// (metric name, type, help, value accessor) for the per-model token series
+type ModelTokenSeries = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&ModelTokens) -> f64,
+);
+
+#[derive(Default)]
+struct MetricsInner {
+    /// (method, path, status) -> count
+    requests: BTreeMap<(String, String, u16), u64>,
+    /// (path, model) -> upstream latency histogram
+    latencies: BTreeMap<(String, String), Histogram>,
+    tokens: BTreeMap<String, ModelTokens>,
+    upstream_errors: BTreeMap<u16, u64>,
+}
+
+/// Counters exposed by the proxy's `/metrics` route in Prometheus text format
+#[derive(Default)]
+pub struct ProxyMetrics {
+    inner: Mutex<MetricsInner>,
+    in_flight_streams: Arc<AtomicU64>,
+}
+
+/// Decrements the in-flight stream gauge when the response body is finished
+pub struct InFlightGuard {
+    counter: Arc<AtomicU64>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Collapses unknown paths so scrapes cannot be flooded with arbitrary label values
+fn path_label(path: &str) -> String {
+    if PROXY_METRICS_KNOWN_PATHS.contains(&path) {
+        path.to_string()
+    } else {
+        "other".to_string()
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl ProxyMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_request(&self, method: &str, path: &str, status: u16) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner
+            .requests
+            .entry((method.to_string(), path_label(path), status))
+            .or_insert(0) += 1;
+    }
+
+    pub fn stream_started(&self) -> InFlightGuard {
+        self.in_flight_streams.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard {
+            counter: self.in_flight_streams.clone(),
+        }
+    }
+
+    pub fn in_flight_streams(&self) -> u64 {
+        self.in_flight_streams.load(Ordering::Relaxed)
+    }
+
+    /// Records a forwarded request once its response body has been fully delivered
+    pub fn record_upstream(
+        &self,
+        path: &str,
+        model: &str,
+        status: u16,
+        duration: Duration,
+        completion_tokens: Option<u64>,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .latencies
+            .entry((path_label(path), model.to_string()))
+            .or_default()
+            .observe(duration.as_secs_f64());
+
+        if status >= 400 {
+            *inner.upstream_errors.entry(status).or_insert(0) += 1;
+        }
+
+        if let Some(tokens) = completion_tokens.filter(|t| *t > 0) {
+            let seconds = duration.as_secs_f64();
+            let entry = inner.tokens.entry(model.to_string()).or_default();
+            entry.completion_tokens += tokens;
+            entry.generation_seconds += seconds;
+            if seconds > 0.0 {
+                entry.last_tokens_per_second = tokens as f64 / seconds;
+            }
+        }
+    }
+
+    /// Records a request that never got a response from the model server
+    pub fn record_upstream_failure(&self, status: u16) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner.upstream_errors.entry(status).or_insert(0) += 1;
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format
+    pub fn render(&self, running_sessions: usize, queued: &HashMap<String, usize>) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        write_header(
+            &mut out,
+            "jan_proxy_requests_total",
+            "counter",
+            "Requests handled by the proxy server.",
+        );
+        for ((method, path, status), count) in inner.requests.iter() {
+            let _ = writeln!(
+                out,
+                "jan_proxy_requests_total{{method=\"{}\",path=\"{}\",status=\"{}\"}} {}",
+                method, path, status, count
+            );
+        }
+
+        write_header(
+            &mut out,
+            "jan_proxy_upstream_duration_seconds",
+            "histogram",
+            "Time to fully serve a request forwarded to a model.",
+        );
+        for ((path, model), histogram) in inner.latencies.iter() {
+            let labels = format!("path=\"{}\",model=\"{}\"", path, escape_label(model));
+            for (count, bound) in histogram.buckets.iter().zip(PROXY_LATENCY_BUCKETS_SECS) {
+                let _ = writeln!(
+                    out,
+                    "jan_proxy_upstream_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "jan_proxy_upstream_duration_seconds_bucket{{{},le=\"+Inf\"}} {}\n\
+                 jan_proxy_upstream_duration_seconds_sum{{{}}} {}\n\
+                 jan_proxy_upstream_duration_seconds_count{{{}}} {}",
+                labels, histogram.count, labels, histogram.sum, labels, histogram.count
+            );
+        }
+
+        write_header(
+            &mut out,
+            "jan_proxy_in_flight_streams",
+            "gauge",
+            "Responses currently being streamed to clients.",
+        );
+        let _ = writeln!(
+            out,
+            "jan_proxy_in_flight_streams {}",
+            self.in_flight_streams()
+        );
+
+        let per_model: [ModelTokenSeries; 3] = [
+            (
+                "jan_proxy_completion_tokens_total",
+                "counter",
+                "Completion tokens generated per model.",
+                |t| t.completion_tokens as f64,
+            ),
+            (
+                "jan_proxy_generation_seconds_total",
+                "counter",
+                "Time spent serving requests that produced completion tokens.",
+                |t| t.generation_seconds,
+            ),
+            (
+                "jan_proxy_tokens_per_second",
+                "gauge",
+                "Completion tokens per second of the last request per model.",
+                |t| t.last_tokens_per_second,
+            ),
+        ];
+        for (name, kind, help, value) in per_model {
+            write_header(&mut out, name, kind, help);
+            for (model, tokens) in inner.tokens.iter() {
+                let _ = writeln!(
+                    out,
+                    "{}{{model=\"{}\"}} {}",
+                    name,
+                    escape_label(model),
+                    value(tokens)
+                );
+            }
+        }
+
+        write_header(
+            &mut out,
+            "jan_proxy_upstream_errors_total",
+            "counter",
+            "Error responses from model servers by status code.",
+        );
+        for (status, count) in inner.upstream_errors.iter() {
+            let _ = writeln!(
+                out,
+                "jan_proxy_upstream_errors_total{{status=\"{}\"}} {}",
+                status, count
+            );
+        }
+
+        write_header(
+            &mut out,
+            "jan_proxy_queued_requests",
+            "gauge",
+            "Requests waiting for a free model session slot.",
+        );
+        let queued: BTreeMap<_, _> = queued.iter().collect();
+        for (model, count) in queued {
+            let _ = writeln!(
+                out,
+                "jan_proxy_queued_requests{{model=\"{}\"}} {}",
+                escape_label(model),
+                count
+            );
+        }
+
+        write_header(
+            &mut out,
+            "jan_llama_sessions_running",
+            "gauge",
+            "llama-server sessions currently loaded.",
+        );
+        let _ = writeln!(out, "jan_llama_sessions_running {}", running_sessions);
+
+        out
+    }
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
@@ -1,2 +1,23 @@
+pub mod audit_log;
 pub mod commands;
+mod constants;
+pub mod cors;
+pub mod embeddings;
+pub mod grammar;
+pub mod health;
+pub mod listener;
+pub mod metrics;
+pub mod models;
+pub mod multimodal;
+pub mod openapi;
 pub mod proxy;
+pub mod queue;
+pub mod request_log;
+pub mod response_cache;
+pub mod routing;
+pub mod structured_output;
+pub mod tool_loop;
+pub mod vector_store;
+
+#[cfg(test)]
+mod tests;
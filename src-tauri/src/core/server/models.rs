@@ -0,0 +1,433 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::constants::{
+    PROXY_AUDIT_LOG_MAX_FILES, PROXY_AUDIT_LOG_MAX_FILE_BYTES, PROXY_CACHE_DEFAULT_MAX_TOTAL_BYTES,
+    PROXY_CACHE_DEFAULT_TTL_SECS, PROXY_CAPTURED_BODY_MAX_BYTES, PROXY_CORS_DEFAULT_HEADERS,
+    PROXY_CORS_DEFAULT_MAX_AGE_SECS, PROXY_CORS_DEFAULT_METHODS,
+    PROXY_EMBEDDING_BATCH_DEFAULT_MAX_INPUTS, PROXY_EMBEDDING_BATCH_DEFAULT_WINDOW_MS,
+    PROXY_IMAGE_DEFAULT_FETCH_TIMEOUT_SECS, PROXY_IMAGE_DEFAULT_MAX_BYTES,
+    PROXY_QUEUE_DEFAULT_MAX_CONCURRENT, PROXY_QUEUE_DEFAULT_MAX_DEPTH,
+    PROXY_QUEUE_DEFAULT_RETRY_AFTER_SECS, PROXY_QUEUE_DEFAULT_TIMEOUT_SECS,
+    PROXY_REQUEST_LOG_MAX_FILES, PROXY_REQUEST_LOG_MAX_FILE_BYTES,
+    PROXY_SESSION_HEALTH_DEFAULT_INTERVAL_SECS, PROXY_SESSION_HEALTH_DEFAULT_TIMEOUT_SECS,
+    PROXY_STRUCTURED_OUTPUT_DEFAULT_MAX_RETRIES, PROXY_TOOL_LOOP_DEFAULT_MAX_ITERATIONS,
+};
+
+/// Optional features of the proxy server, passed alongside the basic listener settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxyServerOptions {
+    pub request_log: RequestLogOptions,
+    pub request_queue: RequestQueueOptions,
+    /// Client model names (e.g. `gpt-4o-mini`) mapped to session model ids
+    pub model_aliases: HashMap<String, ModelRoute>,
+    pub tls: TlsOptions,
+    /// Additional Unix domain socket to listen on (ignored on Windows)
+    pub unix_socket_path: Option<String>,
+    pub cors: CorsPolicy,
+    pub mcp_tools: McpToolsOptions,
+    pub response_cache: ResponseCacheOptions,
+    pub structured_output: StructuredOutputOptions,
+    pub embedding_batch: EmbeddingBatchOptions,
+    pub vector_stores: VectorStoreOptions,
+    pub audit_log: AuditLogOptions,
+    pub session_health: SessionHealthOptions,
+    pub multimodal: MultimodalOptions,
+}
+
+/// Handling of `image_url` parts in chat requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MultimodalOptions {
+    /// Fetch remote URLs and local paths and send them to the model as data URIs
+    pub inline_images: bool,
+    /// Allow absolute paths and `file://` URLs pointing at image files on this machine
+    pub allow_local_files: bool,
+    /// Directories local image paths must resolve into; no file is readable while empty
+    pub local_image_dirs: Vec<String>,
+    /// Allow fetching images from loopback, private and link-local addresses
+    pub allow_private_hosts: bool,
+    pub max_image_bytes: usize,
+    pub fetch_timeout_secs: u64,
+}
+
+impl Default for MultimodalOptions {
+    fn default() -> Self {
+        Self {
+            inline_images: false,
+            allow_local_files: false,
+            local_image_dirs: Vec::new(),
+            allow_private_hosts: false,
+            max_image_bytes: PROXY_IMAGE_DEFAULT_MAX_BYTES,
+            fetch_timeout_secs: PROXY_IMAGE_DEFAULT_FETCH_TIMEOUT_SECS,
+        }
+    }
+}
+
+/// Crash detection and background health checks for the llama-server sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionHealthOptions {
+    /// Ping each session's `/health` endpoint in the background
+    pub health_checks: bool,
+    pub interval_secs: u64,
+    pub timeout_secs: u64,
+    /// Send a failed request once more when it is safe to repeat
+    pub retry_idempotent: bool,
+}
+
+impl Default for SessionHealthOptions {
+    fn default() -> Self {
+        Self {
+            health_checks: true,
+            interval_secs: PROXY_SESSION_HEALTH_DEFAULT_INTERVAL_SECS,
+            timeout_secs: PROXY_SESSION_HEALTH_DEFAULT_TIMEOUT_SECS,
+            retry_idempotent: true,
+        }
+    }
+}
+
+/// Health of a llama-server session as seen by the proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionHealthState {
+    Healthy,
+    /// `/health` answered 503 while the model is still loading
+    Loading,
+    Unhealthy,
+    /// The process is gone and the session was removed
+    Exited,
+}
+
+/// Emitted as `proxy-session-health` when a session's health changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionHealthEvent {
+    pub pid: i32,
+    pub model_id: String,
+    pub port: i32,
+    pub state: SessionHealthState,
+    pub previous: Option<SessionHealthState>,
+    pub detail: Option<String>,
+    pub timestamp: String,
+}
+
+/// Append-only record of who reached the API, including rejected requests.
+/// Rotated by size, keeping at most `max_files` files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditLogOptions {
+    pub enabled: bool,
+    pub max_file_bytes: u64,
+    pub max_files: usize,
+}
+
+impl Default for AuditLogOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_file_bytes: PROXY_AUDIT_LOG_MAX_FILE_BYTES,
+            max_files: PROXY_AUDIT_LOG_MAX_FILES,
+        }
+    }
+}
+
+/// One request as seen by the audit log
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    pub timestamp: String,
+    /// Peer address, or `unix:<path>` for the Unix socket listener
+    pub remote_addr: String,
+    pub method: String,
+    pub path: String,
+    pub host: Option<String>,
+    pub origin: Option<String>,
+    pub user_agent: Option<String>,
+    /// Id of the bearer token presented, whether or not it was valid
+    pub key_id: Option<String>,
+    pub model: Option<String>,
+    pub status: u16,
+    /// Why the proxy refused the request (e.g. `invalid_host`), unset when it was served
+    pub rejection: Option<String>,
+}
+
+/// Filters for reading the audit log; entries are returned newest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditLogQuery {
+    /// RFC 3339 timestamps bounding the entries returned
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Matches addresses starting with this value, e.g. an IP without the port
+    pub remote_addr: Option<String>,
+    pub key_id: Option<String>,
+    pub model: Option<String>,
+    pub path_prefix: Option<String>,
+    pub rejected_only: bool,
+    pub limit: Option<usize>,
+}
+
+/// Coalesces concurrent `/embeddings` requests for the same session into one upstream call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingBatchOptions {
+    pub enabled: bool,
+    /// How long the first request of a batch waits for others to join
+    pub window_ms: u64,
+    /// Inputs per upstream request; larger requests are sent on their own
+    pub max_batch_inputs: usize,
+}
+
+impl Default for EmbeddingBatchOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_ms: PROXY_EMBEDDING_BATCH_DEFAULT_WINDOW_MS,
+            max_batch_inputs: PROXY_EMBEDDING_BATCH_DEFAULT_MAX_INPUTS,
+        }
+    }
+}
+
+/// Local vector collections served under `/vector_stores`, stored in the data folder
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VectorStoreOptions {
+    pub enabled: bool,
+}
+
+/// Handling of `response_format: {type: "json_schema"}` requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StructuredOutputOptions {
+    /// Compile the schema into a llama-server grammar and validate the output
+    pub enabled: bool,
+    /// Extra attempts when a non-streaming response does not match the schema
+    pub max_retries: u32,
+}
+
+impl Default for StructuredOutputOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_retries: PROXY_STRUCTURED_OUTPUT_DEFAULT_MAX_RETRIES,
+        }
+    }
+}
+
+/// On-disk cache of model responses, keyed by the normalized request and the model file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseCacheOptions {
+    pub enabled: bool,
+    pub ttl_secs: u64,
+    pub max_total_bytes: u64,
+    /// Larger responses are not cached (capped by the proxy's capture limit)
+    pub max_entry_bytes: u64,
+    /// Only cache completions requested with `temperature: 0`
+    pub deterministic_only: bool,
+}
+
+impl Default for ResponseCacheOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: PROXY_CACHE_DEFAULT_TTL_SECS,
+            max_total_bytes: PROXY_CACHE_DEFAULT_MAX_TOTAL_BYTES,
+            max_entry_bytes: PROXY_CAPTURED_BODY_MAX_BYTES as u64,
+            deterministic_only: true,
+        }
+    }
+}
+
+/// Offers the connected MCP servers' tools to chat completions that do not bring
+/// their own, running the calls in the proxy and returning only the final answer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct McpToolsOptions {
+    pub enabled: bool,
+    /// Model round-trips allowed before the request fails
+    pub max_iterations: usize,
+}
+
+impl Default for McpToolsOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_iterations: PROXY_TOOL_LOOP_DEFAULT_MAX_ITERATIONS,
+        }
+    }
+}
+
+/// Cross-origin policy applied to preflight and regular responses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CorsPolicy {
+    /// Exact origins or wildcard patterns such as `https://*.example.com`; `*` allows any origin
+    pub allowed_origins: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    /// Send `Access-Control-Allow-Credentials` to origins matched by a listed pattern;
+    /// never applies to origins only admitted by `*`
+    pub allow_credentials: bool,
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["*".to_string()],
+            allowed_headers: PROXY_CORS_DEFAULT_HEADERS
+                .iter()
+                .map(|h| h.to_string())
+                .collect(),
+            allowed_methods: PROXY_CORS_DEFAULT_METHODS
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+            allow_credentials: false,
+            max_age_secs: PROXY_CORS_DEFAULT_MAX_AGE_SECS,
+        }
+    }
+}
+
+/// HTTPS for the TCP listener. Without a certificate and key, a self-signed
+/// pair is generated in the data folder and reused on later starts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsOptions {
+    pub enabled: bool,
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+}
+
+/// Structured request/response logging for proxied model requests
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestLogOptions {
+    pub enabled: bool,
+    /// Store request and response bodies (required for replay)
+    pub include_bodies: bool,
+    /// JSON keys whose values are replaced before bodies are written (case-insensitive)
+    pub redact_fields: Vec<String>,
+    pub max_file_bytes: u64,
+    pub max_files: usize,
+}
+
+impl Default for RequestLogOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            include_bodies: false,
+            redact_fields: vec![
+                "api_key".to_string(),
+                "apiKey".to_string(),
+                "authorization".to_string(),
+                "password".to_string(),
+                "secret".to_string(),
+                "token".to_string(),
+            ],
+            max_file_bytes: PROXY_REQUEST_LOG_MAX_FILE_BYTES,
+            max_files: PROXY_REQUEST_LOG_MAX_FILES,
+        }
+    }
+}
+
+/// Per-session admission control so llama-server slots are not oversubscribed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestQueueOptions {
+    pub enabled: bool,
+    /// Requests forwarded to one session at the same time (match llama-server `--parallel`)
+    pub max_concurrent: usize,
+    /// Requests allowed to wait for a slot before new ones are rejected
+    pub max_queue_depth: usize,
+    pub queue_timeout_secs: u64,
+    /// Value of the `Retry-After` header on 503 responses
+    pub retry_after_secs: u64,
+}
+
+impl Default for RequestQueueOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_concurrent: PROXY_QUEUE_DEFAULT_MAX_CONCURRENT,
+            max_queue_depth: PROXY_QUEUE_DEFAULT_MAX_DEPTH,
+            queue_timeout_secs: PROXY_QUEUE_DEFAULT_TIMEOUT_SECS,
+            retry_after_secs: PROXY_QUEUE_DEFAULT_RETRY_AFTER_SECS,
+        }
+    }
+}
+
+/// Target of a model alias, with optional fallbacks tried in order when the primary is not loaded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelRoute {
+    pub model_id: String,
+    #[serde(default)]
+    pub fallbacks: Vec<String>,
+}
+
+/// Settings the running server was started or last updated with
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerConfigSnapshot {
+    pub host: String,
+    pub port: u16,
+    pub prefix: String,
+    pub api_key_required: bool,
+    pub trusted_hosts: Vec<String>,
+    pub options: ProxyServerOptions,
+}
+
+/// Returned by `get_server_status`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ServerStatus {
+    pub running: bool,
+    /// e.g. `https://127.0.0.1:1337`
+    pub address: Option<String>,
+    pub unix_socket_path: Option<String>,
+    pub uptime_secs: Option<u64>,
+    pub active_connections: usize,
+    pub in_flight_streams: u64,
+    pub config: Option<ServerConfigSnapshot>,
+}
+
+/// Settings that can be changed while the server is running. Omitted fields are left as is.
+/// The listener settings in `options` (`tls`, `unix_socket_path`) still require a restart.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ServerConfigUpdate {
+    pub prefix: Option<String>,
+    pub api_key: Option<String>,
+    pub trusted_hosts: Option<Vec<String>>,
+    pub options: Option<ProxyServerOptions>,
+}
+
+/// A single proxied request as written to the request log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestLogEntry {
+    pub id: String,
+    pub timestamp: String,
+    pub key_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub model: Option<String>,
+    pub stream: bool,
+    pub status: Option<u16>,
+    /// Time until the upstream response headers arrived
+    pub latency_ms: u64,
+    /// Time until the response body was fully delivered
+    pub duration_ms: u64,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub error: Option<String>,
+    pub request_body: Option<serde_json::Value>,
+    pub response_body: Option<String>,
+}
+
+/// Result of re-sending a logged request
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayResult {
+    pub request_id: String,
+    pub status: u16,
+    pub latency_ms: u64,
+    pub body: String,
+}
@@ -0,0 +1,276 @@
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use base64::{engine::general_purpose, Engine as _};
+use futures_util::StreamExt;
+use reqwest::{redirect, Client};
+use serde_json::Value;
+use url::Url;
+
+use super::constants::PROXY_IMAGE_MAX_REDIRECTS;
+use super::models::MultimodalOptions;
+
+/// Mutable references to the `image_url.url` strings in a chat request's messages
+fn image_urls(body: &mut Value) -> Vec<&mut Value> {
+    let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+        return Vec::new();
+    };
+    messages
+        .iter_mut()
+        .filter_map(|message| message.get_mut("content")?.as_array_mut())
+        .flatten()
+        .filter(|part| part["type"] == "image_url")
+        .filter_map(|part| {
+            let image_url = part.get_mut("image_url")?;
+            // Accept the shorthand `"image_url": "<url>"` as well as `{url}`
+            if image_url.is_string() {
+                Some(image_url)
+            } else {
+                image_url.get_mut("url")
+            }
+        })
+        .collect()
+}
+
+/// Whether any message of a chat request carries an `image_url` part
+pub fn has_image_content(body: &Value) -> bool {
+    body["messages"].as_array().is_some_and(|messages| {
+        messages.iter().any(|message| {
+            message["content"]
+                .as_array()
+                .is_some_and(|parts| parts.iter().any(|part| part["type"] == "image_url"))
+        })
+    })
+}
+
+/// Identifies the image format from the file signature
+fn sniff_image_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if bytes.starts_with(b"BM") {
+        Some("image/bmp")
+    } else {
+        None
+    }
+}
+
+fn to_data_uri(bytes: &[u8], mime: &str) -> String {
+    format!(
+        "data:{};base64,{}",
+        mime,
+        general_purpose::STANDARD.encode(bytes)
+    )
+}
+
+/// Whether an address is reachable from outside this machine's networks: loopback,
+/// private, link-local, unspecified and broadcast addresses are not
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (v4.octets()[0] == 100 && v4.octets()[1] & 0xC0 == 64))
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_ip(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    // Unique local fc00::/7 and link-local fe80::/10
+                    || first & 0xFE00 == 0xFC00
+                    || first & 0xFFC0 == 0xFE80)
+            }
+        },
+    }
+}
+
+/// Resolves the host of an image URL, refusing hosts on private networks unless allowed
+async fn resolve_image_host(
+    url: &Url,
+    options: &MultimodalOptions,
+) -> Result<Vec<SocketAddr>, String> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("Image URL has no host: {}", url))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| format!("Image URL has no port: {}", url))?;
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+        .await
+        .map_err(|e| format!("Failed to resolve image host {}: {}", host, e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("Failed to resolve image host {}", host));
+    }
+    if !options.allow_private_hosts && addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+        return Err(format!(
+            "Image host {} is on a private network and may not be fetched",
+            host
+        ));
+    }
+    Ok(addrs)
+}
+
+async fn fetch_remote_image(url: &str, options: &MultimodalOptions) -> Result<String, String> {
+    let mut target = Url::parse(url).map_err(|e| format!("Invalid image URL {}: {}", url, e))?;
+    let mut redirects = 0;
+    let response = loop {
+        if !matches!(target.scheme(), "http" | "https") {
+            return Err(format!("Image URL must use http or https: {}", target));
+        }
+        // Connect to the addresses that were checked, so a second DNS answer cannot point
+        // the request elsewhere, and follow redirects here so every hop is checked
+        let addrs = resolve_image_host(&target, options).await?;
+        let client = Client::builder()
+            .redirect(redirect::Policy::none())
+            .resolve_to_addrs(target.host_str().unwrap_or_default(), &addrs)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let response = client
+            .get(target.clone())
+            .timeout(Duration::from_secs(options.fetch_timeout_secs))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch image {}: {}", url, e))?;
+        if !response.status().is_redirection() {
+            break response;
+        }
+        redirects += 1;
+        if redirects > PROXY_IMAGE_MAX_REDIRECTS {
+            return Err(format!("Too many redirects fetching image {}", url));
+        }
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| format!("Failed to fetch image {}: redirect without a location", url))?;
+        target = target
+            .join(location)
+            .map_err(|e| format!("Invalid redirect for image {}: {}", url, e))?;
+    };
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to fetch image {}: server returned {}",
+            url,
+            response.status()
+        ));
+    }
+    let declared_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_lowercase())
+        .filter(|v| v.starts_with("image/"));
+
+    let mut bytes = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Failed to fetch image {}: {}", url, e))?;
+        if bytes.len() + chunk.len() > options.max_image_bytes {
+            return Err(format!(
+                "Image {} is larger than {} bytes",
+                url, options.max_image_bytes
+            ));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    let mime = match declared_type {
+        Some(mime) => mime,
+        None => sniff_image_type(&bytes)
+            .ok_or_else(|| format!("{} did not return an image", url))?
+            .to_string(),
+    };
+    Ok(to_data_uri(&bytes, &mime))
+}
+
+/// Canonicalizes a local image path and checks that it lies in one of the allowed directories
+async fn allowed_local_path(path: PathBuf, options: &MultimodalOptions) -> Result<PathBuf, String> {
+    let display = path.display().to_string();
+    let path = tokio::fs::canonicalize(&path)
+        .await
+        .map_err(|e| format!("Failed to read image {}: {}", display, e))?;
+    for dir in &options.local_image_dirs {
+        match tokio::fs::canonicalize(dir).await {
+            Ok(dir) if path.starts_with(&dir) => return Ok(path),
+            Ok(_) => {}
+            Err(e) => log::warn!("Ignoring image directory {}: {}", dir, e),
+        }
+    }
+    Err(format!(
+        "Image {} is outside the directories local images may be read from",
+        display
+    ))
+}
+
+async fn read_local_image(path: PathBuf, options: &MultimodalOptions) -> Result<String, String> {
+    let display = path.display().to_string();
+    let path = allowed_local_path(path, options).await?;
+    let metadata = tokio::fs::metadata(&path)
+        .await
+        .map_err(|e| format!("Failed to read image {}: {}", display, e))?;
+    if metadata.len() > options.max_image_bytes as u64 {
+        return Err(format!(
+            "Image {} is larger than {} bytes",
+            display, options.max_image_bytes
+        ));
+    }
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|e| format!("Failed to read image {}: {}", display, e))?;
+    // Only files that really are images may be sent to the model
+    let mime = sniff_image_type(&bytes)
+        .ok_or_else(|| format!("{} is not a supported image file", display))?;
+    Ok(to_data_uri(&bytes, mime))
+}
+
+/// Replaces remote URLs and local file paths in `image_url` parts with base64
+/// data URIs, returning how many images were inlined
+pub async fn inline_images(body: &mut Value, options: &MultimodalOptions) -> Result<usize, String> {
+    let mut inlined = 0;
+    for url in image_urls(body) {
+        let Some(source) = url.as_str().map(str::to_string) else {
+            return Err("'image_url.url' must be a string".to_string());
+        };
+        let data_uri = if source.starts_with("data:") {
+            continue;
+        } else if source.starts_with("http://") || source.starts_with("https://") {
+            fetch_remote_image(&source, options).await?
+        } else {
+            if !options.allow_local_files {
+                return Err(format!(
+                    "Local image paths are disabled on this server: {}",
+                    source
+                ));
+            }
+            let path = if source.starts_with("file://") {
+                Url::parse(&source)
+                    .ok()
+                    .and_then(|u| u.to_file_path().ok())
+                    .ok_or_else(|| format!("Invalid file URL: {}", source))?
+            } else {
+                PathBuf::from(&source)
+            };
+            if !path.is_absolute() {
+                return Err(format!("Image path must be absolute or a URL: {}", source));
+            }
+            read_local_image(path, options).await?
+        };
+        *url = Value::String(data_uri);
+        inlined += 1;
+    }
+    Ok(inlined)
+}
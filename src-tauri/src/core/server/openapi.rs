@@ -0,0 +1,274 @@
+use serde_json::{json, Map, Value};
+
+/// What the running proxy serves, used to describe it to clients
+pub struct ApiDescription<'a> {
+    pub version: &'a str,
+    pub prefix: &'a str,
+    pub api_key_required: bool,
+    pub vector_stores: bool,
+}
+
+fn json_body(schema: &str) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": { "$ref": format!("#/components/schemas/{}", schema) } } }
+    })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } }
+    })
+}
+
+fn error_responses(responses: &mut Map<String, Value>, api_key_required: bool) {
+    let error = json!({ "$ref": "#/components/schemas/Error" });
+    responses.insert(
+        "400".into(),
+        json_response("Invalid request", error.clone()),
+    );
+    if api_key_required {
+        responses.insert(
+            "401".into(),
+            json!({ "description": "Missing or invalid API key" }),
+        );
+    }
+    responses.insert(
+        "404".into(),
+        json!({ "description": "No running session for the requested model" }),
+    );
+    responses.insert(
+        "502".into(),
+        json_response("The model session failed", error),
+    );
+    responses.insert(
+        "503".into(),
+        json!({ "description": "The model's request queue is full or timed out" }),
+    );
+}
+
+fn api_base<'a>(description: &ApiDescription<'a>) -> &'a str {
+    if description.prefix.is_empty() {
+        "/"
+    } else {
+        description.prefix
+    }
+}
+
+fn operation(
+    summary: &str,
+    body: Option<&str>,
+    success: Value,
+    description: &ApiDescription,
+) -> Value {
+    let mut responses = Map::new();
+    responses.insert("200".into(), success);
+    error_responses(&mut responses, description.api_key_required);
+
+    let mut operation = json!({ "summary": summary, "responses": responses });
+    if let Some(schema) = body {
+        operation["requestBody"] = json_body(schema);
+    }
+    operation
+}
+
+/// Builds the OpenAPI 3 document for the routes the proxy currently serves
+pub fn openapi_document(description: &ApiDescription) -> Value {
+    let any_object = json!({ "type": "object" });
+    let completion = json!({
+        "description": "A JSON completion, or an SSE stream when `stream` is true",
+        "content": {
+            "application/json": { "schema": any_object },
+            "text/event-stream": { "schema": { "type": "string" } }
+        }
+    });
+
+    let mut paths = Map::new();
+    paths.insert(
+        "/chat/completions".into(),
+        json!({ "post": operation("Create a chat completion", Some("ChatCompletionRequest"), completion.clone(), description) }),
+    );
+    paths.insert(
+        "/completions".into(),
+        json!({ "post": operation("Create a text completion", Some("CompletionRequest"), completion, description) }),
+    );
+    paths.insert(
+        "/embeddings".into(),
+        json!({ "post": operation("Create embeddings", Some("EmbeddingRequest"), json_response("Embedding vectors", any_object.clone()), description) }),
+    );
+    paths.insert(
+        "/models".into(),
+        json!({ "get": operation("List the loaded models and aliases", None, json_response("Model list", any_object.clone()), description) }),
+    );
+    paths.insert(
+        "/metrics".into(),
+        json!({ "get": operation("Prometheus metrics", None, json!({
+            "description": "Metrics in the Prometheus text format",
+            "content": { "text/plain": { "schema": { "type": "string" } } }
+        }), description) }),
+    );
+
+    if description.vector_stores {
+        let store = json!({ "$ref": "#/components/schemas/VectorStore" });
+        paths.insert(
+            "/vector_stores".into(),
+            json!({
+                "get": operation("List vector stores", None, json_response("Vector stores", any_object.clone()), description),
+                "post": operation("Create a vector store", Some("CreateVectorStoreRequest"), json_response("The new vector store", store.clone()), description),
+            }),
+        );
+        let id_parameter = json!([{ "name": "id", "in": "path", "required": true, "schema": { "type": "string" } }]);
+        paths.insert(
+            "/vector_stores/{id}".into(),
+            json!({
+                "parameters": id_parameter,
+                "get": operation("Get a vector store", None, json_response("The vector store", store), description),
+                "delete": operation("Delete a vector store", None, json_response("Deletion status", any_object.clone()), description),
+            }),
+        );
+        paths.insert(
+            "/vector_stores/{id}/upsert".into(),
+            json!({
+                "parameters": id_parameter,
+                "post": operation("Insert or replace records", Some("UpsertVectorsRequest"), json_response("Upsert counts", any_object.clone()), description),
+            }),
+        );
+        paths.insert(
+            "/vector_stores/{id}/query".into(),
+            json!({
+                "parameters": id_parameter,
+                "post": operation("Find the most similar records", Some("QueryVectorsRequest"), json_response("Matching records, best first", any_object), description),
+            }),
+        );
+    }
+
+    let mut schemas = json!({
+        "Error": {
+            "type": "object",
+            "properties": { "error": { "type": "object", "properties": { "message": { "type": "string" } } } }
+        },
+        "ChatCompletionRequest": {
+            "type": "object",
+            "required": ["model", "messages"],
+            "properties": {
+                "model": { "type": "string", "description": "A loaded model id or configured alias" },
+                "messages": { "type": "array", "items": { "type": "object" } },
+                "stream": { "type": "boolean" },
+                "temperature": { "type": "number" },
+                "tools": { "type": "array", "items": { "type": "object" } },
+                "response_format": {
+                    "type": "object",
+                    "description": "`{type: \"json_schema\", json_schema: {name, schema}}` constrains the output to a JSON Schema"
+                }
+            }
+        },
+        "CompletionRequest": {
+            "type": "object",
+            "required": ["model", "prompt"],
+            "properties": {
+                "model": { "type": "string" },
+                "prompt": { "type": "string" },
+                "stream": { "type": "boolean" },
+                "response_format": { "type": "object" }
+            }
+        },
+        "EmbeddingRequest": {
+            "type": "object",
+            "required": ["model", "input"],
+            "properties": {
+                "model": { "type": "string" },
+                "input": { "oneOf": [
+                    { "type": "string" },
+                    { "type": "array", "items": { "type": "string" } }
+                ] }
+            }
+        }
+    });
+    if description.vector_stores {
+        let vector_schemas = json!({
+            "VectorStore": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string" },
+                    "name": { "type": "string" },
+                    "created_at": { "type": "integer" },
+                    "model": { "type": "string", "nullable": true },
+                    "dimensions": { "type": "integer", "nullable": true },
+                    "record_count": { "type": "integer" }
+                }
+            },
+            "CreateVectorStoreRequest": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "model": { "type": "string", "description": "Embedding model for records and queries given as text" }
+                }
+            },
+            "UpsertVectorsRequest": {
+                "type": "object",
+                "required": ["records"],
+                "properties": {
+                    "records": { "type": "array", "items": {
+                        "type": "object",
+                        "required": ["id"],
+                        "properties": {
+                            "id": { "type": "string" },
+                            "text": { "type": "string" },
+                            "embedding": { "type": "array", "items": { "type": "number" } },
+                            "metadata": { "type": "object" }
+                        }
+                    } },
+                    "model": { "type": "string" }
+                }
+            },
+            "QueryVectorsRequest": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "embedding": { "type": "array", "items": { "type": "number" } },
+                    "top_k": { "type": "integer" },
+                    "filter": { "type": "object", "description": "Exact metadata values records must have" },
+                    "model": { "type": "string" }
+                }
+            }
+        });
+        if let (Some(schemas), Some(extra)) = (schemas.as_object_mut(), vector_schemas.as_object())
+        {
+            schemas.extend(extra.clone());
+        }
+    }
+
+    let mut document = json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Jan Local API Server",
+            "version": description.version,
+            "description": "OpenAI-compatible API for the models running in Jan"
+        },
+        "servers": [{ "url": api_base(description) }],
+        "paths": paths,
+        "components": { "schemas": schemas }
+    });
+    if description.api_key_required {
+        document["components"]["securitySchemes"] =
+            json!({ "bearerAuth": { "type": "http", "scheme": "bearer" } });
+        document["security"] = json!([{ "bearerAuth": [] }]);
+    }
+    document
+}
+
+/// The `/` landing document: version, loaded models and how to authenticate
+pub fn landing_document(description: &ApiDescription, models: &[String]) -> Value {
+    json!({
+        "name": "Jan Local API Server",
+        "version": description.version,
+        "models": models,
+        "auth": {
+            "required": description.api_key_required,
+            "scheme": description.api_key_required.then_some("bearer"),
+        },
+        "api_base": api_base(description),
+        "openapi_url": format!("{}/openapi.json", description.prefix),
+    })
+}
@@ -1,17 +1,47 @@
+use futures_util::future::BoxFuture;
 use futures_util::StreamExt;
 use hyper::body::Bytes;
-use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Request, Response, Server, StatusCode};
-use jan_utils::{is_cors_header, is_valid_host, remove_prefix};
+use hyper::service::service_fn;
+use hyper::{Body, Request, Response, StatusCode};
+use jan_utils::{api_key_id, is_cors_header, is_valid_host, remove_prefix};
 use reqwest::Client;
 use serde_json;
 use std::collections::HashMap;
-use std::convert::Infallible;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use tauri_plugin_llamacpp::LLamaBackendSession;
+use tokio::net::TcpListener;
 use tokio::sync::Mutex;
-
+use tokio::task::JoinHandle;
+
+use super::audit_log::AuditLogger;
+use super::constants::{
+    PROXY_CACHE_DIR, PROXY_CACHE_STATUS_HEADER, PROXY_QUEUE_POSITION_HEADER,
+    PROXY_UPSTREAM_RETRY_DELAY_MS, PROXY_VECTOR_STORE_DIR, PROXY_WHITELISTED_PATHS,
+};
+use super::cors::{add_cors_headers, add_preflight_headers, CorsDenial};
+use super::embeddings::{embedding_vectors, send_embeddings, EmbeddingBatcher, EmbeddingTarget};
+use super::health::{is_retryable, spawn_health_checker, SessionHealth};
+#[cfg(unix)]
+use super::listener::{bind_unix_socket, serve_unix};
+use super::listener::{load_tls_acceptor, serve_tcp, ConnectionTracker};
+use super::metrics::ProxyMetrics;
+use super::models::{
+    AuditLogEntry, CorsPolicy, ModelRoute, MultimodalOptions, ProxyServerOptions, RequestLogEntry,
+    ServerConfigSnapshot, ServerConfigUpdate, ServerStatus, SessionHealthEvent,
+    SessionHealthOptions, StructuredOutputOptions,
+};
+use super::multimodal::{has_image_content, inline_images};
+use super::openapi::{landing_document, openapi_document, ApiDescription};
+use super::queue::{AdmissionError, AdmissionQueue};
+use super::request_log::{RequestLogger, ResponseCapture};
+use super::response_cache::ResponseCache;
+use super::routing::resolve_model_id;
+use super::structured_output::{run_structured_completion, StructuredOutput};
+use super::tool_loop::{completion_to_sse, run_tool_loop, ToolProvider};
+use super::vector_store::{TextEmbedder, VectorStoreManager};
 use crate::core::state::ServerHandle;
 
 /// Configuration for the proxy server
@@ -20,6 +50,72 @@ struct ProxyConfig {
     prefix: String,
     proxy_api_key: String,
     trusted_hosts: Vec<Vec<String>>,
+    request_log: Option<Arc<RequestLogger>>,
+    metrics: Arc<ProxyMetrics>,
+    request_queue: Option<Arc<AdmissionQueue>>,
+    model_aliases: Arc<HashMap<String, ModelRoute>>,
+    cors: Arc<CorsPolicy>,
+    tool_provider: Option<Arc<dyn ToolProvider>>,
+    tool_loop_max_iterations: usize,
+    response_cache: Option<Arc<ResponseCache>>,
+    structured_output: StructuredOutputOptions,
+    embedding_batcher: Option<Arc<EmbeddingBatcher>>,
+    vector_stores: Option<Arc<VectorStoreManager>>,
+    audit_log: Option<Arc<AuditLogger>>,
+    session_health: Arc<SessionHealth>,
+    session_health_options: SessionHealthOptions,
+    multimodal: MultimodalOptions,
+}
+
+/// Embeds vector store text with the running session serving the requested model
+struct SessionEmbedder {
+    client: Client,
+    config: ProxyConfig,
+    sessions: Arc<Mutex<HashMap<i32, LLamaBackendSession>>>,
+}
+
+impl SessionEmbedder {
+    async fn target(&self, model: &str) -> Result<(EmbeddingTarget, String), String> {
+        let sessions = self.sessions.lock().await;
+        let session = resolve_model_id(
+            model,
+            &self.config.model_aliases,
+            sessions.values().map(|s| s.info.model_id.as_str()),
+        )
+        .and_then(|id| sessions.values().find(|s| s.info.model_id == id))
+        .ok_or_else(|| format!("No running session found for model '{}'", model))?;
+
+        let model_id = session.info.model_id.clone();
+        let target = EmbeddingTarget {
+            client: self.client.clone(),
+            upstream_url: format!("http://127.0.0.1:{}/embeddings", session.info.port),
+            session_api_key: Some(session.info.api_key.clone()),
+            admission: self
+                .config
+                .request_queue
+                .clone()
+                .map(|queue| (queue, model_id.clone())),
+        };
+        Ok((target, model_id))
+    }
+}
+
+impl TextEmbedder for SessionEmbedder {
+    fn embed(
+        &self,
+        model: String,
+        texts: Vec<String>,
+    ) -> BoxFuture<'_, Result<Vec<Vec<f32>>, String>> {
+        Box::pin(async move {
+            let (target, model_id) = self.target(&model).await?;
+            let body = serde_json::json!({ "model": model_id, "input": texts });
+            let response = match &self.config.embedding_batcher {
+                Some(batcher) => batcher.embed(target, body).await?,
+                None => send_embeddings(&target, &body).await?,
+            };
+            embedding_vectors(&response)
+        })
+    }
 }
 
 /// Determines the final destination path based on the original request path
@@ -27,12 +123,59 @@ fn get_destination_path(original_path: &str, prefix: &str) -> String {
     remove_prefix(original_path, prefix)
 }
 
-/// Handles the proxy request logic
+/// Serves a single request and records it in the proxy metrics and audit log
+async fn handle_request(
+    req: Request<Body>,
+    client: Client,
+    config: ProxyConfig,
+    sessions: Arc<Mutex<HashMap<i32, LLamaBackendSession>>>,
+    remote_addr: String,
+) -> Result<Response<Body>, hyper::Error> {
+    let method = req.method().clone();
+    let path = get_destination_path(req.uri().path(), &config.prefix);
+    let metrics = config.metrics.clone();
+    let audit_log = config.audit_log.clone();
+    let mut audit = AuditLogEntry::from_request(&req, &path, remote_addr);
+
+    let response = proxy_request(req, client, config, sessions, &mut audit).await?;
+    let status = response.status().as_u16();
+    metrics.record_request(method.as_str(), &path, status);
+    if let Some(audit_log) = audit_log {
+        audit.status = status;
+        audit_log.append(&audit).await;
+    }
+    Ok(response)
+}
+
+/// OpenAI-style 400 response for requests the model cannot serve as sent
+fn invalid_request_response(
+    config: &ProxyConfig,
+    origin: &str,
+    message: String,
+    code: &str,
+) -> Response<Body> {
+    let body = serde_json::json!({
+        "error": {
+            "message": message,
+            "type": "invalid_request_error",
+            "code": code,
+        }
+    });
+    let builder = Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .header(hyper::header::CONTENT_TYPE, "application/json");
+    add_cors_headers(builder, origin, &config.cors)
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// Handles the proxy request logic. Rejections and the resolved model are noted on `audit`.
 async fn proxy_request(
     req: Request<Body>,
     client: Client,
     config: ProxyConfig,
     sessions: Arc<Mutex<HashMap<i32, LLamaBackendSession>>>,
+    audit: &mut AuditLogEntry,
 ) -> Result<Response<Body>, hyper::Error> {
     if req.method() == hyper::Method::OPTIONS {
         log::debug!(
@@ -60,23 +203,48 @@ async fn proxy_request(
             .and_then(|v| v.to_str().ok())
             .unwrap_or("");
 
-        let allowed_methods = ["GET", "POST", "PUT", "DELETE", "OPTIONS", "PATCH"];
-        let method_allowed = requested_method.is_empty()
-            || allowed_methods
-                .iter()
-                .any(|&method| method.eq_ignore_ascii_case(requested_method));
+        let requested_headers = req
+            .headers()
+            .get("Access-Control-Request-Headers")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
 
-        if !method_allowed {
-            log::warn!("CORS preflight: Method '{}' not allowed", requested_method);
+        if let Err(denial) =
+            config
+                .cors
+                .check_preflight(origin, requested_method, requested_headers)
+        {
+            audit.reject(match denial {
+                CorsDenial::Origin => "origin_not_allowed",
+                CorsDenial::Method => "method_not_allowed",
+                CorsDenial::Headers => "headers_not_allowed",
+            });
+            let (status, message) = match denial {
+                CorsDenial::Origin => {
+                    log::warn!("CORS preflight: Origin '{}' not allowed", origin);
+                    (StatusCode::FORBIDDEN, "Origin not allowed")
+                }
+                CorsDenial::Method => {
+                    log::warn!("CORS preflight: Method '{}' not allowed", requested_method);
+                    (StatusCode::METHOD_NOT_ALLOWED, "Method not allowed")
+                }
+                CorsDenial::Headers => {
+                    log::warn!(
+                        "CORS preflight: Some requested headers not allowed: {}",
+                        requested_headers
+                    );
+                    (StatusCode::FORBIDDEN, "Headers not allowed")
+                }
+            };
             return Ok(Response::builder()
-                .status(StatusCode::METHOD_NOT_ALLOWED)
-                .body(Body::from("Method not allowed"))
+                .status(status)
+                .header("Vary", "Origin")
+                .body(Body::from(message))
                 .unwrap());
         }
 
         let request_path = req.uri().path();
-        let whitelisted_paths = ["/", "/openapi.json", "/favicon.ico"];
-        let is_whitelisted_path = whitelisted_paths.contains(&request_path);
+        let is_whitelisted_path = PROXY_WHITELISTED_PATHS.contains(&request_path);
 
         let is_trusted = if is_whitelisted_path {
             log::debug!(
@@ -102,88 +270,19 @@ async fn proxy_request(
                 host,
                 request_path
             );
+            audit.reject("invalid_host");
             return Ok(Response::builder()
                 .status(StatusCode::FORBIDDEN)
                 .body(Body::from("Host not allowed"))
                 .unwrap());
         }
 
-        let requested_headers = req
-            .headers()
-            .get("Access-Control-Request-Headers")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("");
-
-        let allowed_headers = [
-            "accept",
-            "accept-language",
-            "authorization",
-            "cache-control",
-            "connection",
-            "content-type",
-            "dnt",
-            "host",
-            "if-modified-since",
-            "keep-alive",
-            "origin",
-            "user-agent",
-            "x-api-key",
-            "x-csrf-token",
-            "x-forwarded-for",
-            "x-forwarded-host",
-            "x-forwarded-proto",
-            "x-requested-with",
-            "x-stainless-arch",
-            "x-stainless-lang",
-            "x-stainless-os",
-            "x-stainless-package-version",
-            "x-stainless-retry-count",
-            "x-stainless-runtime",
-            "x-stainless-runtime-version",
-            "x-stainless-timeout",
-        ];
-
-        let headers_valid = if requested_headers.is_empty() {
-            true
-        } else {
-            requested_headers
-                .split(',')
-                .map(|h| h.trim())
-                .all(|header| {
-                    allowed_headers
-                        .iter()
-                        .any(|&allowed| allowed.eq_ignore_ascii_case(header))
-                })
-        };
-
-        if !headers_valid {
-            log::warn!(
-                "CORS preflight: Some requested headers not allowed: {}",
-                requested_headers
-            );
-            return Ok(Response::builder()
-                .status(StatusCode::FORBIDDEN)
-                .body(Body::from("Headers not allowed"))
-                .unwrap());
-        }
-
-        let mut response = Response::builder()
-            .status(StatusCode::OK)
-            .header("Access-Control-Allow-Methods", allowed_methods.join(", "))
-            .header("Access-Control-Allow-Headers", allowed_headers.join(", "))
-            .header("Access-Control-Max-Age", "86400")
-            .header(
-                "Vary",
-                "Origin, Access-Control-Request-Method, Access-Control-Request-Headers",
-            );
-
-        if !origin.is_empty() {
-            response = response
-                .header("Access-Control-Allow-Origin", origin)
-                .header("Access-Control-Allow-Credentials", "true");
-        } else {
-            response = response.header("Access-Control-Allow-Origin", "*");
-        }
+        let response = add_preflight_headers(
+            Response::builder().status(StatusCode::OK),
+            origin,
+            requested_headers,
+            &config.cors,
+        );
 
         log::debug!(
             "CORS preflight response: host_trusted={}, origin='{}'",
@@ -214,32 +313,39 @@ async fn proxy_request(
 
     let path = get_destination_path(original_path, &config.prefix);
     let method = parts.method.clone();
+    let key_id = parts
+        .headers
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(api_key_id);
+
+    if config.cors.check_request(&origin_header).is_err() {
+        log::warn!("CORS: Origin '{}' not allowed for {}", origin_header, path);
+        audit.reject("origin_not_allowed");
+        return Ok(Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .header("Vary", "Origin")
+            .body(Body::from("Origin not allowed"))
+            .unwrap());
+    }
 
-    let whitelisted_paths = ["/", "/openapi.json", "/favicon.ico"];
-    let is_whitelisted_path = whitelisted_paths.contains(&path.as_str());
+    let is_whitelisted_path = PROXY_WHITELISTED_PATHS.contains(&path.as_str());
 
     if !is_whitelisted_path {
         if !host_header.is_empty() {
             if !is_valid_host(&host_header, &config.trusted_hosts) {
+                audit.reject("invalid_host");
                 let mut error_response = Response::builder().status(StatusCode::FORBIDDEN);
-                error_response = add_cors_headers_with_host_and_origin(
-                    error_response,
-                    &host_header,
-                    &origin_header,
-                    &config.trusted_hosts,
-                );
+                error_response = add_cors_headers(error_response, &origin_header, &config.cors);
                 return Ok(error_response
                     .body(Body::from("Invalid host header"))
                     .unwrap());
             }
         } else {
+            audit.reject("missing_host");
             let mut error_response = Response::builder().status(StatusCode::BAD_REQUEST);
-            error_response = add_cors_headers_with_host_and_origin(
-                error_response,
-                &host_header,
-                &origin_header,
-                &config.trusted_hosts,
-            );
+            error_response = add_cors_headers(error_response, &origin_header, &config.cors);
             return Ok(error_response
                 .body(Body::from("Missing host header"))
                 .unwrap());
@@ -253,25 +359,17 @@ async fn proxy_request(
             let auth_str = authorization.to_str().unwrap_or("");
 
             if auth_str.strip_prefix("Bearer ") != Some(config.proxy_api_key.as_str()) {
+                audit.reject("invalid_api_key");
                 let mut error_response = Response::builder().status(StatusCode::UNAUTHORIZED);
-                error_response = add_cors_headers_with_host_and_origin(
-                    error_response,
-                    &host_header,
-                    &origin_header,
-                    &config.trusted_hosts,
-                );
+                error_response = add_cors_headers(error_response, &origin_header, &config.cors);
                 return Ok(error_response
                     .body(Body::from("Invalid or missing authorization token"))
                     .unwrap());
             }
         } else {
+            audit.reject("missing_api_key");
             let mut error_response = Response::builder().status(StatusCode::UNAUTHORIZED);
-            error_response = add_cors_headers_with_host_and_origin(
-                error_response,
-                &host_header,
-                &origin_header,
-                &config.trusted_hosts,
-            );
+            error_response = add_cors_headers(error_response, &origin_header, &config.cors);
             return Ok(error_response
                 .body(Body::from("Missing authorization header"))
                 .unwrap());
@@ -284,19 +382,19 @@ async fn proxy_request(
     }
 
     if path.contains("/configs") {
+        audit.reject("configs_access");
         let mut error_response = Response::builder().status(StatusCode::NOT_FOUND);
-        error_response = add_cors_headers_with_host_and_origin(
-            error_response,
-            &host_header,
-            &origin_header,
-            &config.trusted_hosts,
-        );
+        error_response = add_cors_headers(error_response, &origin_header, &config.cors);
         return Ok(error_response.body(Body::from("Not Found")).unwrap());
     }
 
     let target_port: Option<i32>;
+    let session_pid: Option<i32>;
     let session_api_key: Option<String>;
-    let buffered_body: Option<Bytes>;
+    let mut buffered_body: Option<Bytes>;
+    let request_model: Option<String>;
+    let request_model_path: Option<String>;
+    let request_json: Option<serde_json::Value>;
     let original_path = parts.uri.path();
     let destination_path = get_destination_path(original_path, &config.prefix);
 
@@ -313,12 +411,7 @@ async fn proxy_request(
                 Err(_) => {
                     let mut error_response =
                         Response::builder().status(StatusCode::INTERNAL_SERVER_ERROR);
-                    error_response = add_cors_headers_with_host_and_origin(
-                        error_response,
-                        &host_header,
-                        &origin_header,
-                        &config.trusted_hosts,
-                    );
+                    error_response = add_cors_headers(error_response, &origin_header, &config.cors);
                     return Ok(error_response
                         .body(Body::from("Failed to read request body"))
                         .unwrap());
@@ -327,10 +420,17 @@ async fn proxy_request(
             buffered_body = Some(body_bytes.clone());
 
             match serde_json::from_slice::<serde_json::Value>(&body_bytes) {
-                Ok(json_body) => {
-                    if let Some(model_id) = json_body.get("model").and_then(|v| v.as_str()) {
+                Ok(mut json_body) => {
+                    if let Some(model_id) = json_body
+                        .get("model")
+                        .and_then(|v| v.as_str())
+                        .map(String::from)
+                    {
+                        let model_id = model_id.as_str();
                         log::debug!("Extracted model_id: {}", model_id);
-                        let sessions_guard = sessions.lock().await;
+                        audit.model = Some(model_id.to_string());
+                        let mut sessions_guard = sessions.lock().await;
+                        config.session_health.reap_exited(&mut sessions_guard);
 
                         if sessions_guard.is_empty() {
                             log::warn!(
@@ -339,34 +439,68 @@ async fn proxy_request(
                             );
                             let mut error_response =
                                 Response::builder().status(StatusCode::SERVICE_UNAVAILABLE);
-                            error_response = add_cors_headers_with_host_and_origin(
-                                error_response,
-                                &host_header,
-                                &origin_header,
-                                &config.trusted_hosts,
-                            );
+                            error_response =
+                                add_cors_headers(error_response, &origin_header, &config.cors);
                             return Ok(error_response
                                 .body(Body::from("No models are available"))
                                 .unwrap());
                         }
 
-                        if let Some(session) = sessions_guard
-                            .values()
-                            .find(|s| s.info.model_id == model_id)
+                        let resolved_model_id = resolve_model_id(
+                            model_id,
+                            &config.model_aliases,
+                            sessions_guard.values().map(|s| s.info.model_id.as_str()),
+                        );
+
+                        if let Some(session) = resolved_model_id
+                            .as_deref()
+                            .and_then(|id| sessions_guard.values().find(|s| s.info.model_id == id))
                         {
+                            if destination_path == "/chat/completions"
+                                && session.info.mmproj_path.is_none()
+                                && has_image_content(&json_body)
+                            {
+                                let message = format!(
+                                    "Model '{}' does not accept image input. Load it with a multimodal projector (mmproj) to send images.",
+                                    session.info.model_id
+                                );
+                                log::warn!("{}", message);
+                                return Ok(invalid_request_response(
+                                    &config,
+                                    &origin_header,
+                                    message,
+                                    "image_input_not_supported",
+                                ));
+                            }
+
                             target_port = Some(session.info.port);
+                            session_pid = Some(session.info.pid);
                             session_api_key = Some(session.info.api_key.clone());
                             log::debug!("Found session for model_id {}", model_id,);
+
+                            if session.info.model_id != model_id {
+                                log::debug!(
+                                    "Routing alias '{}' to model '{}'",
+                                    model_id,
+                                    session.info.model_id
+                                );
+                                json_body["model"] =
+                                    serde_json::Value::String(session.info.model_id.clone());
+                                buffered_body = serde_json::to_vec(&json_body)
+                                    .ok()
+                                    .map(Bytes::from)
+                                    .or(buffered_body);
+                            }
+                            request_model = Some(session.info.model_id.clone());
+                            audit.model = request_model.clone();
+                            request_model_path = Some(session.info.model_path.clone());
+                            request_json = Some(json_body.clone());
                         } else {
                             log::warn!("No running session found for model_id: {}", model_id);
                             let mut error_response =
                                 Response::builder().status(StatusCode::NOT_FOUND);
-                            error_response = add_cors_headers_with_host_and_origin(
-                                error_response,
-                                &host_header,
-                                &origin_header,
-                                &config.trusted_hosts,
-                            );
+                            error_response =
+                                add_cors_headers(error_response, &origin_header, &config.cors);
                             return Ok(error_response
                                 .body(Body::from(format!(
                                     "No running session found for model '{}'",
@@ -381,12 +515,8 @@ async fn proxy_request(
                         );
                         let mut error_response =
                             Response::builder().status(StatusCode::BAD_REQUEST);
-                        error_response = add_cors_headers_with_host_and_origin(
-                            error_response,
-                            &host_header,
-                            &origin_header,
-                            &config.trusted_hosts,
-                        );
+                        error_response =
+                            add_cors_headers(error_response, &origin_header, &config.cors);
                         return Ok(error_response
                             .body(Body::from("Request body must contain a 'model' field"))
                             .unwrap());
@@ -399,23 +529,101 @@ async fn proxy_request(
                         e
                     );
                     let mut error_response = Response::builder().status(StatusCode::BAD_REQUEST);
-                    error_response = add_cors_headers_with_host_and_origin(
-                        error_response,
-                        &host_header,
-                        &origin_header,
-                        &config.trusted_hosts,
-                    );
+                    error_response = add_cors_headers(error_response, &origin_header, &config.cors);
                     return Ok(error_response
                         .body(Body::from("Invalid JSON body"))
                         .unwrap());
                 }
             }
         }
+        (hyper::Method::GET, "/metrics") => {
+            log::debug!("Handling GET /metrics request");
+            let running_sessions = sessions.lock().await.len();
+            let queued = match &config.request_queue {
+                Some(queue) => queue.waiting().await,
+                None => HashMap::new(),
+            };
+            let body_str = config.metrics.render(running_sessions, &queued);
+
+            let mut response_builder = Response::builder()
+                .status(StatusCode::OK)
+                .header(hyper::header::CONTENT_TYPE, "text/plain; version=0.0.4");
+
+            response_builder = add_cors_headers(response_builder, &origin_header, &config.cors);
+
+            return Ok(response_builder.body(Body::from(body_str)).unwrap());
+        }
+        (_, vector_path)
+            if vector_path == "/vector_stores" || vector_path.starts_with("/vector_stores/") =>
+        {
+            let Some(stores) = config.vector_stores.clone() else {
+                let mut error_response = Response::builder().status(StatusCode::NOT_FOUND);
+                error_response = add_cors_headers(error_response, &origin_header, &config.cors);
+                return Ok(error_response.body(Body::from("Not Found")).unwrap());
+            };
+            let body_bytes = match hyper::body::to_bytes(body).await {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    let mut error_response =
+                        Response::builder().status(StatusCode::INTERNAL_SERVER_ERROR);
+                    error_response = add_cors_headers(error_response, &origin_header, &config.cors);
+                    return Ok(error_response
+                        .body(Body::from("Failed to read request body"))
+                        .unwrap());
+                }
+            };
+
+            let embedder = SessionEmbedder {
+                client: client.clone(),
+                config: config.clone(),
+                sessions: sessions.clone(),
+            };
+            let (status, response_json) = stores
+                .handle_request(&method, &destination_path, &body_bytes, &embedder)
+                .await;
+
+            let mut response_builder = Response::builder()
+                .status(status)
+                .header(hyper::header::CONTENT_TYPE, "application/json");
+            response_builder = add_cors_headers(response_builder, &origin_header, &config.cors);
+            return Ok(response_builder
+                .body(Body::from(response_json.to_string()))
+                .unwrap());
+        }
+        (hyper::Method::GET, "/openapi.json") | (hyper::Method::GET, "/") => {
+            let description = ApiDescription {
+                version: env!("CARGO_PKG_VERSION"),
+                prefix: &config.prefix,
+                api_key_required: !config.proxy_api_key.is_empty(),
+                vector_stores: config.vector_stores.is_some(),
+            };
+            let document = if destination_path == "/" {
+                let mut models: Vec<String> = sessions
+                    .lock()
+                    .await
+                    .values()
+                    .map(|s| s.info.model_id.clone())
+                    .collect();
+                models.sort();
+                landing_document(&description, &models)
+            } else {
+                openapi_document(&description)
+            };
+
+            let mut response_builder = Response::builder()
+                .status(StatusCode::OK)
+                .header(hyper::header::CONTENT_TYPE, "application/json");
+            response_builder = add_cors_headers(response_builder, &origin_header, &config.cors);
+            return Ok(response_builder
+                .body(Body::from(document.to_string()))
+                .unwrap());
+        }
         (hyper::Method::GET, "/models") => {
             log::debug!("Handling GET /v1/models request");
-            let sessions_guard = sessions.lock().await;
+            let mut sessions_guard = sessions.lock().await;
+            config.session_health.reap_exited(&mut sessions_guard);
 
-            let models_data: Vec<_> = sessions_guard
+            let mut models_data: Vec<_> = sessions_guard
                 .values()
                 .map(|session| {
                     serde_json::json!({
@@ -427,6 +635,26 @@ async fn proxy_request(
                 })
                 .collect();
 
+            let mut aliases: Vec<_> = config.model_aliases.keys().collect();
+            aliases.sort();
+            for alias in aliases {
+                if let Some(target) = resolve_model_id(
+                    alias,
+                    &config.model_aliases,
+                    sessions_guard.values().map(|s| s.info.model_id.as_str()),
+                )
+                .filter(|target| target != alias)
+                {
+                    models_data.push(serde_json::json!({
+                        "id": alias,
+                        "object": "model",
+                        "created": 1,
+                        "owned_by": "user",
+                        "alias_for": target
+                    }));
+                }
+            }
+
             let response_json = serde_json::json!({
                 "object": "list",
                 "data": models_data
@@ -439,27 +667,17 @@ async fn proxy_request(
                 .status(StatusCode::OK)
                 .header(hyper::header::CONTENT_TYPE, "application/json");
 
-            response_builder = add_cors_headers_with_host_and_origin(
-                response_builder,
-                &host_header,
-                &origin_header,
-                &config.trusted_hosts,
-            );
+            response_builder = add_cors_headers(response_builder, &origin_header, &config.cors);
 
             return Ok(response_builder.body(Body::from(body_str)).unwrap());
         }
         _ => {
             let is_explicitly_whitelisted_get = method == hyper::Method::GET
-                && whitelisted_paths.contains(&destination_path.as_str());
+                && PROXY_WHITELISTED_PATHS.contains(&destination_path.as_str());
             if is_explicitly_whitelisted_get {
                 log::debug!("Handled whitelisted GET path: {}", destination_path);
                 let mut error_response = Response::builder().status(StatusCode::NOT_FOUND);
-                error_response = add_cors_headers_with_host_and_origin(
-                    error_response,
-                    &host_header,
-                    &origin_header,
-                    &config.trusted_hosts,
-                );
+                error_response = add_cors_headers(error_response, &origin_header, &config.cors);
                 return Ok(error_response.body(Body::from("Not Found")).unwrap());
             } else {
                 log::warn!(
@@ -468,12 +686,7 @@ async fn proxy_request(
                     destination_path
                 );
                 let mut error_response = Response::builder().status(StatusCode::NOT_FOUND);
-                error_response = add_cors_headers_with_host_and_origin(
-                    error_response,
-                    &host_header,
-                    &origin_header,
-                    &config.trusted_hosts,
-                );
+                error_response = add_cors_headers(error_response, &origin_header, &config.cors);
                 return Ok(error_response.body(Body::from("Not Found")).unwrap());
             }
         }
@@ -486,20 +699,241 @@ async fn proxy_request(
                 "Internal API server routing error: target is None after successful lookup"
             );
             let mut error_response = Response::builder().status(StatusCode::INTERNAL_SERVER_ERROR);
-            error_response = add_cors_headers_with_host_and_origin(
-                error_response,
-                &host_header,
-                &origin_header,
-                &config.trusted_hosts,
-            );
+            error_response = add_cors_headers(error_response, &origin_header, &config.cors);
             return Ok(error_response
                 .body(Body::from("Internal routing error"))
                 .unwrap());
         }
     };
 
+    let request_stream = request_json
+        .as_ref()
+        .and_then(|body| body.get("stream"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let log_entry = config.request_log.as_ref().map(|logger| RequestLogEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        key_id: key_id.clone(),
+        method: method.to_string(),
+        path: destination_path.clone(),
+        model: request_model.clone(),
+        stream: request_stream,
+        status: None,
+        latency_ms: 0,
+        duration_ms: 0,
+        prompt_tokens: None,
+        completion_tokens: None,
+        error: None,
+        request_body: if logger.include_bodies() {
+            request_json.as_ref().map(|body| logger.redact(body))
+        } else {
+            None
+        },
+        response_body: None,
+    });
+    let started_at = Instant::now();
+
+    // The request log keeps the original URLs, the model gets the images inline
+    let mut request_json = request_json;
+    if let (true, true, Some(body)) = (
+        destination_path == "/chat/completions",
+        config.multimodal.inline_images,
+        request_json.as_mut(),
+    ) {
+        match inline_images(body, &config.multimodal).await {
+            Ok(0) => {}
+            Ok(count) => {
+                log::debug!("Inlined {} image(s) as data URIs", count);
+                buffered_body = serde_json::to_vec(body)
+                    .ok()
+                    .map(Bytes::from)
+                    .or(buffered_body);
+            }
+            Err(e) => {
+                log::warn!("Rejecting request with unusable image: {}", e);
+                return Ok(invalid_request_response(
+                    &config,
+                    &origin_header,
+                    e,
+                    "invalid_image",
+                ));
+            }
+        }
+    }
+
+    let use_tool_loop = destination_path == "/chat/completions"
+        && config.tool_provider.is_some()
+        && request_json
+            .as_ref()
+            .is_some_and(|body| body.get("tools").is_none());
+
+    let structured_output = match &request_json {
+        Some(body)
+            if config.structured_output.enabled
+                && !use_tool_loop
+                && (destination_path == "/chat/completions"
+                    || destination_path == "/completions") =>
+        {
+            let parsed = StructuredOutput::from_request(body).and_then(|output| {
+                let Some(output) = output else {
+                    return Ok(None);
+                };
+                let mut upstream_body = body.clone();
+                output.apply_grammar(&mut upstream_body)?;
+                Ok(Some((output, upstream_body)))
+            });
+            match parsed {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    log::warn!("Rejecting structured output request: {}", e);
+                    let mut error_response = Response::builder().status(StatusCode::BAD_REQUEST);
+                    error_response = add_cors_headers(error_response, &origin_header, &config.cors);
+                    return Ok(error_response.body(Body::from(e)).unwrap());
+                }
+            }
+        }
+        _ => None,
+    };
+    if let Some((_, upstream_body)) = &structured_output {
+        buffered_body = serde_json::to_vec(upstream_body)
+            .ok()
+            .map(Bytes::from)
+            .or(buffered_body);
+    }
+
+    let cache_key = match (&config.response_cache, &request_json, &request_model_path) {
+        (Some(cache), Some(body), Some(model_path))
+            if !use_tool_loop && cache.is_cacheable(&destination_path, body) =>
+        {
+            match cache.key(&destination_path, body, model_path).await {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    log::warn!("Response cache unavailable for this request: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    if let (Some(cache), Some(key)) = (&config.response_cache, &cache_key) {
+        if let Some(cached) = cache.get(key).await {
+            log::debug!("Serving {} from the response cache", destination_path);
+            if let (Some(logger), Some(mut entry)) = (config.request_log.as_ref(), log_entry) {
+                entry.status = Some(StatusCode::OK.as_u16());
+                entry.latency_ms = started_at.elapsed().as_millis() as u64;
+                entry.duration_ms = entry.latency_ms;
+                if logger.include_bodies() {
+                    entry.response_body = Some(cached.body.clone());
+                }
+                logger.append(&entry).await;
+            }
+
+            let mut builder = Response::builder()
+                .status(StatusCode::OK)
+                .header(PROXY_CACHE_STATUS_HEADER, "HIT");
+            if let Some(content_type) = &cached.content_type {
+                builder = builder.header(hyper::header::CONTENT_TYPE, content_type);
+            }
+            builder = add_cors_headers(builder, &origin_header, &config.cors);
+            return Ok(builder.body(Body::from(cached.body)).unwrap());
+        }
+    }
+
+    if let (Some(batcher), Some(body), "/embeddings") = (
+        &config.embedding_batcher,
+        &request_json,
+        destination_path.as_str(),
+    ) {
+        // The batch takes the admission slot, so requests can wait together
+        let target = EmbeddingTarget {
+            client: client.clone(),
+            upstream_url: format!("http://127.0.0.1:{}{}", port, destination_path),
+            session_api_key: session_api_key.clone(),
+            admission: config.request_queue.clone().zip(request_model.clone()),
+        };
+        let request = SynthesizedRequest {
+            config: &config,
+            origin: &origin_header,
+            path: &destination_path,
+            model: request_model.as_deref().unwrap_or_default(),
+            log_entry,
+            cache_key,
+        };
+        return Ok(handle_batched_embeddings(request, batcher, target, body.clone()).await);
+    }
+
+    let admission = match (&config.request_queue, &request_model) {
+        (Some(queue), Some(model_id)) => match queue.acquire(model_id).await {
+            Ok(admission) => Some(admission),
+            Err(e) => {
+                let message = match e {
+                    AdmissionError::QueueFull => {
+                        format!("Too many queued requests for model '{}'", model_id)
+                    }
+                    AdmissionError::Timeout => {
+                        format!("Timed out waiting for a free slot on model '{}'", model_id)
+                    }
+                };
+                let mut error_response = Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .header(
+                        hyper::header::RETRY_AFTER,
+                        queue.retry_after_secs().to_string(),
+                    );
+                error_response = add_cors_headers(error_response, &origin_header, &config.cors);
+                return Ok(error_response.body(Body::from(message)).unwrap());
+            }
+        },
+        _ => None,
+    };
+
     let upstream_url = format!("http://127.0.0.1:{}{}", port, destination_path);
 
+    if let (true, Some(provider), Some(body)) =
+        (use_tool_loop, &config.tool_provider, &request_json)
+    {
+        let request = SynthesizedRequest {
+            config: &config,
+            origin: &origin_header,
+            path: &destination_path,
+            model: request_model.as_deref().unwrap_or_default(),
+            log_entry,
+            cache_key: None,
+        };
+        return Ok(handle_tool_loop(
+            request,
+            &client,
+            provider.as_ref(),
+            &upstream_url,
+            session_api_key.as_deref(),
+            body.clone(),
+            request_stream,
+        )
+        .await);
+    }
+
+    if let (Some((output, upstream_body)), false) = (&structured_output, request_stream) {
+        let request = SynthesizedRequest {
+            config: &config,
+            origin: &origin_header,
+            path: &destination_path,
+            model: request_model.as_deref().unwrap_or_default(),
+            log_entry,
+            cache_key,
+        };
+        return Ok(handle_structured_output(
+            request,
+            &client,
+            output,
+            &upstream_url,
+            session_api_key.as_deref(),
+            upstream_body,
+        )
+        .await);
+    }
+
     let mut outbound_req = client.request(method.clone(), &upstream_url);
 
     for (name, value) in headers.iter() {
@@ -521,23 +955,48 @@ async fn proxy_request(
     } else {
         log::error!("Internal logic error: Request reached proxy stage without a buffered body.");
         let mut error_response = Response::builder().status(StatusCode::INTERNAL_SERVER_ERROR);
-        error_response = add_cors_headers_with_host_and_origin(
-            error_response,
-            &host_header,
-            &origin_header,
-            &config.trusted_hosts,
-        );
+        error_response = add_cors_headers(error_response, &origin_header, &config.cors);
         return Ok(error_response
             .body(Body::from("Internal server error: unhandled request path"))
             .unwrap());
     };
 
-    match outbound_req_with_body.send().await {
+    let retry_req = if config.session_health_options.retry_idempotent {
+        outbound_req_with_body.try_clone()
+    } else {
+        None
+    };
+    let mut upstream_result = outbound_req_with_body.send().await;
+    if let (Err(e), Some(retry_req)) = (&upstream_result, retry_req) {
+        let session_alive = {
+            let mut sessions_guard = sessions.lock().await;
+            config.session_health.reap_exited(&mut sessions_guard);
+            session_pid.is_some_and(|pid| sessions_guard.contains_key(&pid))
+        };
+        if session_alive && is_retryable(&method, &destination_path, e) {
+            log::warn!(
+                "Retrying {} once after upstream failure: {}",
+                destination_path,
+                e
+            );
+            tokio::time::sleep(Duration::from_millis(PROXY_UPSTREAM_RETRY_DELAY_MS)).await;
+            upstream_result = retry_req.send().await;
+        }
+    }
+
+    match upstream_result {
         Ok(response) => {
             let status = response.status();
+            let latency_ms = started_at.elapsed().as_millis() as u64;
             log::debug!("Received response with status: {}", status);
 
             let mut builder = Response::builder().status(status);
+            if let Some(admission) = &admission {
+                builder = builder.header(
+                    PROXY_QUEUE_POSITION_HEADER,
+                    admission.queue_position.to_string(),
+                );
+            }
 
             for (name, value) in response.headers() {
                 if !is_cors_header(name.as_str()) && name != hyper::header::CONTENT_LENGTH {
@@ -545,84 +1004,608 @@ async fn proxy_request(
                 }
             }
 
-            builder = add_cors_headers_with_host_and_origin(
-                builder,
-                &host_header,
-                &origin_header,
-                &config.trusted_hosts,
-            );
+            if config.response_cache.is_some() {
+                let cache_status = if cache_key.is_some() {
+                    "MISS"
+                } else {
+                    "BYPASS"
+                };
+                builder = builder.header(PROXY_CACHE_STATUS_HEADER, cache_status);
+            }
 
+            builder = add_cors_headers(builder, &origin_header, &config.cors);
+
+            let content_type = response
+                .headers()
+                .get(hyper::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from);
+            let response_cache = config.response_cache.clone();
             let mut stream = response.bytes_stream();
             let (mut sender, body) = hyper::Body::channel();
+            let request_log = config.request_log.clone();
+            let metrics = config.metrics.clone();
+            let metrics_model = request_model.clone().unwrap_or_default();
+            let metrics_path = destination_path.clone();
 
             tokio::spawn(async move {
+                // Hold the session slot until the whole response has been streamed
+                let _admission = admission;
+                let _in_flight = metrics.stream_started();
+                let mut capture = ResponseCapture::default();
+                let mut stream_error: Option<String> = None;
                 while let Some(chunk_result) = stream.next().await {
                     match chunk_result {
                         Ok(chunk) => {
+                            capture.push(&chunk);
                             if sender.send_data(chunk).await.is_err() {
                                 log::debug!("Client disconnected during streaming");
+                                stream_error = Some("Client disconnected".to_string());
                                 break;
                             }
                         }
                         Err(e) => {
                             log::error!("Stream error: {}", e);
+                            stream_error = Some(format!("Stream error: {}", e));
                             break;
                         }
                     }
                 }
                 log::debug!("Streaming complete to client");
+
+                let (prompt_tokens, completion_tokens) = capture.usage(request_stream);
+                metrics.record_upstream(
+                    &metrics_path,
+                    &metrics_model,
+                    status.as_u16(),
+                    started_at.elapsed(),
+                    completion_tokens,
+                );
+
+                if let (Some(cache), Some(key)) = (response_cache, cache_key) {
+                    match capture.complete_body() {
+                        Some(body) if status.is_success() && stream_error.is_none() => {
+                            cache.put(&key, &metrics_model, content_type, body).await;
+                        }
+                        _ => log::debug!("Response not cached"),
+                    }
+                }
+
+                if let (Some(logger), Some(mut entry)) = (request_log, log_entry) {
+                    entry.status = Some(status.as_u16());
+                    entry.latency_ms = latency_ms;
+                    entry.duration_ms = started_at.elapsed().as_millis() as u64;
+                    entry.prompt_tokens = prompt_tokens;
+                    entry.completion_tokens = completion_tokens;
+                    entry.error = stream_error;
+                    if logger.include_bodies() {
+                        entry.response_body = Some(capture.body_text());
+                    }
+                    logger.append(&entry).await;
+                }
             });
 
             Ok(builder.body(body).unwrap())
         }
         Err(e) => {
-            let error_msg = format!("Proxy request to model failed: {}", e);
+            let exited = {
+                let mut sessions_guard = sessions.lock().await;
+                config.session_health.reap_exited(&mut sessions_guard);
+                session_pid.is_some_and(|pid| !sessions_guard.contains_key(&pid))
+            };
+            // A session that is gone will not come back, so tell the client to load it again
+            let (status, error_msg) = if exited {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!(
+                        "The session for model '{}' has exited and must be loaded again",
+                        request_model.as_deref().unwrap_or_default()
+                    ),
+                )
+            } else {
+                (
+                    StatusCode::BAD_GATEWAY,
+                    format!("Proxy request to model failed: {}", e),
+                )
+            };
             log::error!("{}", error_msg);
-            let mut error_response = Response::builder().status(StatusCode::BAD_GATEWAY);
-            error_response = add_cors_headers_with_host_and_origin(
-                error_response,
-                &host_header,
-                &origin_header,
-                &config.trusted_hosts,
-            );
+            config.metrics.record_upstream_failure(status.as_u16());
+            if let (Some(logger), Some(mut entry)) = (config.request_log.as_ref(), log_entry) {
+                entry.status = Some(status.as_u16());
+                entry.latency_ms = started_at.elapsed().as_millis() as u64;
+                entry.duration_ms = entry.latency_ms;
+                entry.error = Some(error_msg.clone());
+                logger.append(&entry).await;
+            }
+            let mut error_response = Response::builder().status(status);
+            error_response = add_cors_headers(error_response, &origin_header, &config.cors);
             Ok(error_response.body(Body::from(error_msg)).unwrap())
         }
     }
 }
 
-fn add_cors_headers_with_host_and_origin(
-    builder: hyper::http::response::Builder,
-    host: &str,
-    origin: &str,
-    trusted_hosts: &[Vec<String>],
-) -> hyper::http::response::Builder {
-    let mut builder = builder;
-    let allow_origin_header = if !origin.is_empty() && is_valid_host(host, trusted_hosts) {
-        origin.to_string()
-    } else if !origin.is_empty() {
-        origin.to_string()
-    } else {
-        "*".to_string()
+/// Request details for the handlers that build a response in the proxy instead
+/// of streaming it from llama-server
+struct SynthesizedRequest<'a> {
+    config: &'a ProxyConfig,
+    origin: &'a str,
+    path: &'a str,
+    model: &'a str,
+    log_entry: Option<RequestLogEntry>,
+    cache_key: Option<String>,
+}
+
+/// A response built by the proxy, before it is recorded and sent
+struct SynthesizedResponse {
+    status: StatusCode,
+    body: String,
+    content_type: &'static str,
+    error: Option<String>,
+    prompt_tokens: Option<u64>,
+    completion_tokens: Option<u64>,
+}
+
+/// Records metrics, caches successful responses, writes the request log entry and
+/// builds the response with the cache status and CORS headers
+async fn finish_synthesized_response(
+    request: SynthesizedRequest<'_>,
+    started_at: Instant,
+    response: SynthesizedResponse,
+) -> Response<Body> {
+    let config = request.config;
+    config.metrics.record_upstream(
+        request.path,
+        request.model,
+        response.status.as_u16(),
+        started_at.elapsed(),
+        response.completion_tokens,
+    );
+    if let (true, Some(cache), Some(key)) = (
+        response.status.is_success(),
+        &config.response_cache,
+        &request.cache_key,
+    ) {
+        cache
+            .put(
+                key,
+                request.model,
+                Some(response.content_type.to_string()),
+                response.body.clone(),
+            )
+            .await;
+    }
+    if let (Some(logger), Some(mut entry)) = (config.request_log.as_ref(), request.log_entry) {
+        entry.status = Some(response.status.as_u16());
+        entry.latency_ms = started_at.elapsed().as_millis() as u64;
+        entry.duration_ms = entry.latency_ms;
+        entry.prompt_tokens = response.prompt_tokens;
+        entry.completion_tokens = response.completion_tokens;
+        entry.error = response.error;
+        if logger.include_bodies() {
+            entry.response_body = Some(response.body.clone());
+        }
+        logger.append(&entry).await;
+    }
+
+    let mut builder = Response::builder()
+        .status(response.status)
+        .header(hyper::header::CONTENT_TYPE, response.content_type);
+    if config.response_cache.is_some() {
+        let cache_status = if request.cache_key.is_some() {
+            "MISS"
+        } else {
+            "BYPASS"
+        };
+        builder = builder.header(PROXY_CACHE_STATUS_HEADER, cache_status);
+    }
+    add_cors_headers(builder, request.origin, &config.cors)
+        .body(Body::from(response.body))
+        .unwrap()
+}
+
+/// Serves a chat completion through the tool loop, answering with JSON or a
+/// replayed SSE stream depending on what the client asked for
+async fn handle_tool_loop(
+    request: SynthesizedRequest<'_>,
+    client: &Client,
+    provider: &dyn ToolProvider,
+    upstream_url: &str,
+    session_api_key: Option<&str>,
+    body: serde_json::Value,
+    stream: bool,
+) -> Response<Body> {
+    let started_at = Instant::now();
+    let result = run_tool_loop(
+        client,
+        upstream_url,
+        session_api_key,
+        body,
+        provider,
+        request.config.tool_loop_max_iterations,
+    )
+    .await;
+
+    let response = match result {
+        Ok(outcome) => {
+            log::debug!(
+                "Tool loop finished after {} iteration(s) and {} tool call(s)",
+                outcome.iterations,
+                outcome.tool_calls
+            );
+            let (body, content_type) = if stream {
+                (completion_to_sse(&outcome.response), "text/event-stream")
+            } else {
+                (outcome.response.to_string(), "application/json")
+            };
+            SynthesizedResponse {
+                status: StatusCode::OK,
+                body,
+                content_type,
+                error: None,
+                prompt_tokens: Some(outcome.prompt_tokens),
+                completion_tokens: Some(outcome.completion_tokens),
+            }
+        }
+        Err(e) => {
+            log::error!("Proxy tool loop failed: {}", e);
+            SynthesizedResponse {
+                status: StatusCode::BAD_GATEWAY,
+                body: e.clone(),
+                content_type: "text/plain",
+                error: Some(e),
+                prompt_tokens: None,
+                completion_tokens: None,
+            }
+        }
     };
+    finish_synthesized_response(request, started_at, response).await
+}
 
-    builder = builder
-        .header("Access-Control-Allow-Origin", allow_origin_header.clone())
-        .header("Access-Control-Allow-Methods", "GET, POST, PUT, DELETE, OPTIONS, PATCH")
-        .header("Access-Control-Allow-Headers", "Authorization, Content-Type, Host, Accept, Accept-Language, Cache-Control, Connection, DNT, If-Modified-Since, Keep-Alive, Origin, User-Agent, X-Requested-With, X-CSRF-Token, X-Forwarded-For, X-Forwarded-Proto, X-Forwarded-Host, authorization, content-type, x-api-key")
-        .header("Vary", "Origin");
+/// Serves a non-streaming `json_schema` completion, retrying when the model's
+/// output does not validate and failing with the violations if it never does
+async fn handle_structured_output(
+    request: SynthesizedRequest<'_>,
+    client: &Client,
+    output: &StructuredOutput,
+    upstream_url: &str,
+    session_api_key: Option<&str>,
+    body: &serde_json::Value,
+) -> Response<Body> {
+    let started_at = Instant::now();
+    let result = run_structured_completion(
+        client,
+        upstream_url,
+        session_api_key,
+        body,
+        output,
+        request.config.structured_output.max_retries,
+    )
+    .await;
+
+    let response = match result {
+        Ok(outcome) if outcome.violations.is_empty() => {
+            log::debug!(
+                "Structured output validated after {} attempt(s)",
+                outcome.attempts
+            );
+            SynthesizedResponse {
+                status: StatusCode::OK,
+                body: outcome.response.to_string(),
+                content_type: "application/json",
+                error: None,
+                prompt_tokens: Some(outcome.prompt_tokens),
+                completion_tokens: Some(outcome.completion_tokens),
+            }
+        }
+        Ok(outcome) => {
+            let message = format!(
+                "Model output did not match the response_format schema after {} attempt(s)",
+                outcome.attempts
+            );
+            log::error!("{}: {:?}", message, outcome.violations);
+            let body = serde_json::json!({
+                "error": {
+                    "message": message,
+                    "type": "schema_validation_error",
+                    "violations": outcome.violations,
+                }
+            });
+            SynthesizedResponse {
+                status: StatusCode::BAD_GATEWAY,
+                body: body.to_string(),
+                content_type: "application/json",
+                error: Some(message),
+                prompt_tokens: Some(outcome.prompt_tokens),
+                completion_tokens: Some(outcome.completion_tokens),
+            }
+        }
+        Err(e) => {
+            log::error!("Structured output request failed: {}", e);
+            let body = serde_json::json!({ "error": { "message": e } });
+            SynthesizedResponse {
+                status: StatusCode::BAD_GATEWAY,
+                body: body.to_string(),
+                content_type: "application/json",
+                error: Some(e),
+                prompt_tokens: None,
+                completion_tokens: None,
+            }
+        }
+    };
+    finish_synthesized_response(request, started_at, response).await
+}
+
+/// Serves an `/embeddings` request through the batcher
+async fn handle_batched_embeddings(
+    request: SynthesizedRequest<'_>,
+    batcher: &Arc<EmbeddingBatcher>,
+    target: EmbeddingTarget,
+    body: serde_json::Value,
+) -> Response<Body> {
+    let started_at = Instant::now();
+    let response = match batcher.embed(target, body).await {
+        Ok(response) => SynthesizedResponse {
+            status: StatusCode::OK,
+            prompt_tokens: response["usage"]["prompt_tokens"].as_u64(),
+            body: response.to_string(),
+            content_type: "application/json",
+            error: None,
+            completion_tokens: None,
+        },
+        Err(e) => {
+            log::error!("Embeddings request failed: {}", e);
+            let body = serde_json::json!({ "error": { "message": e } });
+            SynthesizedResponse {
+                status: StatusCode::BAD_GATEWAY,
+                body: body.to_string(),
+                content_type: "application/json",
+                error: Some(e),
+                prompt_tokens: None,
+                completion_tokens: None,
+            }
+        }
+    };
+    finish_synthesized_response(request, started_at, response).await
+}
 
-    if allow_origin_header != "*" {
-        builder = builder.header("Access-Control-Allow-Credentials", "true");
+/// A running proxy server and what is needed to inspect, reconfigure and stop it
+pub struct RunningServer {
+    task: JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+    config: Arc<RwLock<ProxyConfig>>,
+    snapshot: ServerConfigSnapshot,
+    connections: ConnectionTracker,
+    address: String,
+    unix_socket_path: Option<PathBuf>,
+    data_dir: PathBuf,
+    started_at: Instant,
+    tool_provider: Option<Arc<dyn ToolProvider>>,
+    health_task: JoinHandle<()>,
+}
+
+/// Builds the per-request configuration, reusing the request log, admission
+/// queue and response cache of `previous` when their options did not change
+fn build_proxy_config(
+    snapshot: &ServerConfigSnapshot,
+    data_dir: &Path,
+    metrics: Arc<ProxyMetrics>,
+    session_health: Arc<SessionHealth>,
+    previous: Option<(&ProxyConfig, &ProxyServerOptions)>,
+) -> ProxyConfig {
+    let options = &snapshot.options;
+    let log_dir = data_dir.join("logs");
+    if options.cors.allow_credentials && options.cors.allows_any_origin() {
+        log::warn!(
+            "CORS: credentials are never allowed for origins matched only by '*'; list trusted origins explicitly"
+        );
     }
+    let request_log = match previous {
+        Some((config, old)) if old.request_log == options.request_log => config.request_log.clone(),
+        _ if options.request_log.enabled => {
+            log::info!(
+                "Proxy request logging enabled, writing to {}",
+                log_dir.display()
+            );
+            Some(Arc::new(RequestLogger::new(
+                log_dir.clone(),
+                options.request_log.clone(),
+            )))
+        }
+        _ => None,
+    };
+    let request_queue = match previous {
+        Some((config, old)) if old.request_queue == options.request_queue => {
+            config.request_queue.clone()
+        }
+        _ if options.request_queue.enabled => {
+            Some(Arc::new(AdmissionQueue::new(options.request_queue.clone())))
+        }
+        _ => None,
+    };
+    let response_cache = match previous {
+        Some((config, old)) if old.response_cache == options.response_cache => {
+            config.response_cache.clone()
+        }
+        _ if options.response_cache.enabled => Some(Arc::new(ResponseCache::new(
+            data_dir.join(PROXY_CACHE_DIR),
+            options.response_cache.clone(),
+        ))),
+        _ => None,
+    };
+    let embedding_batcher = match previous {
+        Some((config, old)) if old.embedding_batch == options.embedding_batch => {
+            config.embedding_batcher.clone()
+        }
+        _ if options.embedding_batch.enabled => Some(Arc::new(EmbeddingBatcher::new(
+            options.embedding_batch.clone(),
+        ))),
+        _ => None,
+    };
+    let vector_stores = match previous {
+        Some((config, old)) if old.vector_stores == options.vector_stores => {
+            config.vector_stores.clone()
+        }
+        _ if options.vector_stores.enabled => Some(Arc::new(VectorStoreManager::new(
+            data_dir.join(PROXY_VECTOR_STORE_DIR),
+        ))),
+        _ => None,
+    };
+    let audit_log = match previous {
+        Some((config, old)) if old.audit_log == options.audit_log => config.audit_log.clone(),
+        _ if options.audit_log.enabled => {
+            Some(Arc::new(AuditLogger::new(&log_dir, &options.audit_log)))
+        }
+        _ => None,
+    };
 
-    builder
+    ProxyConfig {
+        prefix: snapshot.prefix.clone(),
+        proxy_api_key: String::new(),
+        trusted_hosts: vec![snapshot.trusted_hosts.clone()],
+        request_log,
+        metrics,
+        request_queue,
+        model_aliases: Arc::new(options.model_aliases.clone()),
+        cors: Arc::new(options.cors.clone()),
+        tool_provider: None,
+        tool_loop_max_iterations: options.mcp_tools.max_iterations,
+        response_cache,
+        structured_output: options.structured_output.clone(),
+        embedding_batcher,
+        vector_stores,
+        audit_log,
+        session_health,
+        session_health_options: options.session_health.clone(),
+        multimodal: options.multimodal.clone(),
+    }
+}
+
+pub async fn get_server_status(server_handle: Arc<Mutex<Option<ServerHandle>>>) -> ServerStatus {
+    let handle_guard = server_handle.lock().await;
+    match handle_guard.as_ref() {
+        Some(server) => {
+            let in_flight_streams = server.config.read().unwrap().metrics.in_flight_streams();
+            ServerStatus {
+                running: true,
+                address: Some(server.address.clone()),
+                unix_socket_path: server
+                    .unix_socket_path
+                    .as_ref()
+                    .map(|p| p.to_string_lossy().to_string()),
+                uptime_secs: Some(server.started_at.elapsed().as_secs()),
+                active_connections: server.connections.active(),
+                in_flight_streams,
+                config: Some(server.snapshot.clone()),
+            }
+        }
+        None => ServerStatus::default(),
+    }
 }
 
-pub async fn is_server_running(server_handle: Arc<Mutex<Option<ServerHandle>>>) -> bool {
+/// Session health changes of the running server, `None` when it is not running
+pub async fn subscribe_session_health(
+    server_handle: Arc<Mutex<Option<ServerHandle>>>,
+) -> Option<tokio::sync::broadcast::Receiver<SessionHealthEvent>> {
     let handle_guard = server_handle.lock().await;
-    handle_guard.is_some()
+    handle_guard
+        .as_ref()
+        .map(|server| server.config.read().unwrap().session_health.subscribe())
 }
 
+/// Applies new settings to the running server. Requests already in progress
+/// finish with the settings they started with.
+pub async fn update_server_config(
+    server_handle: Arc<Mutex<Option<ServerHandle>>>,
+    update: ServerConfigUpdate,
+) -> Result<ServerConfigSnapshot, String> {
+    let mut handle_guard = server_handle.lock().await;
+    let server = handle_guard
+        .as_mut()
+        .ok_or_else(|| "Server is not running".to_string())?;
+
+    let mut snapshot = server.snapshot.clone();
+    if let Some(options) = update.options {
+        if options.tls != snapshot.options.tls
+            || options.unix_socket_path != snapshot.options.unix_socket_path
+        {
+            return Err(
+                "TLS and Unix socket settings can only be changed by restarting the server"
+                    .to_string(),
+            );
+        }
+        snapshot.options = options;
+    }
+    if let Some(prefix) = update.prefix {
+        snapshot.prefix = prefix;
+    }
+    if let Some(trusted_hosts) = update.trusted_hosts {
+        snapshot.trusted_hosts = trusted_hosts;
+    }
+
+    let mut config = server.config.write().unwrap();
+    let mut new_config = build_proxy_config(
+        &snapshot,
+        &server.data_dir,
+        config.metrics.clone(),
+        config.session_health.clone(),
+        Some((&config, &server.snapshot.options)),
+    );
+    new_config.proxy_api_key = update
+        .api_key
+        .unwrap_or_else(|| config.proxy_api_key.clone());
+    snapshot.api_key_required = !new_config.proxy_api_key.is_empty();
+    if snapshot.options.mcp_tools.enabled {
+        new_config.tool_provider = server.tool_provider.clone();
+    }
+
+    *config = new_config;
+    drop(config);
+    server.snapshot = snapshot.clone();
+
+    log::info!("Jan API server configuration updated");
+    Ok(snapshot)
+}
+
+/// Stops accepting connections, then waits up to `drain_timeout` for in-flight
+/// responses to finish before closing the remaining connections
+pub async fn stop_server(
+    server_handle: Arc<Mutex<Option<ServerHandle>>>,
+    drain_timeout: Duration,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let server = server_handle.lock().await.take();
+
+    let Some(server) = server else {
+        log::debug!("Server was not running");
+        return Ok(());
+    };
+
+    server.task.abort();
+    let _ = server.task.await;
+    server.health_task.abort();
+
+    let active = server.connections.active();
+    if active > 0 {
+        log::info!(
+            "Waiting up to {}s for {} open connection(s) to finish",
+            drain_timeout.as_secs(),
+            active
+        );
+    }
+    if !server.connections.drain(drain_timeout).await {
+        log::warn!(
+            "Closed {} connection(s) that were still open after the drain timeout",
+            server.connections.active()
+        );
+    }
+
+    if let Some(path) = &server.unix_socket_path {
+        let _ = std::fs::remove_file(path);
+    }
+
+    log::info!("Jan API server stopped");
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn start_server(
     server_handle: Arc<Mutex<Option<ServerHandle>>>,
     sessions: Arc<Mutex<HashMap<i32, LLamaBackendSession>>>,
@@ -630,7 +1613,10 @@ pub async fn start_server(
     port: u16,
     prefix: String,
     proxy_api_key: String,
-    trusted_hosts: Vec<Vec<String>>,
+    trusted_hosts: Vec<String>,
+    options: ProxyServerOptions,
+    data_dir: PathBuf,
+    tool_provider: Option<Arc<dyn ToolProvider>>,
 ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
     let mut handle_guard = server_handle.lock().await;
     if handle_guard.is_some() {
@@ -641,11 +1627,27 @@ pub async fn start_server(
         .parse()
         .map_err(|e| format!("Invalid address: {}", e))?;
 
-    let config = ProxyConfig {
+    let snapshot = ServerConfigSnapshot {
+        host: host.clone(),
+        port,
         prefix,
-        proxy_api_key,
+        api_key_required: !proxy_api_key.is_empty(),
         trusted_hosts,
+        options,
     };
+    let session_health = Arc::new(SessionHealth::new());
+    let mut config = build_proxy_config(
+        &snapshot,
+        &data_dir,
+        Arc::new(ProxyMetrics::new()),
+        session_health.clone(),
+        None,
+    );
+    config.proxy_api_key = proxy_api_key;
+    if snapshot.options.mcp_tools.enabled {
+        log::info!("Proxy MCP tool mode enabled for chat completions");
+        config.tool_provider = tool_provider.clone();
+    }
 
     let client = Client::builder()
         .timeout(std::time::Duration::from_secs(300))
@@ -653,45 +1655,107 @@ pub async fn start_server(
         .pool_idle_timeout(std::time::Duration::from_secs(30))
         .build()?;
 
-    let make_svc = make_service_fn(move |_conn| {
-        let client = client.clone();
-        let config = config.clone();
-        let sessions = sessions.clone();
+    let options = &snapshot.options;
+    let tls = if options.tls.enabled {
+        let mut hostnames = vec![host.clone()];
+        hostnames.extend(snapshot.trusted_hosts.iter().cloned());
+        Some(load_tls_acceptor(&options.tls, &data_dir, &hostnames)?)
+    } else {
+        None
+    };
 
-        async move {
-            Ok::<_, Infallible>(service_fn(move |req| {
-                proxy_request(req, client.clone(), config.clone(), sessions.clone())
-            }))
+    let tcp_listener = TcpListener::bind(addr).await?;
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let address = format!("{}://{}", scheme, tcp_listener.local_addr()?);
+    log::info!("Jan API server started on {}", address);
+
+    #[cfg(unix)]
+    let unix_listener = match &options.unix_socket_path {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            let listener = bind_unix_socket(&path)?;
+            log::info!("Jan API server listening on unix socket {}", path.display());
+            Some((listener, path))
         }
+        None => None,
+    };
+    #[cfg(unix)]
+    let unix_socket_path = unix_listener.as_ref().map(|(_, path)| path.clone());
+    #[cfg(not(unix))]
+    let unix_socket_path = None;
+    #[cfg(not(unix))]
+    if options.unix_socket_path.is_some() {
+        log::warn!("Unix socket listener is not supported on this platform, ignoring");
+    }
+
+    let shared_config = Arc::new(RwLock::new(config));
+    let connections = ConnectionTracker::new();
+
+    let health_task = spawn_health_checker(session_health, client.clone(), sessions.clone(), {
+        let shared_config = shared_config.clone();
+        move || shared_config.read().unwrap().session_health_options.clone()
     });
 
-    let server = Server::bind(&addr).serve(make_svc);
-    log::info!("Jan API server started on http://{}", addr);
+    let make_service = {
+        let shared_config = shared_config.clone();
+        move |remote_addr: String| {
+            let client = client.clone();
+            let shared_config = shared_config.clone();
+            let sessions = sessions.clone();
+            service_fn(move |req| {
+                // Snapshot per request so live config updates never affect a request midway
+                let config = shared_config.read().unwrap().clone();
+                handle_request(
+                    req,
+                    client.clone(),
+                    config,
+                    sessions.clone(),
+                    remote_addr.clone(),
+                )
+            })
+        }
+    };
 
+    let server_connections = connections.clone();
     let server_task = tokio::spawn(async move {
-        if let Err(e) = server.await {
+        let tcp = serve_tcp(tcp_listener, tls, server_connections.clone(), {
+            let make_service = make_service.clone();
+            move |remote_addr: SocketAddr| make_service(remote_addr.to_string())
+        });
+
+        #[cfg(unix)]
+        let result = match unix_listener {
+            Some((unix_listener, path)) => {
+                let remote_addr = format!("unix:{}", path.display());
+                tokio::select! {
+                    result = tcp => result,
+                    result = serve_unix(unix_listener, server_connections, move || {
+                        make_service(remote_addr.clone())
+                    }) => result,
+                }
+            }
+            None => tcp.await,
+        };
+        #[cfg(not(unix))]
+        let result = tcp.await;
+
+        if let Err(e) = &result {
             log::error!("Server error: {}", e);
-            return Err(Box::new(e) as Box<dyn std::error::Error + Send + Sync>);
         }
-        Ok(())
+        result
     });
 
-    *handle_guard = Some(server_task);
+    *handle_guard = Some(RunningServer {
+        task: server_task,
+        config: shared_config,
+        snapshot,
+        connections,
+        address,
+        unix_socket_path,
+        data_dir,
+        started_at: Instant::now(),
+        tool_provider,
+        health_task,
+    });
     Ok(true)
 }
-
-pub async fn stop_server(
-    server_handle: Arc<Mutex<Option<ServerHandle>>>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut handle_guard = server_handle.lock().await;
-
-    if let Some(handle) = handle_guard.take() {
-        handle.abort();
-        *handle_guard = None;
-        log::info!("Jan API server stopped");
-    } else {
-        log::debug!("Server was not running");
-    }
-
-    Ok(())
-}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio::time::timeout;
+
+use super::models::RequestQueueOptions;
+
+/// Why a request could not be admitted to a model session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdmissionError {
+    QueueFull,
+    Timeout,
+}
+
+/// A slot on a model session, released when dropped
+pub struct Admission {
+    _permit: OwnedSemaphorePermit,
+    /// Number of requests that were ahead of this one when it arrived (0 = admitted immediately)
+    pub queue_position: usize,
+}
+
+struct SessionQueue {
+    slots: Arc<Semaphore>,
+    waiting: AtomicUsize,
+}
+
+/// Limits how many requests are forwarded to each model session at once.
+/// Requests above the limit wait in FIFO order until a slot frees up, the queue
+/// is full, or the queue timeout expires.
+pub struct AdmissionQueue {
+    options: RequestQueueOptions,
+    sessions: Mutex<HashMap<String, Arc<SessionQueue>>>,
+}
+
+impl AdmissionQueue {
+    pub fn new(options: RequestQueueOptions) -> Self {
+        Self {
+            options,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn retry_after_secs(&self) -> u64 {
+        self.options.retry_after_secs
+    }
+
+    async fn session_queue(&self, model_id: &str) -> Arc<SessionQueue> {
+        let mut sessions = self.sessions.lock().await;
+        sessions
+            .entry(model_id.to_string())
+            .or_insert_with(|| {
+                Arc::new(SessionQueue {
+                    slots: Arc::new(Semaphore::new(self.options.max_concurrent.max(1))),
+                    waiting: AtomicUsize::new(0),
+                })
+            })
+            .clone()
+    }
+
+    /// Waits for a free slot on the session serving `model_id`
+    pub async fn acquire(&self, model_id: &str) -> Result<Admission, AdmissionError> {
+        let queue = self.session_queue(model_id).await;
+
+        if let Ok(permit) = queue.slots.clone().try_acquire_owned() {
+            return Ok(Admission {
+                _permit: permit,
+                queue_position: 0,
+            });
+        }
+
+        let queue_position = queue.waiting.fetch_add(1, Ordering::SeqCst) + 1;
+        if queue_position > self.options.max_queue_depth {
+            queue.waiting.fetch_sub(1, Ordering::SeqCst);
+            log::warn!(
+                "Admission queue for model '{}' is full ({} waiting)",
+                model_id,
+                self.options.max_queue_depth
+            );
+            return Err(AdmissionError::QueueFull);
+        }
+
+        log::debug!(
+            "Request for model '{}' queued at position {}",
+            model_id,
+            queue_position
+        );
+        let result = timeout(
+            Duration::from_secs(self.options.queue_timeout_secs),
+            queue.slots.clone().acquire_owned(),
+        )
+        .await;
+        queue.waiting.fetch_sub(1, Ordering::SeqCst);
+
+        match result {
+            Ok(Ok(permit)) => Ok(Admission {
+                _permit: permit,
+                queue_position,
+            }),
+            Ok(Err(_)) | Err(_) => {
+                log::warn!(
+                    "Request for model '{}' timed out after {}s in the admission queue",
+                    model_id,
+                    self.options.queue_timeout_secs
+                );
+                Err(AdmissionError::Timeout)
+            }
+        }
+    }
+
+    /// Number of requests currently waiting for a slot, per model
+    pub async fn waiting(&self) -> HashMap<String, usize> {
+        let sessions = self.sessions.lock().await;
+        sessions
+            .iter()
+            .map(|(model_id, queue)| (model_id.clone(), queue.waiting.load(Ordering::SeqCst)))
+            .collect()
+    }
+}
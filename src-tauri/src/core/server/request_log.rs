@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+
+use jan_utils::{redact_json, RotatingJsonl};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use super::constants::{
+    PROXY_CAPTURED_BODY_MAX_BYTES, PROXY_CAPTURED_TAIL_BYTES, PROXY_REQUEST_LOG_FILE,
+    PROXY_REQUEST_LOG_MAX_FILE_BYTES,
+};
+use super::models::{RequestLogEntry, RequestLogOptions};
+
+/// Appends proxied requests to a size-rotated JSONL file
+pub struct RequestLogger {
+    files: RotatingJsonl,
+    options: RequestLogOptions,
+    write_lock: Mutex<()>,
+}
+
+impl RequestLogger {
+    pub fn new(dir: PathBuf, options: RequestLogOptions) -> Self {
+        Self {
+            files: RotatingJsonl::new(
+                dir,
+                PROXY_REQUEST_LOG_FILE,
+                options.max_file_bytes,
+                options.max_files,
+            ),
+            options,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn include_bodies(&self) -> bool {
+        self.options.include_bodies
+    }
+
+    /// Returns a copy of the request body with sensitive fields replaced
+    pub fn redact(&self, body: &Value) -> Value {
+        redact_json(body, &self.options.redact_fields)
+    }
+
+    pub async fn append(&self, entry: &RequestLogEntry) {
+        let mut entry = entry.clone();
+        entry.response_body = entry
+            .response_body
+            .map(|body| redact_text(&body, &self.options.redact_fields));
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                log::warn!("Failed to serialize proxy request log entry: {}", e);
+                return;
+            }
+        };
+
+        let _guard = self.write_lock.lock().await;
+        if let Err(e) = self.files.append_line_blocking(line).await {
+            log::warn!("Failed to write proxy request log entry: {}", e);
+        }
+    }
+}
+
+fn request_log_files(dir: &Path, max_files: usize) -> RotatingJsonl {
+    RotatingJsonl::new(
+        dir,
+        PROXY_REQUEST_LOG_FILE,
+        PROXY_REQUEST_LOG_MAX_FILE_BYTES,
+        max_files,
+    )
+}
+
+/// Path of the n-th rotated log file (0 is the active file)
+pub fn request_log_path(dir: &Path, index: usize) -> PathBuf {
+    request_log_files(dir, index + 1).path(index)
+}
+
+/// Looks up a logged request by id across the rotated files
+pub fn find_request_log_entry(
+    dir: &Path,
+    max_files: usize,
+    request_id: &str,
+) -> Result<Option<RequestLogEntry>, String> {
+    request_log_files(dir, max_files).find_latest(|entry: &RequestLogEntry| entry.id == request_id)
+}
+
+/// Redacts a response body that is either a JSON document or an SSE stream of JSON events.
+/// Lines that are not JSON (such as a truncation marker) are kept as they are.
+pub fn redact_text(text: &str, fields: &[String]) -> String {
+    if let Ok(value) = serde_json::from_str::<Value>(text) {
+        return redact_json(&value, fields).to_string();
+    }
+    text.split('\n')
+        .map(|line| {
+            line.strip_prefix("data:")
+                .and_then(|data| serde_json::from_str::<Value>(data.trim()).ok())
+                .map(|value| format!("data: {}", redact_json(&value, fields)))
+                .unwrap_or_else(|| line.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Collects a bounded copy of a streamed response body
+#[derive(Default)]
+pub struct ResponseCapture {
+    body: Vec<u8>,
+    tail: Vec<u8>,
+    truncated: bool,
+}
+
+impl ResponseCapture {
+    pub fn push(&mut self, chunk: &[u8]) {
+        if self.body.len() + chunk.len() <= PROXY_CAPTURED_BODY_MAX_BYTES {
+            self.body.extend_from_slice(chunk);
+        } else {
+            self.truncated = true;
+        }
+
+        self.tail.extend_from_slice(chunk);
+        if self.tail.len() > PROXY_CAPTURED_TAIL_BYTES {
+            let excess = self.tail.len() - PROXY_CAPTURED_TAIL_BYTES;
+            self.tail.drain(..excess);
+        }
+    }
+
+    pub fn body_text(&self) -> String {
+        let mut text = String::from_utf8_lossy(&self.body).into_owned();
+        if self.truncated {
+            text.push_str("\n[truncated]");
+        }
+        text
+    }
+
+    /// The full response body, unless it exceeded the capture limit
+    pub fn complete_body(&self) -> Option<String> {
+        if self.truncated {
+            None
+        } else {
+            Some(String::from_utf8_lossy(&self.body).into_owned())
+        }
+    }
+
+    /// Token counts from the `usage` object (or llama-server `timings`)
+    pub fn usage(&self, stream: bool) -> (Option<u64>, Option<u64>) {
+        if stream {
+            parse_stream_usage(&String::from_utf8_lossy(&self.tail))
+        } else if !self.truncated {
+            serde_json::from_slice::<Value>(&self.body)
+                .map(|v| usage_from_json(&v))
+                .unwrap_or((None, None))
+        } else {
+            (None, None)
+        }
+    }
+}
+
+/// Reads token counts from the last SSE `data:` event that carries them
+pub fn parse_stream_usage(text: &str) -> (Option<u64>, Option<u64>) {
+    text.lines()
+        .rev()
+        .filter_map(|line| line.trim().strip_prefix("data:"))
+        .map(str::trim)
+        .filter(|data| *data != "[DONE]")
+        .filter_map(|data| serde_json::from_str::<Value>(data).ok())
+        .map(|v| usage_from_json(&v))
+        .find(|(prompt, completion)| prompt.is_some() || completion.is_some())
+        .unwrap_or((None, None))
+}
+
+fn usage_from_json(value: &Value) -> (Option<u64>, Option<u64>) {
+    if let Some(usage) = value.get("usage").filter(|u| u.is_object()) {
+        return (
+            usage.get("prompt_tokens").and_then(Value::as_u64),
+            usage.get("completion_tokens").and_then(Value::as_u64),
+        );
+    }
+    if let Some(timings) = value.get("timings").filter(|t| t.is_object()) {
+        return (
+            timings.get("prompt_n").and_then(Value::as_u64),
+            timings.get("predicted_n").and_then(Value::as_u64),
+        );
+    }
+    (None, None)
+}
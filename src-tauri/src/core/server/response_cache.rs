@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use super::constants::PROXY_CACHE_FINGERPRINT_SAMPLE_BYTES;
+use super::models::ResponseCacheOptions;
+
+/// Paths whose responses may be cached
+const CACHEABLE_PATHS: &[&str] = &["/chat/completions", "/completions", "/embeddings"];
+
+/// Request fields that do not change the generated output
+const IGNORED_FIELDS: &[&str] = &["model", "user", "stream_options"];
+
+/// A cached upstream response, stored as one JSON file per key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    /// Unix timestamp in seconds
+    pub created_at: u64,
+    pub model: String,
+    pub content_type: Option<String>,
+    /// The JSON body, or the full SSE text for streamed responses
+    pub body: String,
+}
+
+/// On-disk cache of model responses for repeated deterministic requests
+pub struct ResponseCache {
+    dir: PathBuf,
+    options: ResponseCacheOptions,
+    /// (path, size, modified) -> fingerprint, so model files are only read once
+    fingerprints: Mutex<HashMap<(PathBuf, u64, u64), String>>,
+    write_lock: Mutex<()>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Rebuilds a JSON value with object keys sorted and ignored fields removed,
+/// so equivalent requests serialize identically
+pub fn normalize_request(value: &Value) -> Value {
+    fn sorted(value: &Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let mut keys: Vec<_> = map.keys().collect();
+                keys.sort();
+                Value::Object(
+                    keys.into_iter()
+                        .map(|k| (k.clone(), sorted(&map[k])))
+                        .collect(),
+                )
+            }
+            Value::Array(items) => Value::Array(items.iter().map(sorted).collect()),
+            other => other.clone(),
+        }
+    }
+
+    let mut normalized = sorted(value);
+    if let Some(map) = normalized.as_object_mut() {
+        for field in IGNORED_FIELDS {
+            map.remove(*field);
+        }
+    }
+    normalized
+}
+
+/// Hashes the file size plus its first and last bytes. Hashing a whole
+/// multi-gigabyte GGUF would stall the first request for too long.
+fn fingerprint_model_file(path: &Path, size: u64) -> Result<String, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let sample = PROXY_CACHE_FINGERPRINT_SAMPLE_BYTES;
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_le_bytes());
+
+    let mut buffer = vec![0u8; sample.min(size) as usize];
+    file.read_exact(&mut buffer).map_err(|e| e.to_string())?;
+    hasher.update(&buffer);
+
+    if size > sample {
+        file.seek(SeekFrom::End(-(sample.min(size - sample) as i64)))
+            .map_err(|e| e.to_string())?;
+        buffer.clear();
+        file.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
+        hasher.update(&buffer);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, options: ResponseCacheOptions) -> Self {
+        Self {
+            dir,
+            options,
+            fingerprints: Mutex::new(HashMap::new()),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Whether a request may be served from or stored in the cache
+    pub fn is_cacheable(&self, path: &str, body: &Value) -> bool {
+        if !CACHEABLE_PATHS.contains(&path) {
+            return false;
+        }
+        if path == "/embeddings" || !self.options.deterministic_only {
+            return true;
+        }
+        body.get("temperature").and_then(|t| t.as_f64()) == Some(0.0)
+    }
+
+    async fn model_fingerprint(&self, model_path: &str) -> Result<String, String> {
+        let path = PathBuf::from(model_path);
+        let metadata = tokio::fs::metadata(&path)
+            .await
+            .map_err(|e| format!("Failed to read model file {}: {}", model_path, e))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let id = (path.clone(), metadata.len(), modified);
+
+        if let Some(fingerprint) = self.fingerprints.lock().await.get(&id) {
+            return Ok(fingerprint.clone());
+        }
+
+        let size = metadata.len();
+        let fingerprint = tokio::task::spawn_blocking(move || fingerprint_model_file(&path, size))
+            .await
+            .map_err(|e| e.to_string())??;
+        self.fingerprints
+            .lock()
+            .await
+            .insert(id, fingerprint.clone());
+        Ok(fingerprint)
+    }
+
+    /// Cache key for a request body sent to the model stored at `model_path`
+    pub async fn key(&self, path: &str, body: &Value, model_path: &str) -> Result<String, String> {
+        let fingerprint = self.model_fingerprint(model_path).await?;
+        let mut hasher = Sha256::new();
+        hasher.update(fingerprint.as_bytes());
+        hasher.update(path.as_bytes());
+        hasher.update(normalize_request(body).to_string().as_bytes());
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Returns the cached response for `key` unless it is missing or expired
+    pub async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let path = self.entry_path(key);
+        let content = tokio::fs::read_to_string(&path).await.ok()?;
+        let entry: CachedResponse = serde_json::from_str(&content).ok()?;
+
+        if now_secs().saturating_sub(entry.created_at) > self.options.ttl_secs {
+            let _ = tokio::fs::remove_file(&path).await;
+            return None;
+        }
+        Some(entry)
+    }
+
+    /// Stores a response, then evicts expired and oldest entries to stay within the size limit
+    pub async fn put(&self, key: &str, model: &str, content_type: Option<String>, body: String) {
+        if body.len() as u64 > self.options.max_entry_bytes {
+            log::debug!("Response too large to cache ({} bytes)", body.len());
+            return;
+        }
+
+        let entry = CachedResponse {
+            created_at: now_secs(),
+            model: model.to_string(),
+            content_type,
+            body,
+        };
+        let content = match serde_json::to_string(&entry) {
+            Ok(content) => content,
+            Err(e) => {
+                log::warn!("Failed to serialize cache entry: {}", e);
+                return;
+            }
+        };
+
+        let _guard = self.write_lock.lock().await;
+        if let Err(e) = tokio::fs::create_dir_all(&self.dir).await {
+            log::warn!("Failed to create response cache directory: {}", e);
+            return;
+        }
+        if let Err(e) = tokio::fs::write(self.entry_path(key), content).await {
+            log::warn!("Failed to write response cache entry: {}", e);
+            return;
+        }
+        if let Err(e) = self.evict().await {
+            log::warn!("Failed to evict response cache entries: {}", e);
+        }
+    }
+
+    async fn evict(&self) -> Result<(), String> {
+        let mut entries = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.dir)
+            .await
+            .map_err(|e| e.to_string())?;
+        while let Some(entry) = dir.next_entry().await.map_err(|e| e.to_string())? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let metadata = entry.metadata().await.map_err(|e| e.to_string())?;
+            let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+            entries.push((modified, metadata.len(), path));
+        }
+
+        let now = SystemTime::now();
+        let ttl = std::time::Duration::from_secs(self.options.ttl_secs);
+        entries.sort();
+
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        for (modified, len, path) in entries {
+            let expired = now.duration_since(modified).unwrap_or_default() > ttl;
+            if !expired && total <= self.options.max_total_bytes {
+                continue;
+            }
+            if tokio::fs::remove_file(&path).await.is_ok() {
+                total = total.saturating_sub(len);
+            }
+        }
+        Ok(())
+    }
+}
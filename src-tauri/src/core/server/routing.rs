@@ -0,0 +1,26 @@
+use std::collections::HashMap;
+
+use super::models::ModelRoute;
+
+/// Resolves a client-facing model name to the `model_id` of a running session.
+///
+/// A name that matches a running session directly always wins. Otherwise the alias
+/// table is consulted and the first running target (primary, then fallbacks in order)
+/// is returned.
+pub fn resolve_model_id<'a>(
+    requested: &str,
+    aliases: &HashMap<String, ModelRoute>,
+    running_model_ids: impl IntoIterator<Item = &'a str> + Clone,
+) -> Option<String> {
+    let is_running = |id: &str| running_model_ids.clone().into_iter().any(|r| r == id);
+
+    if is_running(requested) {
+        return Some(requested.to_string());
+    }
+
+    let route = aliases.get(requested)?;
+    std::iter::once(&route.model_id)
+        .chain(route.fallbacks.iter())
+        .find(|candidate| is_running(candidate))
+        .cloned()
+}
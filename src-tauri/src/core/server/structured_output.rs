@@ -0,0 +1,141 @@
+use jan_utils::{validate_json_schema, SchemaViolation};
+use reqwest::Client;
+use serde_json::Value;
+
+use super::grammar::json_schema_to_gbnf;
+
+/// The JSON Schema a client asked the response to follow
+#[derive(Debug, Clone)]
+pub struct StructuredOutput {
+    pub name: Option<String>,
+    pub schema: Value,
+}
+
+impl StructuredOutput {
+    /// Reads `response_format: {type: "json_schema", json_schema: {schema}}` from a request.
+    /// Returns `None` for any other (or missing) response format.
+    pub fn from_request(body: &Value) -> Result<Option<Self>, String> {
+        let Some(format) = body.get("response_format") else {
+            return Ok(None);
+        };
+        if format.get("type").and_then(|t| t.as_str()) != Some("json_schema") {
+            return Ok(None);
+        }
+        let json_schema = &format["json_schema"];
+        let schema = json_schema
+            .get("schema")
+            .filter(|s| s.is_object() || s.is_boolean())
+            .ok_or("response_format.json_schema.schema must be a JSON Schema object")?;
+        Ok(Some(Self {
+            name: json_schema
+                .get("name")
+                .and_then(|n| n.as_str())
+                .map(String::from),
+            schema: schema.clone(),
+        }))
+    }
+
+    /// Replaces `response_format` with a GBNF `grammar` that llama-server enforces
+    pub fn apply_grammar(&self, body: &mut Value) -> Result<(), String> {
+        let grammar = json_schema_to_gbnf(&self.schema)
+            .map_err(|e| format!("Unsupported response_format schema: {}", e))?;
+        if let Some(object) = body.as_object_mut() {
+            object.remove("response_format");
+            object.insert("grammar".to_string(), Value::String(grammar));
+        }
+        Ok(())
+    }
+
+    /// Checks the generated text of the first choice against the schema
+    pub fn validate_completion(&self, completion: &Value) -> Vec<SchemaViolation> {
+        let choice = &completion["choices"][0];
+        let Some(text) = choice["message"]["content"]
+            .as_str()
+            .or_else(|| choice["text"].as_str())
+        else {
+            return vec![SchemaViolation {
+                path: "$".to_string(),
+                message: "response contains no generated text".to_string(),
+            }];
+        };
+        match serde_json::from_str::<Value>(text) {
+            Ok(value) => validate_json_schema(&self.schema, &value),
+            Err(e) => vec![SchemaViolation {
+                path: "$".to_string(),
+                message: format!("output is not valid JSON: {}", e),
+            }],
+        }
+    }
+}
+
+/// Result of a validated structured output request
+#[derive(Debug)]
+pub struct StructuredOutcome {
+    /// The last completion received from the model
+    pub response: Value,
+    pub attempts: u32,
+    /// Empty when the response matches the schema
+    pub violations: Vec<SchemaViolation>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// Sends a non-streaming request and validates the output, retrying up to
+/// `max_retries` times when it does not match the schema
+pub async fn run_structured_completion(
+    client: &Client,
+    upstream_url: &str,
+    session_api_key: Option<&str>,
+    body: &Value,
+    output: &StructuredOutput,
+    max_retries: u32,
+) -> Result<StructuredOutcome, String> {
+    let mut prompt_tokens = 0;
+    let mut completion_tokens = 0;
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+        let mut request = client.post(upstream_url).json(body);
+        if let Some(key) = session_api_key {
+            request = request.header("Authorization", format!("Bearer {}", key));
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Proxy request to model failed: {}", e))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read model response: {}", e))?;
+        if !status.is_success() {
+            return Err(format!("Model returned {}: {}", status, text));
+        }
+        let completion: Value =
+            serde_json::from_str(&text).map_err(|e| format!("Invalid model response: {}", e))?;
+
+        if let Some(usage) = completion.get("usage") {
+            prompt_tokens += usage["prompt_tokens"].as_u64().unwrap_or(0);
+            completion_tokens += usage["completion_tokens"].as_u64().unwrap_or(0);
+        }
+
+        let violations = output.validate_completion(&completion);
+        if violations.is_empty() || attempt > max_retries {
+            return Ok(StructuredOutcome {
+                response: completion,
+                attempts: attempt,
+                violations,
+                prompt_tokens,
+                completion_tokens,
+            });
+        }
+        log::warn!(
+            "Structured output did not match schema {} (attempt {}): {:?}",
+            output.name.as_deref().unwrap_or("<unnamed>"),
+            attempt,
+            violations
+        );
+    }
+}
@@ -0,0 +1,1879 @@
+use super::audit_log::{read_audit_log, AuditLogger};
+use super::constants::{PROXY_TLS_CERT_FILE, PROXY_TLS_DIR, PROXY_TLS_KEY_FILE};
+use super::cors::{add_cors_headers, CorsDenial};
+use super::embeddings::{embedding_vectors, EmbeddingBatcher, EmbeddingTarget};
+use super::grammar::json_schema_to_gbnf;
+use super::listener::load_tls_acceptor;
+use super::metrics::ProxyMetrics;
+use super::models::{
+    AuditLogEntry, AuditLogOptions, AuditLogQuery, CorsPolicy, EmbeddingBatchOptions, ModelRoute,
+    ProxyServerOptions, RequestLogEntry, RequestLogOptions, RequestQueueOptions,
+    ResponseCacheOptions, ServerConfigUpdate, TlsOptions, VectorStoreOptions,
+};
+use super::proxy;
+use super::queue::{AdmissionError, AdmissionQueue};
+use super::request_log::*;
+use super::response_cache::ResponseCache;
+use super::routing::resolve_model_id;
+use super::structured_output::{run_structured_completion, StructuredOutput};
+use super::tool_loop::{completion_to_sse, run_tool_loop, ProxyTool, ToolProvider};
+use super::vector_store::{TextEmbedder, VectorStoreManager};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn temp_log_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("proxy-log-test-{}-{}", name, uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn log_entry(id: &str) -> RequestLogEntry {
+    RequestLogEntry {
+        id: id.to_string(),
+        timestamp: "2025-01-01T00:00:00Z".to_string(),
+        key_id: None,
+        method: "POST".to_string(),
+        path: "/chat/completions".to_string(),
+        model: Some("test-model".to_string()),
+        stream: false,
+        status: Some(200),
+        latency_ms: 1,
+        duration_ms: 2,
+        prompt_tokens: None,
+        completion_tokens: None,
+        error: None,
+        request_body: Some(json!({"model": "test-model"})),
+        response_body: None,
+    }
+}
+
+#[test]
+fn test_redact_json_nested_fields() {
+    let body = json!({
+        "model": "m",
+        "api_key": "secret-value",
+        "metadata": { "Authorization": "Bearer abc", "keep": 1 },
+        "items": [{ "password": "p" }]
+    });
+    let redacted = jan_utils::redact_json(&body, &RequestLogOptions::default().redact_fields);
+    assert_eq!(redacted["model"], "m");
+    assert_eq!(redacted["api_key"], "[REDACTED]");
+    assert_eq!(redacted["metadata"]["Authorization"], "[REDACTED]");
+    assert_eq!(redacted["metadata"]["keep"], 1);
+    assert_eq!(redacted["items"][0]["password"], "[REDACTED]");
+}
+
+#[test]
+fn test_parse_stream_usage_from_final_chunk() {
+    let sse = "data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}]}\n\n\
+               data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":34}}\n\n\
+               data: [DONE]\n\n";
+    assert_eq!(parse_stream_usage(sse), (Some(12), Some(34)));
+    assert_eq!(parse_stream_usage("data: [DONE]\n"), (None, None));
+}
+
+#[test]
+fn test_response_capture_non_stream_usage() {
+    let mut capture = ResponseCapture::default();
+    capture.push(br#"{"choices":[],"usage":{"prompt_tokens":5,"#);
+    capture.push(br#""completion_tokens":7}}"#);
+    assert_eq!(capture.usage(false), (Some(5), Some(7)));
+}
+
+#[tokio::test]
+async fn test_request_log_rotation_and_lookup() {
+    let dir = temp_log_dir("rotation");
+    let options = RequestLogOptions {
+        enabled: true,
+        max_file_bytes: 200,
+        max_files: 3,
+        ..Default::default()
+    };
+    let logger = RequestLogger::new(dir.clone(), options);
+
+    for i in 0..6 {
+        logger.append(&log_entry(&format!("req-{}", i))).await;
+    }
+
+    assert!(request_log_path(&dir, 0).exists());
+    assert!(request_log_path(&dir, 2).exists());
+    assert!(!request_log_path(&dir, 3).exists());
+
+    let latest = find_request_log_entry(&dir, 3, "req-5").unwrap();
+    assert_eq!(latest.map(|e| e.id), Some("req-5".to_string()));
+    let dropped = find_request_log_entry(&dir, 3, "req-0").unwrap();
+    assert!(dropped.is_none());
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn test_request_log_redacts_response_bodies() {
+    let dir = temp_log_dir("redact-response");
+    let options = RequestLogOptions {
+        enabled: true,
+        include_bodies: true,
+        ..Default::default()
+    };
+    let fields = options.redact_fields.clone();
+    let logger = RequestLogger::new(dir.clone(), options);
+
+    let mut entry = log_entry("req-json");
+    entry.response_body = Some(r#"{"token":"abc","content":"hi"}"#.to_string());
+    logger.append(&entry).await;
+
+    let logged = find_request_log_entry(&dir, 1, "req-json")
+        .unwrap()
+        .and_then(|e| e.response_body)
+        .unwrap();
+    assert!(!logged.contains("abc"));
+    assert!(logged.contains("hi"));
+
+    let sse = "data: {\"secret\":\"s3\",\"n\":1}\n\ndata: [DONE]\n\n[truncated]";
+    let redacted = redact_text(sse, &fields);
+    assert!(!redacted.contains("s3"));
+    assert!(redacted.contains("data: [DONE]"));
+    assert!(redacted.ends_with("[truncated]"));
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_metrics_render_prometheus_text() {
+    let metrics = ProxyMetrics::new();
+    metrics.record_request("POST", "/chat/completions", 200);
+    metrics.record_request("GET", "/some/unknown/path", 404);
+    metrics.record_upstream(
+        "/chat/completions",
+        "qwen",
+        200,
+        Duration::from_secs(2),
+        Some(40),
+    );
+    metrics.record_upstream_failure(502);
+
+    let guard = metrics.stream_started();
+    let queued = HashMap::from([("qwen".to_string(), 2)]);
+    let text = metrics.render(1, &queued);
+    drop(guard);
+
+    assert!(text.contains(
+        "jan_proxy_requests_total{method=\"POST\",path=\"/chat/completions\",status=\"200\"} 1"
+    ));
+    assert!(text.contains("path=\"other\",status=\"404\""));
+    assert!(text.contains("jan_proxy_tokens_per_second{model=\"qwen\"} 20"));
+    assert!(text.contains(
+        "jan_proxy_upstream_duration_seconds_bucket{path=\"/chat/completions\",model=\"qwen\",le=\"2.5\"} 1"
+    ));
+    assert!(text.contains("jan_proxy_upstream_errors_total{status=\"502\"} 1"));
+    assert!(text.contains("jan_proxy_in_flight_streams 1"));
+    assert!(text.contains("jan_proxy_queued_requests{model=\"qwen\"} 2"));
+    assert!(text.contains("jan_llama_sessions_running 1"));
+    assert_eq!(metrics.in_flight_streams(), 0);
+}
+
+#[tokio::test]
+async fn test_admission_queue_limits_and_positions() {
+    let queue = Arc::new(AdmissionQueue::new(RequestQueueOptions {
+        enabled: true,
+        max_concurrent: 1,
+        max_queue_depth: 1,
+        queue_timeout_secs: 5,
+        retry_after_secs: 1,
+    }));
+
+    let first = queue.acquire("model").await.unwrap();
+    assert_eq!(first.queue_position, 0);
+
+    let waiter = {
+        let queue = queue.clone();
+        tokio::spawn(async move { queue.acquire("model").await.map(|a| a.queue_position) })
+    };
+    while queue.waiting().await.get("model").copied().unwrap_or(0) == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    // Queue depth of 1 is taken by the waiter
+    assert_eq!(
+        queue.acquire("model").await.err(),
+        Some(AdmissionError::QueueFull)
+    );
+    // Other models have their own slots
+    assert!(queue.acquire("other-model").await.is_ok());
+
+    drop(first);
+    assert_eq!(waiter.await.unwrap(), Ok(1));
+}
+
+#[tokio::test]
+async fn test_admission_queue_timeout() {
+    let queue = AdmissionQueue::new(RequestQueueOptions {
+        enabled: true,
+        max_concurrent: 1,
+        max_queue_depth: 4,
+        queue_timeout_secs: 1,
+        retry_after_secs: 1,
+    });
+
+    let _held = queue.acquire("model").await.unwrap();
+    assert_eq!(
+        queue.acquire("model").await.err(),
+        Some(AdmissionError::Timeout)
+    );
+    assert_eq!(queue.waiting().await.get("model"), Some(&0));
+}
+
+#[test]
+fn test_resolve_model_id_aliases_and_fallbacks() {
+    let aliases = HashMap::from([
+        (
+            "gpt-4o-mini".to_string(),
+            ModelRoute {
+                model_id: "qwen3-4b".to_string(),
+                fallbacks: vec!["llama-3.2-3b".to_string()],
+            },
+        ),
+        (
+            "gpt-4o".to_string(),
+            ModelRoute {
+                model_id: "qwen3-32b".to_string(),
+                fallbacks: vec![],
+            },
+        ),
+    ]);
+
+    let running = ["qwen3-4b", "llama-3.2-3b"];
+    assert_eq!(
+        resolve_model_id("qwen3-4b", &aliases, running),
+        Some("qwen3-4b".to_string())
+    );
+    assert_eq!(
+        resolve_model_id("gpt-4o-mini", &aliases, running),
+        Some("qwen3-4b".to_string())
+    );
+    assert_eq!(resolve_model_id("gpt-4o", &aliases, running), None);
+    assert_eq!(resolve_model_id("unknown", &aliases, running), None);
+
+    // Primary not loaded, first running fallback wins
+    assert_eq!(
+        resolve_model_id("gpt-4o-mini", &aliases, ["llama-3.2-3b"]),
+        Some("llama-3.2-3b".to_string())
+    );
+}
+
+#[test]
+fn test_self_signed_cert_generated_once_and_reused() {
+    let dir = temp_log_dir("tls");
+    let options = TlsOptions {
+        enabled: true,
+        ..Default::default()
+    };
+
+    load_tls_acceptor(&options, &dir, &["jan.local".to_string()]).unwrap();
+    let cert_path = dir.join(PROXY_TLS_DIR).join(PROXY_TLS_CERT_FILE);
+    let first = fs::read_to_string(&cert_path).unwrap();
+    assert!(first.contains("BEGIN CERTIFICATE"));
+    assert!(dir.join(PROXY_TLS_DIR).join(PROXY_TLS_KEY_FILE).exists());
+
+    load_tls_acceptor(&options, &dir, &[]).unwrap();
+    assert_eq!(fs::read_to_string(&cert_path).unwrap(), first);
+
+    let partial = TlsOptions {
+        enabled: true,
+        cert_path: Some(cert_path.to_string_lossy().to_string()),
+        key_path: None,
+    };
+    assert!(load_tls_acceptor(&partial, &dir, &[]).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn restricted_cors_policy() -> CorsPolicy {
+    CorsPolicy {
+        allowed_origins: vec![
+            "https://app.example.com".to_string(),
+            "https://*.trusted.dev".to_string(),
+            "http://localhost:*".to_string(),
+        ],
+        allowed_headers: vec!["authorization".to_string(), "content-type".to_string()],
+        allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+        allow_credentials: false,
+        max_age_secs: 600,
+    }
+}
+
+#[test]
+fn test_cors_origin_patterns() {
+    let policy = restricted_cors_policy();
+    assert!(policy.is_origin_allowed("https://app.example.com"));
+    assert!(policy.is_origin_allowed("https://api.trusted.dev"));
+    assert!(policy.is_origin_allowed("http://localhost:1420"));
+    assert!(!policy.is_origin_allowed("https://trusted.dev.evil.com"));
+    assert!(!policy.is_origin_allowed("https://evil.com"));
+    assert!(!policy.is_origin_allowed("http://app.example.com"));
+
+    assert_eq!(policy.check_request(""), Ok(()));
+    assert_eq!(
+        policy.check_request("https://evil.com"),
+        Err(CorsDenial::Origin)
+    );
+}
+
+#[test]
+fn test_cors_preflight_denials() {
+    let policy = restricted_cors_policy();
+    let origin = "https://app.example.com";
+
+    assert_eq!(
+        policy.check_preflight(origin, "POST", "Authorization, Content-Type"),
+        Ok(())
+    );
+    assert_eq!(
+        policy.check_preflight("https://evil.com", "POST", ""),
+        Err(CorsDenial::Origin)
+    );
+    assert_eq!(
+        policy.check_preflight(origin, "DELETE", ""),
+        Err(CorsDenial::Method)
+    );
+    assert_eq!(
+        policy.check_preflight(origin, "POST", "content-type, x-custom"),
+        Err(CorsDenial::Headers)
+    );
+}
+
+#[test]
+fn test_cors_response_headers() {
+    let header = |response: &hyper::Response<()>, name: &str| {
+        response
+            .headers()
+            .get(name)
+            .map(|v| v.to_str().unwrap().to_string())
+    };
+
+    let policy = restricted_cors_policy();
+    let denied = add_cors_headers(hyper::Response::builder(), "https://evil.com", &policy)
+        .body(())
+        .unwrap();
+    assert_eq!(header(&denied, "Access-Control-Allow-Origin"), None);
+
+    let allowed = add_cors_headers(hyper::Response::builder(), "http://localhost:3000", &policy)
+        .body(())
+        .unwrap();
+    assert_eq!(
+        header(&allowed, "Access-Control-Allow-Origin").as_deref(),
+        Some("http://localhost:3000")
+    );
+    assert_eq!(header(&allowed, "Access-Control-Allow-Credentials"), None);
+
+    // Credentialed responses must echo the origin rather than `*`
+    let credentialed = CorsPolicy {
+        allow_credentials: true,
+        ..restricted_cors_policy()
+    };
+    let echoed = add_cors_headers(
+        hyper::Response::builder(),
+        "https://app.example.com",
+        &credentialed,
+    )
+    .body(())
+    .unwrap();
+    assert_eq!(
+        header(&echoed, "Access-Control-Allow-Origin").as_deref(),
+        Some("https://app.example.com")
+    );
+    assert_eq!(
+        header(&echoed, "Access-Control-Allow-Credentials").as_deref(),
+        Some("true")
+    );
+}
+
+#[test]
+fn test_cors_wildcard_never_sends_credentials() {
+    let header = |response: &hyper::Response<()>, name: &str| {
+        response
+            .headers()
+            .get(name)
+            .map(|v| v.to_str().unwrap().to_string())
+    };
+
+    let default_policy = CorsPolicy::default();
+    assert!(!default_policy.allow_credentials);
+    let response = add_cors_headers(
+        hyper::Response::builder(),
+        "https://any.site",
+        &default_policy,
+    )
+    .body(())
+    .unwrap();
+    assert_eq!(
+        header(&response, "Access-Control-Allow-Origin").as_deref(),
+        Some("*")
+    );
+    assert_eq!(header(&response, "Access-Control-Allow-Credentials"), None);
+
+    // `*` combined with credentials still answers arbitrary origins with a bare `*`
+    let mut wildcard = restricted_cors_policy();
+    wildcard.allowed_origins.push("*".to_string());
+    wildcard.allow_credentials = true;
+    let arbitrary = add_cors_headers(hyper::Response::builder(), "https://evil.com", &wildcard)
+        .body(())
+        .unwrap();
+    assert_eq!(
+        header(&arbitrary, "Access-Control-Allow-Origin").as_deref(),
+        Some("*")
+    );
+    assert_eq!(header(&arbitrary, "Access-Control-Allow-Credentials"), None);
+    let listed = add_cors_headers(
+        hyper::Response::builder(),
+        "https://app.example.com",
+        &wildcard,
+    )
+    .body(())
+    .unwrap();
+    assert_eq!(
+        header(&listed, "Access-Control-Allow-Origin").as_deref(),
+        Some("https://app.example.com")
+    );
+    assert_eq!(
+        header(&listed, "Access-Control-Allow-Credentials").as_deref(),
+        Some("true")
+    );
+}
+
+#[tokio::test]
+async fn test_server_status_live_update_and_drain() {
+    let server_handle = Arc::new(tokio::sync::Mutex::new(None));
+    let sessions = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
+    let data_dir = temp_log_dir("server");
+
+    proxy::start_server(
+        server_handle.clone(),
+        sessions,
+        "127.0.0.1".to_string(),
+        0,
+        "/v1".to_string(),
+        String::new(),
+        vec!["127.0.0.1".to_string()],
+        ProxyServerOptions::default(),
+        data_dir.clone(),
+        None,
+    )
+    .await
+    .unwrap();
+
+    let status = proxy::get_server_status(server_handle.clone()).await;
+    assert!(status.running);
+    let address = status.address.unwrap();
+    assert!(address.starts_with("http://127.0.0.1:"));
+    assert!(!status.config.unwrap().api_key_required);
+
+    let client = reqwest::Client::new();
+    let models_url = format!("{}/v1/models", address);
+    let response = client.get(&models_url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let snapshot = proxy::update_server_config(
+        server_handle.clone(),
+        ServerConfigUpdate {
+            api_key: Some("secret".to_string()),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert!(snapshot.api_key_required);
+    let response = client.get(&models_url).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+
+    let mut listener_change = ProxyServerOptions::default();
+    listener_change.tls.enabled = true;
+    assert!(proxy::update_server_config(
+        server_handle.clone(),
+        ServerConfigUpdate {
+            options: Some(listener_change),
+            ..Default::default()
+        },
+    )
+    .await
+    .is_err());
+
+    // The client keeps an idle keep-alive connection open, which draining must close
+    assert_eq!(
+        proxy::get_server_status(server_handle.clone())
+            .await
+            .active_connections,
+        1
+    );
+    let started = std::time::Instant::now();
+    proxy::stop_server(server_handle.clone(), Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(!proxy::get_server_status(server_handle).await.running);
+
+    fs::remove_dir_all(&data_dir).unwrap();
+}
+
+struct EchoToolProvider {
+    calls: std::sync::Mutex<Vec<String>>,
+}
+
+impl ToolProvider for EchoToolProvider {
+    fn list_tools(&self) -> futures_util::future::BoxFuture<'_, Result<Vec<ProxyTool>, String>> {
+        Box::pin(async {
+            Ok(vec![ProxyTool {
+                name: "find_leads".to_string(),
+                description: Some("Find leads".to_string()),
+                parameters: json!({"type": "object"}),
+            }])
+        })
+    }
+
+    fn call_tool(
+        &self,
+        name: String,
+        arguments: Option<serde_json::Map<String, serde_json::Value>>,
+    ) -> futures_util::future::BoxFuture<'_, Result<String, String>> {
+        self.calls.lock().unwrap().push(name);
+        let query = arguments
+            .and_then(|args| args.get("query")?.as_str().map(String::from))
+            .unwrap_or_default();
+        Box::pin(async move { Ok(format!("2 leads for {}", query)) })
+    }
+}
+
+/// Fake llama-server: asks for a tool with `arguments` on the first turn, answers once a
+/// tool result is present
+async fn spawn_fake_tool_model(arguments: &'static str) -> String {
+    use hyper::service::{make_service_fn, service_fn};
+
+    let make_svc = make_service_fn(move |_| async move {
+        Ok::<_, std::convert::Infallible>(service_fn(
+            move |req: hyper::Request<hyper::Body>| async move {
+                let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                assert_eq!(request["stream"], json!(false));
+                assert_eq!(request["tools"][0]["function"]["name"], json!("find_leads"));
+
+                let last = request["messages"].as_array().unwrap().last().unwrap();
+                let message = if last["role"] == "tool" {
+                    json!({"role": "assistant", "content": format!("Done: {}", last["content"].as_str().unwrap())})
+                } else {
+                    json!({"role": "assistant", "content": null, "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "find_leads", "arguments": arguments}
+                    }]})
+                };
+                let response = json!({
+                    "id": "chatcmpl-1",
+                    "created": 1,
+                    "model": "test-model",
+                    "choices": [{"index": 0, "message": message, "finish_reason": "stop"}],
+                    "usage": {"prompt_tokens": 10, "completion_tokens": 5}
+                });
+                Ok::<_, std::convert::Infallible>(hyper::Response::new(hyper::Body::from(
+                    response.to_string(),
+                )))
+            },
+        ))
+    });
+    let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+    let url = format!("http://{}/chat/completions", server.local_addr());
+    tokio::spawn(server);
+    url
+}
+
+#[tokio::test]
+async fn test_tool_loop_runs_tools_until_final_answer() {
+    let url = spawn_fake_tool_model("{\"query\":\"acme\"}").await;
+    let provider = EchoToolProvider {
+        calls: std::sync::Mutex::new(Vec::new()),
+    };
+    let body = json!({
+        "model": "test-model",
+        "stream": true,
+        "messages": [{"role": "user", "content": "Find leads at acme"}]
+    });
+
+    let outcome = run_tool_loop(&reqwest::Client::new(), &url, None, body, &provider, 4)
+        .await
+        .unwrap();
+    assert_eq!(outcome.iterations, 2);
+    assert_eq!(outcome.tool_calls, 1);
+    assert_eq!(outcome.completion_tokens, 10);
+    assert_eq!(*provider.calls.lock().unwrap(), vec!["find_leads"]);
+    assert_eq!(
+        outcome.response["choices"][0]["message"]["content"],
+        json!("Done: 2 leads for acme")
+    );
+
+    let sse = completion_to_sse(&outcome.response);
+    assert!(sse.contains("\"content\":\"Done: 2 leads for acme\""));
+    assert!(sse.ends_with("data: [DONE]\n\n"));
+
+    let exhausted = run_tool_loop(
+        &reqwest::Client::new(),
+        &url,
+        None,
+        json!({"model": "test-model", "messages": [{"role": "user", "content": "hi"}]}),
+        &provider,
+        1,
+    )
+    .await;
+    assert!(exhausted.is_err());
+}
+
+#[tokio::test]
+async fn test_tool_loop_rejects_invalid_arguments() {
+    let url = spawn_fake_tool_model("{\"query\": acme").await;
+    let provider = EchoToolProvider {
+        calls: std::sync::Mutex::new(Vec::new()),
+    };
+    let body = json!({
+        "model": "test-model",
+        "messages": [{"role": "user", "content": "Find leads at acme"}]
+    });
+
+    let outcome = run_tool_loop(&reqwest::Client::new(), &url, None, body, &provider, 4)
+        .await
+        .unwrap();
+    // The tool never runs; the model is told why instead
+    assert_eq!(outcome.tool_calls, 0);
+    assert!(provider.calls.lock().unwrap().is_empty());
+    let content = outcome.response["choices"][0]["message"]["content"]
+        .as_str()
+        .unwrap();
+    assert!(content.starts_with("Done: Error: invalid arguments for tool 'find_leads'"));
+}
+
+#[tokio::test]
+async fn test_response_cache_keys_ttl_and_eviction() {
+    let dir = temp_log_dir("cache");
+    let model_path = dir.join("model.gguf");
+    fs::write(&model_path, vec![7u8; 64 * 1024]).unwrap();
+    let model_path = model_path.to_string_lossy().to_string();
+
+    let cache = ResponseCache::new(
+        dir.join("entries"),
+        ResponseCacheOptions {
+            enabled: true,
+            max_total_bytes: 1024,
+            ..Default::default()
+        },
+    );
+
+    let body =
+        json!({"model": "a", "temperature": 0, "messages": [{"role": "user", "content": "hi"}]});
+    let reordered = json!({"messages": [{"content": "hi", "role": "user"}], "temperature": 0, "model": "alias"});
+    assert!(cache.is_cacheable("/chat/completions", &body));
+    assert!(!cache.is_cacheable("/chat/completions", &json!({"temperature": 0.7})));
+    assert!(!cache.is_cacheable("/models", &body));
+
+    let key = cache
+        .key("/chat/completions", &body, &model_path)
+        .await
+        .unwrap();
+    assert_eq!(
+        key,
+        cache
+            .key("/chat/completions", &reordered, &model_path)
+            .await
+            .unwrap()
+    );
+    let streamed = json!({"temperature": 0, "stream": true, "messages": []});
+    assert_ne!(
+        key,
+        cache
+            .key("/chat/completions", &streamed, &model_path)
+            .await
+            .unwrap()
+    );
+
+    assert!(cache.get(&key).await.is_none());
+    cache
+        .put(
+            &key,
+            "a",
+            Some("application/json".to_string()),
+            "{\"ok\":true}".to_string(),
+        )
+        .await;
+    let cached = cache.get(&key).await.unwrap();
+    assert_eq!(cached.body, "{\"ok\":true}");
+    assert_eq!(cached.content_type.as_deref(), Some("application/json"));
+
+    // Filling past the size limit evicts the oldest entry
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    cache.put("big", "a", None, "x".repeat(900)).await;
+    assert!(cache.get(&key).await.is_none());
+    assert!(cache.get("big").await.is_some());
+
+    let expiring = ResponseCache::new(
+        dir.join("entries"),
+        ResponseCacheOptions {
+            enabled: true,
+            ttl_secs: 0,
+            ..Default::default()
+        },
+    );
+    expiring.put("old", "a", None, "{}".to_string()).await;
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(expiring.get("old").await.is_none());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn lead_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "company": {"type": "string", "minLength": 1},
+            "employees": {"type": "integer", "minimum": 0},
+            "stage": {"enum": ["lead", "customer"]},
+            "contacts": {"type": "array", "items": {"$ref": "#/$defs/Contact"}, "maxItems": 3}
+        },
+        "required": ["company", "stage"],
+        "additionalProperties": false,
+        "$defs": {
+            "Contact": {
+                "type": "object",
+                "properties": {"email": {"type": "string"}, "phone": {"type": ["string", "null"]}},
+                "required": ["email"]
+            }
+        }
+    })
+}
+
+#[test]
+fn test_json_schema_to_gbnf() {
+    let grammar = json_schema_to_gbnf(&lead_schema()).unwrap();
+    assert!(grammar.starts_with("root ::= \"{\" space \"\\\"company\\\"\" space"));
+    assert!(
+        grammar.contains("( \",\" space \"\\\"contacts\\\"\" space \":\" space root-contacts )?")
+    );
+    assert!(grammar.contains("root-company ::= \"\\\"\" char+ \"\\\"\" space"));
+    assert!(
+        grammar.contains("root-stage ::= (\"\\\"lead\\\"\" space | \"\\\"customer\\\"\" space)")
+    );
+    assert!(grammar.contains(
+        "root-contacts ::= \"[\" space ( def-Contact (\",\" space def-Contact){0,2} )? \"]\" space"
+    ));
+    assert!(grammar.contains("def-Contact ::= "));
+    assert!(grammar.contains("def-Contact-phone ::= string | null"));
+    for primitive in [
+        "space ::=",
+        "char ::=",
+        "integer ::=",
+        "null ::=",
+        "integral-part ::=",
+    ] {
+        assert!(grammar.contains(primitive), "missing {}", primitive);
+    }
+    assert!(!grammar.contains("boolean ::="));
+
+    // Recursive definitions refer back to their own rule
+    let tree = json!({
+        "$ref": "#/definitions/Node",
+        "definitions": {"Node": {"type": "object", "properties": {
+            "children": {"type": "array", "items": {"$ref": "#/definitions/Node"}}
+        }}}
+    });
+    let grammar = json_schema_to_gbnf(&tree).unwrap();
+    assert!(grammar.starts_with("root ::= def-Node\n"));
+    assert!(grammar.contains("def-Node-children ::= \"[\" space ( def-Node"));
+
+    assert!(json_schema_to_gbnf(&json!({"allOf": [{"type": "string"}]})).is_err());
+    assert!(json_schema_to_gbnf(&json!({"$ref": "#/$defs/Missing"})).is_err());
+}
+
+#[test]
+fn test_json_schema_validation() {
+    let schema = lead_schema();
+    let valid = json!({
+        "company": "Acme",
+        "stage": "lead",
+        "employees": 12,
+        "contacts": [{"email": "a@acme.test", "phone": null}]
+    });
+    assert!(jan_utils::validate_json_schema(&schema, &valid).is_empty());
+
+    let invalid = json!({
+        "company": "",
+        "stage": "prospect",
+        "employees": 1.5,
+        "contacts": [{"phone": 5}],
+        "notes": "x"
+    });
+    let paths: Vec<String> = jan_utils::validate_json_schema(&schema, &invalid)
+        .into_iter()
+        .map(|v| format!("{}: {}", v.path, v.message))
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            "$.company: must be at least 1 characters",
+            "$.contacts[0]: missing required property 'email'",
+            "$.contacts[0].phone: expected one of [string, null], got integer",
+            "$.employees: expected integer, got number",
+            "$: unexpected property 'notes'",
+            "$.stage: must be one of [\"lead\",\"customer\"]",
+        ]
+    );
+}
+
+#[test]
+fn test_json_schema_circular_refs() {
+    let schema = json!({"$defs": {"A": {"$ref": "#/$defs/A"}}, "$ref": "#/$defs/A"});
+    let violations = jan_utils::validate_json_schema(&schema, &json!({"x": 1}));
+    assert_eq!(violations.len(), 1);
+    assert_eq!(
+        violations[0].message,
+        "circular schema reference '#/$defs/A'"
+    );
+    let mut value = json!("5");
+    assert_eq!(jan_utils::coerce_json_types(&schema, &mut value), 0);
+
+    let mutual = json!({
+        "$defs": {"A": {"allOf": [{"$ref": "#/$defs/B"}]}, "B": {"anyOf": [{"$ref": "#/$defs/A"}]}},
+        "$ref": "#/$defs/A"
+    });
+    assert!(!jan_utils::validate_json_schema(&mutual, &json!(1)).is_empty());
+
+    // Recursion that descends into the value is not a cycle
+    let tree = json!({
+        "$ref": "#/definitions/Node",
+        "definitions": {"Node": {"type": "object", "properties": {
+            "size": {"type": "integer"},
+            "children": {"type": "array", "items": {"$ref": "#/definitions/Node"}}
+        }}}
+    });
+    let mut value = json!({"children": [{"children": [{"size": "3"}]}]});
+    assert_eq!(jan_utils::coerce_json_types(&tree, &mut value), 1);
+    assert!(jan_utils::validate_json_schema(&tree, &value).is_empty());
+}
+
+/// Fake llama-server that answers with invalid JSON first, then a matching object
+async fn spawn_fake_structured_model() -> String {
+    use hyper::service::{make_service_fn, service_fn};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let make_svc = make_service_fn(move |_| {
+        let calls = calls.clone();
+        async move {
+            Ok::<_, std::convert::Infallible>(service_fn(
+                move |req: hyper::Request<hyper::Body>| {
+                    let calls = calls.clone();
+                    async move {
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                        assert!(request["grammar"].as_str().unwrap().starts_with("root ::="));
+                        assert!(request.get("response_format").is_none());
+
+                        let content = match calls.fetch_add(1, Ordering::SeqCst) {
+                            0 => "{\"company\": \"Acme\"".to_string(),
+                            _ => json!({"company": "Acme", "stage": "lead"}).to_string(),
+                        };
+                        let response = json!({
+                            "choices": [{"index": 0, "message": {"role": "assistant", "content": content}}],
+                            "usage": {"prompt_tokens": 10, "completion_tokens": 5}
+                        });
+                        Ok::<_, std::convert::Infallible>(hyper::Response::new(hyper::Body::from(
+                            response.to_string(),
+                        )))
+                    }
+                },
+            ))
+        }
+    });
+    let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+    let url = format!("http://{}/chat/completions", server.local_addr());
+    tokio::spawn(server);
+    url
+}
+
+#[tokio::test]
+async fn test_structured_output_retries_invalid_response() {
+    let request = json!({
+        "model": "test-model",
+        "messages": [{"role": "user", "content": "Enrich Acme"}],
+        "response_format": {"type": "json_schema", "json_schema": {"name": "lead", "schema": lead_schema()}}
+    });
+    let output = StructuredOutput::from_request(&request).unwrap().unwrap();
+    assert_eq!(output.name.as_deref(), Some("lead"));
+    assert!(
+        StructuredOutput::from_request(&json!({"response_format": {"type": "json_object"}}))
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        StructuredOutput::from_request(&json!({"response_format": {"type": "json_schema"}}))
+            .is_err()
+    );
+
+    let mut body = request.clone();
+    output.apply_grammar(&mut body).unwrap();
+
+    let url = spawn_fake_structured_model().await;
+    let client = reqwest::Client::new();
+    let outcome = run_structured_completion(&client, &url, None, &body, &output, 1)
+        .await
+        .unwrap();
+    assert_eq!(outcome.attempts, 2);
+    assert!(outcome.violations.is_empty());
+    assert_eq!(outcome.completion_tokens, 10);
+
+    let url = spawn_fake_structured_model().await;
+    let outcome = run_structured_completion(&client, &url, None, &body, &output, 0)
+        .await
+        .unwrap();
+    assert_eq!(outcome.attempts, 1);
+    assert!(outcome.violations[0]
+        .message
+        .starts_with("output is not valid JSON"));
+}
+
+/// Toy embedding: text length and vowel count, so similarity is predictable
+fn toy_embedding(text: &str) -> Vec<f32> {
+    let vowels = text.chars().filter(|c| "aeiou".contains(*c)).count();
+    vec![text.len() as f32, vowels as f32]
+}
+
+/// Fake llama-server `/embeddings` that records how many inputs each call carried
+async fn spawn_fake_embedding_model(calls: Arc<std::sync::Mutex<Vec<usize>>>) -> String {
+    use hyper::service::{make_service_fn, service_fn};
+
+    let make_svc = make_service_fn(move |_| {
+        let calls = calls.clone();
+        async move {
+            Ok::<_, std::convert::Infallible>(service_fn(
+                move |req: hyper::Request<hyper::Body>| {
+                    let calls = calls.clone();
+                    async move {
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                        let inputs: Vec<String> = match &request["input"] {
+                            serde_json::Value::String(text) => vec![text.clone()],
+                            other => serde_json::from_value(other.clone()).unwrap(),
+                        };
+                        calls.lock().unwrap().push(inputs.len());
+                        let data: Vec<_> = inputs
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .map(|(i, text)| json!({"object": "embedding", "index": i, "embedding": toy_embedding(text)}))
+                        .collect();
+                        let response = json!({
+                            "object": "list",
+                            "model": request["model"],
+                            "data": data,
+                            "usage": {"prompt_tokens": inputs.len() * 4, "total_tokens": inputs.len() * 4}
+                        });
+                        Ok::<_, std::convert::Infallible>(hyper::Response::new(hyper::Body::from(
+                            response.to_string(),
+                        )))
+                    }
+                },
+            ))
+        }
+    });
+    let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+    let url = format!("http://{}/embeddings", server.local_addr());
+    tokio::spawn(server);
+    url
+}
+
+#[tokio::test]
+async fn test_embedding_batcher_coalesces_concurrent_requests() {
+    let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let url = spawn_fake_embedding_model(calls.clone()).await;
+    let batcher = Arc::new(EmbeddingBatcher::new(EmbeddingBatchOptions {
+        enabled: true,
+        window_ms: 50,
+        max_batch_inputs: 8,
+    }));
+    let target = EmbeddingTarget {
+        client: reqwest::Client::new(),
+        upstream_url: url,
+        session_api_key: None,
+        admission: None,
+    };
+
+    let (first, second, third) = tokio::join!(
+        batcher.embed(target.clone(), json!({"model": "embed", "input": "acme"})),
+        batcher.embed(
+            target.clone(),
+            json!({"model": "embed", "input": ["globex", "initech"]})
+        ),
+        batcher.embed(target.clone(), json!({"model": "other", "input": "hooli"})),
+    );
+    let (first, second, third) = (first.unwrap(), second.unwrap(), third.unwrap());
+
+    // Different parameters are never mixed into the same batch
+    let mut batch_sizes = calls.lock().unwrap().clone();
+    batch_sizes.sort();
+    assert_eq!(batch_sizes, vec![1, 3]);
+
+    assert_eq!(
+        embedding_vectors(&first).unwrap(),
+        vec![toy_embedding("acme")]
+    );
+    assert_eq!(
+        embedding_vectors(&second).unwrap(),
+        vec![toy_embedding("globex"), toy_embedding("initech")]
+    );
+    assert_eq!(second["data"][0]["index"], json!(0));
+    assert_eq!(second["usage"]["prompt_tokens"], json!(8));
+    assert_eq!(first["usage"]["prompt_tokens"], json!(4));
+    assert_eq!(
+        embedding_vectors(&third).unwrap(),
+        vec![toy_embedding("hooli")]
+    );
+
+    // Oversized requests skip the batch window
+    let many: Vec<String> = (0..8).map(|i| format!("lead {}", i)).collect();
+    let response = batcher
+        .embed(target, json!({"model": "embed", "input": many}))
+        .await
+        .unwrap();
+    assert_eq!(response["data"].as_array().unwrap().len(), 8);
+}
+
+struct ToyEmbedder;
+
+impl TextEmbedder for ToyEmbedder {
+    fn embed(
+        &self,
+        model: String,
+        texts: Vec<String>,
+    ) -> futures_util::future::BoxFuture<'_, Result<Vec<Vec<f32>>, String>> {
+        Box::pin(async move {
+            assert_eq!(model, "embed");
+            Ok(texts.iter().map(|t| toy_embedding(t)).collect())
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_vector_store_create_upsert_query_and_persist() {
+    use hyper::{Method, StatusCode};
+
+    async fn call(
+        stores: &VectorStoreManager,
+        method: Method,
+        path: String,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let body = body.to_string();
+        stores
+            .handle_request(&method, &path, body.as_bytes(), &ToyEmbedder)
+            .await
+    }
+
+    let dir = temp_log_dir("vectors");
+    let stores = VectorStoreManager::new(dir.clone());
+
+    let (status, created) = call(
+        &stores,
+        Method::POST,
+        "/vector_stores".to_string(),
+        json!({"name": "lead notes", "model": "embed"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let id = created["id"].as_str().unwrap().to_string();
+    assert!(id.starts_with("vs_"));
+
+    let (status, upserted) = call(
+        &stores,
+        Method::POST,
+        format!("/vector_stores/{}/upsert", id),
+        json!({"records": [
+            {"id": "n1", "text": "aaaa", "metadata": {"owner": "sam"}},
+            {"id": "n2", "text": "bcdfgh", "metadata": {"owner": "kim"}},
+            {"id": "n3", "embedding": [4.0, 3.0], "metadata": {"owner": "kim"}}
+        ]}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upserted["record_count"], json!(3));
+
+    let (status, _) = call(
+        &stores,
+        Method::POST,
+        format!("/vector_stores/{}/upsert", id),
+        json!({"records": [{"id": "bad", "embedding": [1.0, 2.0, 3.0]}]}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, results) = call(
+        &stores,
+        Method::POST,
+        format!("/vector_stores/{}/query", id),
+        json!({"query": "eeee", "top_k": 2}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(results["data"][0]["id"], json!("n1"));
+    assert_eq!(results["data"].as_array().unwrap().len(), 2);
+
+    let (_, filtered) = call(
+        &stores,
+        Method::POST,
+        format!("/vector_stores/{}/query", id),
+        json!({"embedding": [1.0, 0.0], "filter": {"owner": "kim"}}),
+    )
+    .await;
+    let ids: Vec<&str> = filtered["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["n2", "n3"]);
+
+    // A fresh manager reads the collection back from disk
+    let reloaded = VectorStoreManager::new(dir.clone());
+    let (status, store) = call(
+        &reloaded,
+        Method::GET,
+        format!("/vector_stores/{}", id),
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(store["record_count"], json!(3));
+    assert_eq!(store["dimensions"], json!(2));
+
+    let (status, _) = call(
+        &reloaded,
+        Method::DELETE,
+        format!("/vector_stores/{}", id),
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, list) = call(
+        &reloaded,
+        Method::GET,
+        "/vector_stores".to_string(),
+        json!({}),
+    )
+    .await;
+    assert_eq!(list["data"], json!([]));
+    assert!(!dir.join(format!("{}.json", id)).exists());
+
+    fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn test_audit_log_records_served_and_rejected_requests() {
+    let server_handle = Arc::new(tokio::sync::Mutex::new(None));
+    let sessions = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
+    let data_dir = temp_log_dir("audit");
+
+    proxy::start_server(
+        server_handle.clone(),
+        sessions,
+        "127.0.0.1".to_string(),
+        0,
+        "/v1".to_string(),
+        "secret".to_string(),
+        vec!["127.0.0.1".to_string()],
+        ProxyServerOptions::default(),
+        data_dir.clone(),
+        None,
+    )
+    .await
+    .unwrap();
+    let address = proxy::get_server_status(server_handle.clone())
+        .await
+        .address
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let models_url = format!("{}/v1/models", address);
+    let send = |request: reqwest::RequestBuilder| async move {
+        request
+            .header("User-Agent", "crm-sync/1.0")
+            .send()
+            .await
+            .unwrap()
+            .status()
+            .as_u16()
+    };
+    assert_eq!(
+        send(client.get(&models_url).bearer_auth("secret")).await,
+        200
+    );
+    assert_eq!(send(client.get(&models_url)).await, 401);
+    assert_eq!(
+        send(client.get(&models_url).bearer_auth("guess")).await,
+        401
+    );
+    assert_eq!(
+        send(client.get(&models_url).header("Host", "evil.example")).await,
+        403
+    );
+    assert_eq!(
+        send(
+            client
+                .get(format!("{}/v1/configs", address))
+                .bearer_auth("secret")
+        )
+        .await,
+        404
+    );
+    proxy::stop_server(server_handle, Duration::from_secs(1))
+        .await
+        .unwrap();
+
+    let log_dir = data_dir.join("logs");
+    let all = read_audit_log(&log_dir, 5, &AuditLogQuery::default()).unwrap();
+    assert_eq!(all.len(), 5);
+    assert!(all.iter().all(|e| e.remote_addr.starts_with("127.0.0.1:")));
+    assert!(all
+        .iter()
+        .all(|e| e.user_agent.as_deref() == Some("crm-sync/1.0")));
+    // Newest first
+    assert_eq!(all[0].rejection.as_deref(), Some("configs_access"));
+    assert_eq!(all[1].host.as_deref(), Some("evil.example"));
+    assert_eq!(all[4].status, 200);
+    assert_eq!(all[4].rejection, None);
+
+    let rejected = read_audit_log(
+        &log_dir,
+        5,
+        &AuditLogQuery {
+            rejected_only: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let reasons: Vec<&str> = rejected
+        .iter()
+        .filter_map(|e| e.rejection.as_deref())
+        .collect();
+    assert_eq!(
+        reasons,
+        vec![
+            "configs_access",
+            "invalid_host",
+            "invalid_api_key",
+            "missing_api_key"
+        ]
+    );
+
+    let guessed = read_audit_log(
+        &log_dir,
+        5,
+        &AuditLogQuery {
+            key_id: Some(jan_utils::api_key_id("guess")),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(guessed.len(), 1);
+    assert_eq!(guessed[0].status, 401);
+
+    let limited = read_audit_log(
+        &log_dir,
+        5,
+        &AuditLogQuery {
+            since: Some(all[4].timestamp.clone()),
+            path_prefix: Some("/models".to_string()),
+            limit: Some(2),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(limited.len(), 2);
+    assert_eq!(limited[0].host.as_deref(), Some("evil.example"));
+    assert!(read_audit_log(
+        &log_dir,
+        5,
+        &AuditLogQuery {
+            since: Some("yesterday".to_string()),
+            ..Default::default()
+        }
+    )
+    .is_err());
+
+    fs::remove_dir_all(&data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_audit_log_rotates() {
+    let dir = temp_log_dir("audit_rotation");
+    let options = AuditLogOptions {
+        enabled: true,
+        max_file_bytes: 300,
+        max_files: 3,
+    };
+    let logger = AuditLogger::new(&dir, &options);
+    for i in 0..12 {
+        logger
+            .append(&AuditLogEntry {
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                remote_addr: "127.0.0.1:5000".to_string(),
+                method: "GET".to_string(),
+                path: format!("/models/{}", i),
+                status: 200,
+                ..Default::default()
+            })
+            .await;
+    }
+
+    let files = (0..4)
+        .map(|index| {
+            let stem = if index == 0 {
+                "proxy_audit.jsonl".to_string()
+            } else {
+                format!("proxy_audit.{}.jsonl", index)
+            };
+            dir.join(stem)
+        })
+        .collect::<Vec<_>>();
+    assert!(files[2].exists());
+    assert!(!files[3].exists());
+    assert!(files[..3]
+        .iter()
+        .all(|path| fs::metadata(path).unwrap().len() <= 300));
+
+    // The oldest entries were dropped with the oldest file; the rest are read across files
+    let entries = read_audit_log(&dir, 3, &AuditLogQuery::default()).unwrap();
+    assert!(entries.len() < 12);
+    assert_eq!(entries[0].path, "/models/11");
+    let paths: Vec<usize> = entries
+        .iter()
+        .map(|e| e.path.trim_start_matches("/models/").parse().unwrap())
+        .collect();
+    assert!(paths.windows(2).all(|pair| pair[0] == pair[1] + 1));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_openapi_document_and_landing_page() {
+    let server_handle = Arc::new(tokio::sync::Mutex::new(None));
+    let sessions = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
+    let data_dir = temp_log_dir("openapi");
+
+    proxy::start_server(
+        server_handle.clone(),
+        sessions,
+        "127.0.0.1".to_string(),
+        0,
+        "/v1".to_string(),
+        "secret".to_string(),
+        vec!["127.0.0.1".to_string()],
+        ProxyServerOptions::default(),
+        data_dir.clone(),
+        None,
+    )
+    .await
+    .unwrap();
+    let address = proxy::get_server_status(server_handle.clone())
+        .await
+        .address
+        .unwrap();
+
+    // Neither document needs the API key
+    let client = reqwest::Client::new();
+    let spec: serde_json::Value = client
+        .get(format!("{}/v1/openapi.json", address))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(spec["openapi"], "3.0.3");
+    assert_eq!(spec["servers"][0]["url"], "/v1");
+    assert_eq!(spec["security"], json!([{ "bearerAuth": [] }]));
+    assert!(spec["paths"]["/chat/completions"]["post"]["requestBody"].is_object());
+    assert!(spec["paths"]["/models"]["get"]["responses"]["401"].is_object());
+    assert!(spec["paths"].get("/vector_stores").is_none());
+
+    let landing: serde_json::Value = client
+        .get(format!("{}/v1", address))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(landing["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(landing["models"], json!([]));
+    assert_eq!(
+        landing["auth"],
+        json!({ "required": true, "scheme": "bearer" })
+    );
+    assert_eq!(landing["openapi_url"], "/v1/openapi.json");
+
+    proxy::update_server_config(
+        server_handle.clone(),
+        ServerConfigUpdate {
+            options: Some(ProxyServerOptions {
+                vector_stores: VectorStoreOptions { enabled: true },
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let spec: serde_json::Value = client
+        .get(format!("{}/v1/openapi.json", address))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(spec["paths"]["/vector_stores/{id}/query"]["post"].is_object());
+    assert!(spec["components"]["schemas"]["UpsertVectorsRequest"].is_object());
+
+    proxy::stop_server(server_handle, Duration::from_secs(1))
+        .await
+        .unwrap();
+    fs::remove_dir_all(&data_dir).unwrap();
+}
+
+/// Fake llama-server whose `/health` reports loading while `loading` is set
+async fn spawn_fake_health_model(loading: Arc<std::sync::atomic::AtomicBool>) -> u16 {
+    use hyper::service::{make_service_fn, service_fn};
+    use std::sync::atomic::Ordering;
+
+    let make_svc = make_service_fn(move |_| {
+        let loading = loading.clone();
+        async move {
+            Ok::<_, std::convert::Infallible>(service_fn(move |_req| {
+                let status = if loading.load(Ordering::SeqCst) {
+                    503
+                } else {
+                    200
+                };
+                async move {
+                    Ok::<_, std::convert::Infallible>(
+                        hyper::Response::builder()
+                            .status(status)
+                            .body(hyper::Body::from("{}"))
+                            .unwrap(),
+                    )
+                }
+            }))
+        }
+    });
+    let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+    let port = server.local_addr().port();
+    tokio::spawn(server);
+    port
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_socket_replaces_only_stale_sockets() {
+    use super::listener::bind_unix_socket;
+
+    let dir = temp_log_dir("unix-socket");
+    let path = dir.join("proxy.sock");
+
+    let listener = bind_unix_socket(&path).unwrap();
+    drop(listener);
+    // The socket file left behind is taken over
+    assert!(bind_unix_socket(&path).is_ok());
+
+    let file = dir.join("notes.txt");
+    fs::write(&file, "keep me").unwrap();
+    let err = bind_unix_socket(&file).unwrap_err();
+    assert!(err.contains("not a socket"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "keep me");
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_session_health_reports_degraded_and_exited_sessions() {
+    use super::health::SessionHealth;
+    use super::models::SessionHealthState;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tauri_plugin_llamacpp::state::SessionInfo;
+    use tauri_plugin_llamacpp::LLamaBackendSession;
+
+    let loading = Arc::new(AtomicBool::new(false));
+    let port = spawn_fake_health_model(loading.clone()).await;
+    let child = tokio::process::Command::new("sleep")
+        .arg("60")
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+    let pid = child.id().unwrap() as i32;
+    let sessions = Arc::new(tokio::sync::Mutex::new(HashMap::from([(
+        pid,
+        LLamaBackendSession {
+            child,
+            info: SessionInfo {
+                pid,
+                port: port as i32,
+                model_id: "qwen".to_string(),
+                model_path: "/models/qwen.gguf".to_string(),
+                api_key: "session-key".to_string(),
+                mmproj_path: None,
+            },
+        },
+    )])));
+
+    let health = SessionHealth::new();
+    let mut events = health.subscribe();
+    let client = reqwest::Client::new();
+    let timeout = Duration::from_secs(1);
+
+    // Healthy on first sight is not announced
+    health.check_sessions(&client, &sessions, timeout).await;
+    assert!(events.try_recv().is_err());
+
+    loading.store(true, Ordering::SeqCst);
+    health.check_sessions(&client, &sessions, timeout).await;
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.state, SessionHealthState::Loading);
+    assert_eq!(event.previous, Some(SessionHealthState::Healthy));
+    health.check_sessions(&client, &sessions, timeout).await;
+    assert!(events.try_recv().is_err());
+
+    // A crashed llama-server makes the proxy drop the session instead of failing with 502
+    let server_handle = Arc::new(tokio::sync::Mutex::new(None));
+    let data_dir = temp_log_dir("health");
+    proxy::start_server(
+        server_handle.clone(),
+        sessions.clone(),
+        "127.0.0.1".to_string(),
+        0,
+        "/v1".to_string(),
+        String::new(),
+        vec!["127.0.0.1".to_string()],
+        ProxyServerOptions::default(),
+        data_dir.clone(),
+        None,
+    )
+    .await
+    .unwrap();
+    let address = proxy::get_server_status(server_handle.clone())
+        .await
+        .address
+        .unwrap();
+    let mut server_events = proxy::subscribe_session_health(server_handle.clone())
+        .await
+        .unwrap();
+
+    {
+        let mut sessions = sessions.lock().await;
+        let child = &mut sessions.get_mut(&pid).unwrap().child;
+        child.kill().await.unwrap();
+    }
+    let response = client
+        .post(format!("{}/v1/embeddings", address))
+        .json(&json!({ "model": "qwen", "input": "hello" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+    assert!(sessions.lock().await.is_empty());
+
+    let event = server_events.try_recv().unwrap();
+    assert_eq!(event.pid, pid);
+    assert_eq!(event.model_id, "qwen");
+    assert_eq!(event.state, SessionHealthState::Exited);
+    assert!(event.detail.unwrap().contains("exited"));
+
+    proxy::stop_server(server_handle, Duration::from_secs(1))
+        .await
+        .unwrap();
+    fs::remove_dir_all(&data_dir).unwrap();
+}
+
+const TINY_PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+/// Serves `TINY_PNG` at `/avatar.png` and HTML everywhere else
+async fn spawn_fake_image_host() -> String {
+    use hyper::service::{make_service_fn, service_fn};
+
+    let make_svc = make_service_fn(|_| async {
+        Ok::<_, std::convert::Infallible>(service_fn(
+            |req: hyper::Request<hyper::Body>| async move {
+                let response = if req.uri().path() == "/avatar.png" {
+                    hyper::Response::builder()
+                        .header("Content-Type", "image/png")
+                        .body(hyper::Body::from(TINY_PNG))
+                } else {
+                    hyper::Response::builder()
+                        .header("Content-Type", "text/html")
+                        .body(hyper::Body::from("<html></html>"))
+                };
+                Ok::<_, std::convert::Infallible>(response.unwrap())
+            },
+        ))
+    });
+    let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+    let url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+    url
+}
+
+#[tokio::test]
+async fn test_inline_images_as_data_uris() {
+    use super::models::MultimodalOptions;
+    use super::multimodal::{has_image_content, inline_images};
+
+    let host = spawn_fake_image_host().await;
+    let dir = temp_log_dir("images");
+    let screenshot = dir.join("profile.png");
+    fs::write(&screenshot, TINY_PNG).unwrap();
+    let notes = dir.join("notes.txt");
+    fs::write(&notes, "not an image").unwrap();
+
+    let image = |url: String| json!({ "type": "image_url", "image_url": { "url": url } });
+    let mut body = json!({
+        "model": "qwen-vl",
+        "messages": [{
+            "role": "user",
+            "content": [
+                { "type": "text", "text": "Summarize this profile" },
+                image(format!("{}/avatar.png", host)),
+                image(screenshot.display().to_string()),
+                image("data:image/png;base64,AAAA".to_string()),
+            ]
+        }]
+    });
+    assert!(has_image_content(&body));
+    assert!(!has_image_content(
+        &json!({ "messages": [{ "role": "user", "content": "hi" }] })
+    ));
+
+    // The fake host listens on loopback
+    let options = MultimodalOptions {
+        inline_images: true,
+        allow_local_files: true,
+        local_image_dirs: vec![dir.display().to_string()],
+        allow_private_hosts: true,
+        ..Default::default()
+    };
+    assert_eq!(inline_images(&mut body, &options).await.unwrap(), 2);
+    let expected = format!(
+        "data:image/png;base64,{}",
+        base64::Engine::encode(&base64::engine::general_purpose::STANDARD, TINY_PNG)
+    );
+    let content = &body["messages"][0]["content"];
+    assert_eq!(content[1]["image_url"]["url"], expected);
+    assert_eq!(content[2]["image_url"]["url"], expected);
+    assert_eq!(content[3]["image_url"]["url"], "data:image/png;base64,AAAA");
+
+    for (url, options) in [
+        (format!("{}/profile", host), options.clone()),
+        (notes.display().to_string(), options.clone()),
+        ("relative/profile.png".to_string(), options.clone()),
+        (
+            screenshot.display().to_string(),
+            MultimodalOptions {
+                allow_local_files: false,
+                ..options.clone()
+            },
+        ),
+        (
+            format!("{}/avatar.png", host),
+            MultimodalOptions {
+                max_image_bytes: 4,
+                ..options.clone()
+            },
+        ),
+    ] {
+        let mut body = json!({ "messages": [{ "role": "user", "content": [image(url.clone())] }] });
+        assert!(
+            inline_images(&mut body, &options).await.is_err(),
+            "{} should be rejected",
+            url
+        );
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_inline_images_rejects_private_hosts_and_outside_paths() {
+    use super::models::MultimodalOptions;
+    use super::multimodal::{inline_images, is_public_ip};
+
+    let host = spawn_fake_image_host().await;
+    let allowed = temp_log_dir("images-allowed");
+    let outside = temp_log_dir("images-outside");
+    let secret = outside.join("secret.png");
+    fs::write(&secret, TINY_PNG).unwrap();
+    fs::write(allowed.join("ok.png"), TINY_PNG).unwrap();
+
+    let options = MultimodalOptions {
+        inline_images: true,
+        allow_local_files: true,
+        local_image_dirs: vec![allowed.display().to_string()],
+        ..Default::default()
+    };
+    let mut rejected = vec![
+        // Loopback, link-local and private hosts
+        (format!("{}/avatar.png", host), options.clone()),
+        (
+            "http://169.254.169.254/latest/meta-data".to_string(),
+            options.clone(),
+        ),
+        ("http://[::1]/avatar.png".to_string(), options.clone()),
+        ("http://10.0.0.8/avatar.png".to_string(), options.clone()),
+        // Paths outside the allowed directory, directly or through `..`
+        (secret.display().to_string(), options.clone()),
+        (format!("file://{}", secret.display()), options.clone()),
+        (
+            allowed
+                .join("..")
+                .join(outside.file_name().unwrap())
+                .join("secret.png")
+                .display()
+                .to_string(),
+            options.clone(),
+        ),
+        // Local files are off by default and need an allowed directory
+        (
+            allowed.join("ok.png").display().to_string(),
+            MultimodalOptions::default(),
+        ),
+        (
+            allowed.join("ok.png").display().to_string(),
+            MultimodalOptions {
+                local_image_dirs: Vec::new(),
+                ..options.clone()
+            },
+        ),
+    ];
+    #[cfg(unix)]
+    {
+        let link = allowed.join("link.png");
+        std::os::unix::fs::symlink(&secret, &link).unwrap();
+        rejected.push((link.display().to_string(), options.clone()));
+    }
+
+    for (url, options) in rejected {
+        let mut body = json!({ "messages": [{ "role": "user", "content": [
+            { "type": "image_url", "image_url": { "url": url.clone() } }
+        ] }] });
+        assert!(
+            inline_images(&mut body, &options).await.is_err(),
+            "{} should be rejected",
+            url
+        );
+    }
+
+    let mut body = json!({ "messages": [{ "role": "user", "content": [
+        { "type": "image_url", "image_url": { "url": allowed.join("ok.png").display().to_string() } }
+    ] }] });
+    assert_eq!(inline_images(&mut body, &options).await.unwrap(), 1);
+
+    for ip in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "192.168.1.1",
+        "172.16.0.1",
+        "169.254.1.1",
+        "100.64.0.1",
+        "0.0.0.0",
+        "::1",
+        "fe80::1",
+        "fd00::1",
+        "::ffff:127.0.0.1",
+    ] {
+        assert!(!is_public_ip(ip.parse().unwrap()), "{} is not public", ip);
+    }
+    for ip in ["93.184.216.34", "2606:4700::1111"] {
+        assert!(is_public_ip(ip.parse().unwrap()), "{} is public", ip);
+    }
+
+    fs::remove_dir_all(&allowed).unwrap();
+    fs::remove_dir_all(&outside).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_image_request_rejected_without_mmproj() {
+    use tauri_plugin_llamacpp::state::SessionInfo;
+    use tauri_plugin_llamacpp::LLamaBackendSession;
+
+    let child = tokio::process::Command::new("sleep")
+        .arg("60")
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+    let pid = child.id().unwrap() as i32;
+    let sessions = Arc::new(tokio::sync::Mutex::new(HashMap::from([(
+        pid,
+        LLamaBackendSession {
+            child,
+            info: SessionInfo {
+                pid,
+                port: 1,
+                model_id: "qwen".to_string(),
+                model_path: "/models/qwen.gguf".to_string(),
+                api_key: String::new(),
+                mmproj_path: None,
+            },
+        },
+    )])));
+
+    let server_handle = Arc::new(tokio::sync::Mutex::new(None));
+    let data_dir = temp_log_dir("vision");
+    proxy::start_server(
+        server_handle.clone(),
+        sessions,
+        "127.0.0.1".to_string(),
+        0,
+        "/v1".to_string(),
+        String::new(),
+        vec!["127.0.0.1".to_string()],
+        ProxyServerOptions::default(),
+        data_dir.clone(),
+        None,
+    )
+    .await
+    .unwrap();
+    let address = proxy::get_server_status(server_handle.clone())
+        .await
+        .address
+        .unwrap();
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", address))
+        .json(&json!({
+            "model": "qwen",
+            "messages": [{ "role": "user", "content": [
+                { "type": "image_url", "image_url": { "url": "https://example.com/a.png" } }
+            ] }]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["error"]["code"], "image_input_not_supported");
+    assert!(error["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("mmproj"));
+
+    proxy::stop_server(server_handle, Duration::from_secs(1))
+        .await
+        .unwrap();
+    fs::remove_dir_all(&data_dir).unwrap();
+}
@@ -0,0 +1,219 @@
+use futures_util::future::BoxFuture;
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+
+/// A tool the proxy can offer to models that did not bring their own
+#[derive(Debug, Clone)]
+pub struct ProxyTool {
+    pub name: String,
+    pub description: Option<String>,
+    pub parameters: Value,
+}
+
+/// Source of tools for the proxy's tool-calling mode, implemented by the MCP integration
+pub trait ToolProvider: Send + Sync {
+    fn list_tools(&self) -> BoxFuture<'_, Result<Vec<ProxyTool>, String>>;
+
+    /// Runs a tool and returns its output as the text sent back to the model
+    fn call_tool(
+        &self,
+        name: String,
+        arguments: Option<Map<String, Value>>,
+    ) -> BoxFuture<'_, Result<String, String>>;
+}
+
+/// Converts tools to the OpenAI `tools` request field
+pub fn openai_tools(tools: &[ProxyTool]) -> Value {
+    Value::Array(
+        tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description.clone().unwrap_or_default(),
+                        "parameters": tool.parameters,
+                    }
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Result of a completed tool loop
+#[derive(Debug)]
+pub struct ToolLoopOutcome {
+    /// The final non-streaming completion from the model
+    pub response: Value,
+    pub iterations: usize,
+    pub tool_calls: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// Sends the request with the provider's tools and executes every tool call the
+/// model makes, feeding results back until it answers without calling a tool
+pub async fn run_tool_loop(
+    client: &Client,
+    upstream_url: &str,
+    session_api_key: Option<&str>,
+    mut body: Value,
+    provider: &dyn ToolProvider,
+    max_iterations: usize,
+) -> Result<ToolLoopOutcome, String> {
+    let tools = provider.list_tools().await?;
+    log::debug!("Injecting {} tools into chat completion", tools.len());
+
+    body["stream"] = Value::Bool(false);
+    if let Some(object) = body.as_object_mut() {
+        object.remove("stream_options");
+    }
+    if !tools.is_empty() {
+        body["tools"] = openai_tools(&tools);
+        body["tool_choice"] = Value::String("auto".to_string());
+    }
+
+    let mut tool_calls = 0;
+    let mut prompt_tokens = 0;
+    let mut completion_tokens = 0;
+
+    for iteration in 1..=max_iterations.max(1) {
+        let mut request = client.post(upstream_url).json(&body);
+        if let Some(key) = session_api_key {
+            request = request.header("Authorization", format!("Bearer {}", key));
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Proxy request to model failed: {}", e))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read model response: {}", e))?;
+        if !status.is_success() {
+            return Err(format!("Model returned {}: {}", status, text));
+        }
+        let completion: Value =
+            serde_json::from_str(&text).map_err(|e| format!("Invalid model response: {}", e))?;
+
+        if let Some(usage) = completion.get("usage") {
+            prompt_tokens += usage["prompt_tokens"].as_u64().unwrap_or(0);
+            completion_tokens += usage["completion_tokens"].as_u64().unwrap_or(0);
+        }
+
+        let message = completion["choices"][0]["message"].clone();
+        let calls = message["tool_calls"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        if calls.is_empty() {
+            return Ok(ToolLoopOutcome {
+                response: completion,
+                iterations: iteration,
+                tool_calls,
+                prompt_tokens,
+                completion_tokens,
+            });
+        }
+
+        let Some(messages) = body["messages"].as_array_mut() else {
+            return Err("Request body must contain a 'messages' array".to_string());
+        };
+        messages.push(message);
+
+        for call in calls {
+            let id = call["id"].as_str().unwrap_or_default().to_string();
+            let name = call["function"]["name"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let content = match parse_tool_arguments(&call["function"]["arguments"]) {
+                Ok(arguments) => {
+                    log::info!("Proxy tool loop calling tool '{}'", name);
+                    tool_calls += 1;
+                    match provider.call_tool(name.clone(), arguments).await {
+                        Ok(output) => output,
+                        Err(e) => {
+                            log::warn!("Tool '{}' failed in proxy tool loop: {}", name, e);
+                            format!("Error: {}", e)
+                        }
+                    }
+                }
+                // Tell the model instead of running the tool with default arguments
+                Err(e) => {
+                    log::warn!("Model sent invalid arguments for tool '{}': {}", name, e);
+                    format!("Error: invalid arguments for tool '{}': {}", name, e)
+                }
+            };
+            messages.push(json!({
+                "role": "tool",
+                "tool_call_id": id,
+                "name": name,
+                "content": content,
+            }));
+        }
+    }
+
+    Err(format!(
+        "Model did not produce a final answer within {} tool iterations",
+        max_iterations
+    ))
+}
+
+/// Reads `function.arguments`, which is a JSON-encoded object string in the
+/// OpenAI format but may also arrive as an object. Missing or empty means no arguments.
+fn parse_tool_arguments(raw: &Value) -> Result<Option<Map<String, Value>>, String> {
+    match raw {
+        Value::Null => Ok(None),
+        Value::String(text) if text.trim().is_empty() => Ok(None),
+        Value::String(text) => serde_json::from_str::<Map<String, Value>>(text)
+            .map(Some)
+            .map_err(|e| e.to_string()),
+        Value::Object(map) => Ok(Some(map.clone())),
+        other => Err(format!("expected a JSON object, got {}", other)),
+    }
+}
+
+/// Renders a non-streaming chat completion as the equivalent SSE stream
+pub fn completion_to_sse(completion: &Value) -> String {
+    let id = completion["id"].clone();
+    let created = completion["created"].clone();
+    let model = completion["model"].clone();
+    let message = &completion["choices"][0]["message"];
+    let finish_reason = completion["choices"][0]["finish_reason"].clone();
+
+    let chunk = |delta: Value, finish_reason: Value| {
+        json!({
+            "id": id,
+            "object": "chat.completion.chunk",
+            "created": created,
+            "model": model,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+        })
+    };
+
+    let mut delta = json!({ "role": "assistant" });
+    if let Some(content) = message.get("content").filter(|c| !c.is_null()) {
+        delta["content"] = content.clone();
+    }
+    if let Some(reasoning) = message.get("reasoning_content").filter(|r| !r.is_null()) {
+        delta["reasoning_content"] = reasoning.clone();
+    }
+
+    let mut last = chunk(json!({}), finish_reason);
+    if let Some(usage) = completion.get("usage") {
+        last["usage"] = usage.clone();
+    }
+
+    let mut out = String::new();
+    for event in [chunk(delta, Value::Null), last] {
+        out.push_str("data: ");
+        out.push_str(&event.to_string());
+        out.push_str("\n\n");
+    }
+    out.push_str("data: [DONE]\n\n");
+    out
+}
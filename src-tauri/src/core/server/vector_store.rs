@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures_util::future::BoxFuture;
+use hyper::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::sync::Mutex;
+
+use super::constants::PROXY_VECTOR_STORE_DEFAULT_TOP_K;
+
+/// Turns text into embedding vectors, implemented by the proxy using the running sessions
+pub trait TextEmbedder: Send + Sync {
+    fn embed(
+        &self,
+        model: String,
+        texts: Vec<String>,
+    ) -> BoxFuture<'_, Result<Vec<Vec<f32>>, String>>;
+}
+
+/// A stored vector with the text and metadata it was created from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorRecord {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+    pub embedding: Vec<f32>,
+}
+
+/// A named collection of vectors, persisted as one JSON file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorStore {
+    pub id: String,
+    pub name: String,
+    /// Unix timestamp in seconds
+    pub created_at: u64,
+    /// Embedding model used for records and queries given as text
+    pub model: Option<String>,
+    /// Set by the first upsert; every later vector must match it
+    pub dimensions: Option<usize>,
+    pub records: Vec<VectorRecord>,
+}
+
+impl VectorStore {
+    fn summary(&self) -> Value {
+        json!({
+            "id": self.id,
+            "object": "vector_store",
+            "name": self.name,
+            "created_at": self.created_at,
+            "model": self.model,
+            "dimensions": self.dimensions,
+            "record_count": self.records.len(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateStoreRequest {
+    name: Option<String>,
+    model: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpsertRecord {
+    id: String,
+    text: Option<String>,
+    #[serde(default)]
+    metadata: Map<String, Value>,
+    embedding: Option<Vec<f32>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpsertRequest {
+    records: Vec<UpsertRecord>,
+    model: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryRequest {
+    query: Option<String>,
+    embedding: Option<Vec<f32>>,
+    top_k: Option<usize>,
+    /// Records must have these exact metadata values
+    #[serde(default)]
+    filter: Map<String, Value>,
+    model: Option<String>,
+}
+
+type ApiResult = Result<(StatusCode, Value), (StatusCode, String)>;
+
+fn bad_request(message: impl Into<String>) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, message.into())
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, (StatusCode, String)> {
+    let body = if body.is_empty() {
+        b"{}".as_slice()
+    } else {
+        body
+    };
+    serde_json::from_slice(body).map_err(|e| bad_request(format!("Invalid request body: {}", e)))
+}
+
+/// Cosine similarity, or 0 when either vector has no magnitude
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// Local vector collections for similarity search, kept in memory and written
+/// through to `<data folder>/vector_stores`
+pub struct VectorStoreManager {
+    dir: PathBuf,
+    /// Loaded from disk on first use
+    stores: Mutex<Option<HashMap<String, VectorStore>>>,
+}
+
+impl VectorStoreManager {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            stores: Mutex::new(None),
+        }
+    }
+
+    async fn load(&self) -> HashMap<String, VectorStore> {
+        let mut stores = HashMap::new();
+        let Ok(mut entries) = tokio::fs::read_dir(&self.dir).await else {
+            return stores;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match tokio::fs::read_to_string(&path)
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|content| {
+                    serde_json::from_str::<VectorStore>(&content).map_err(|e| e.to_string())
+                }) {
+                Ok(store) => {
+                    stores.insert(store.id.clone(), store);
+                }
+                Err(e) => log::warn!("Skipping vector store {}: {}", path.display(), e),
+            }
+        }
+        stores
+    }
+
+    async fn save(&self, store: &VectorStore) -> Result<(), String> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| e.to_string())?;
+        let content = serde_json::to_string(store).map_err(|e| e.to_string())?;
+        let path = self.dir.join(format!("{}.json", store.id));
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, content)
+            .await
+            .map_err(|e| e.to_string())?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Runs `f` on the loaded stores, loading them from disk on first use
+    async fn with_stores<T>(&self, f: impl FnOnce(&mut HashMap<String, VectorStore>) -> T) -> T {
+        let mut guard = self.stores.lock().await;
+        if guard.is_none() {
+            *guard = Some(self.load().await);
+        }
+        f(guard.as_mut().unwrap())
+    }
+
+    /// Serves a `/vector_stores` API request, returning the status and JSON body
+    pub async fn handle_request(
+        &self,
+        method: &Method,
+        path: &str,
+        body: &[u8],
+        embedder: &dyn TextEmbedder,
+    ) -> (StatusCode, Value) {
+        let segments: Vec<&str> = path
+            .trim_start_matches("/vector_stores")
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let result = match (method, segments.as_slice()) {
+            (&Method::GET, []) => self.list().await,
+            (&Method::POST, []) => self.create(body).await,
+            (&Method::GET, [id]) => self.get(id).await,
+            (&Method::DELETE, [id]) => self.delete(id).await,
+            (&Method::POST, [id, "upsert"]) => self.upsert(id, body, embedder).await,
+            (&Method::POST, [id, "query"]) => self.query(id, body, embedder).await,
+            _ => Err((StatusCode::NOT_FOUND, "Not Found".to_string())),
+        };
+
+        match result {
+            Ok(response) => response,
+            Err((status, message)) => (status, json!({ "error": { "message": message } })),
+        }
+    }
+
+    async fn list(&self) -> ApiResult {
+        let mut summaries: Vec<(u64, Value)> = self
+            .with_stores(|stores| {
+                stores
+                    .values()
+                    .map(|store| (store.created_at, store.summary()))
+                    .collect()
+            })
+            .await;
+        summaries.sort_by_key(|(created_at, _)| *created_at);
+        let data: Vec<Value> = summaries.into_iter().map(|(_, s)| s).collect();
+        Ok((StatusCode::OK, json!({ "object": "list", "data": data })))
+    }
+
+    async fn create(&self, body: &[u8]) -> ApiResult {
+        let request: CreateStoreRequest = parse_body(body)?;
+        let store = VectorStore {
+            id: format!("vs_{}", uuid::Uuid::new_v4().simple()),
+            name: request.name.unwrap_or_default(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            model: request.model,
+            dimensions: None,
+            records: Vec::new(),
+        };
+        self.save(&store)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        let summary = store.summary();
+        self.with_stores(|stores| stores.insert(store.id.clone(), store))
+            .await;
+        Ok((StatusCode::OK, summary))
+    }
+
+    async fn get(&self, id: &str) -> ApiResult {
+        self.with_stores(|stores| stores.get(id).map(|store| store.summary()))
+            .await
+            .map(|summary| (StatusCode::OK, summary))
+            .ok_or_else(|| not_found(id))
+    }
+
+    async fn delete(&self, id: &str) -> ApiResult {
+        if self.with_stores(|stores| stores.remove(id)).await.is_none() {
+            return Err(not_found(id));
+        }
+        let path = self.dir.join(format!("{}.json", id));
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            log::warn!(
+                "Failed to remove vector store file {}: {}",
+                path.display(),
+                e
+            );
+        }
+        Ok((
+            StatusCode::OK,
+            json!({ "id": id, "object": "vector_store.deleted", "deleted": true }),
+        ))
+    }
+
+    /// The embedding model for text in a request: the store's own, else the request's
+    async fn embedding_model(
+        &self,
+        id: &str,
+        requested: Option<String>,
+    ) -> Result<Option<String>, (StatusCode, String)> {
+        self.with_stores(|stores| stores.get(id).map(|store| store.model.clone()))
+            .await
+            .map(|model| model.or(requested))
+            .ok_or_else(|| not_found(id))
+    }
+
+    async fn upsert(&self, id: &str, body: &[u8], embedder: &dyn TextEmbedder) -> ApiResult {
+        let request: UpsertRequest = parse_body(body)?;
+        let model = self.embedding_model(id, request.model).await?;
+
+        // Embed the records that only brought text, outside the store lock
+        let texts: Vec<String> = request
+            .records
+            .iter()
+            .filter(|record| record.embedding.is_none())
+            .map(|record| {
+                record.text.clone().ok_or_else(|| {
+                    bad_request(format!(
+                        "Record '{}' needs 'text' or 'embedding'",
+                        record.id
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let mut computed = if texts.is_empty() {
+            Vec::new()
+        } else {
+            let model = model.ok_or_else(|| {
+                bad_request(
+                    "Vector store has no embedding model; pass 'model' or precomputed embeddings",
+                )
+            })?;
+            embedder
+                .embed(model, texts)
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, e))?
+        }
+        .into_iter();
+
+        let records: Vec<VectorRecord> = request
+            .records
+            .into_iter()
+            .map(|record| VectorRecord {
+                embedding: record
+                    .embedding
+                    .unwrap_or_else(|| computed.next().unwrap_or_default()),
+                id: record.id,
+                text: record.text,
+                metadata: record.metadata,
+            })
+            .collect();
+
+        let mut guard = self.stores.lock().await;
+        let store = guard
+            .as_mut()
+            .and_then(|stores| stores.get_mut(id))
+            .ok_or_else(|| not_found(id))?;
+        let dimensions = store
+            .dimensions
+            .or_else(|| records.first().map(|r| r.embedding.len()));
+        if let Some(record) = records
+            .iter()
+            .find(|r| Some(r.embedding.len()) != dimensions || r.embedding.is_empty())
+        {
+            return Err(bad_request(format!(
+                "Record '{}' has {} dimensions, expected {}",
+                record.id,
+                record.embedding.len(),
+                dimensions.unwrap_or_default()
+            )));
+        }
+
+        let upserted = records.len();
+        store.dimensions = dimensions;
+        for record in records {
+            match store.records.iter_mut().find(|r| r.id == record.id) {
+                Some(existing) => *existing = record,
+                None => store.records.push(record),
+            }
+        }
+        let store = store.clone();
+        drop(guard);
+
+        self.save(&store)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        Ok((
+            StatusCode::OK,
+            json!({ "upserted": upserted, "record_count": store.records.len() }),
+        ))
+    }
+
+    async fn query(&self, id: &str, body: &[u8], embedder: &dyn TextEmbedder) -> ApiResult {
+        let request: QueryRequest = parse_body(body)?;
+        let model = self.embedding_model(id, request.model).await?;
+
+        let query = match (request.embedding, request.query) {
+            (Some(embedding), _) => embedding,
+            (None, Some(text)) => {
+                let model = model.ok_or_else(|| {
+                    bad_request("Vector store has no embedding model; pass 'model' or 'embedding'")
+                })?;
+                embedder
+                    .embed(model, vec![text])
+                    .await
+                    .map_err(|e| (StatusCode::BAD_GATEWAY, e))?
+                    .into_iter()
+                    .next()
+                    .unwrap_or_default()
+            }
+            (None, None) => return Err(bad_request("Query needs 'query' text or an 'embedding'")),
+        };
+        let top_k = request.top_k.unwrap_or(PROXY_VECTOR_STORE_DEFAULT_TOP_K);
+
+        let matches = self
+            .with_stores(|stores| {
+                let store = stores.get(id).ok_or_else(|| not_found(id))?;
+                if store.dimensions.is_some_and(|d| d != query.len()) {
+                    return Err(bad_request(format!(
+                        "Query has {} dimensions, expected {}",
+                        query.len(),
+                        store.dimensions.unwrap_or_default()
+                    )));
+                }
+                let mut scored: Vec<(f32, &VectorRecord)> = store
+                    .records
+                    .iter()
+                    .filter(|record| {
+                        request
+                            .filter
+                            .iter()
+                            .all(|(key, value)| record.metadata.get(key) == Some(value))
+                    })
+                    .map(|record| (cosine_similarity(&query, &record.embedding), record))
+                    .collect();
+                scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+                Ok(scored
+                    .into_iter()
+                    .take(top_k)
+                    .map(|(score, record)| {
+                        json!({
+                            "id": record.id,
+                            "score": score,
+                            "text": record.text,
+                            "metadata": record.metadata,
+                        })
+                    })
+                    .collect::<Vec<_>>())
+            })
+            .await?;
+
+        Ok((StatusCode::OK, json!({ "object": "list", "data": matches })))
+    }
+}
+
+fn not_found(id: &str) -> (StatusCode, String) {
+    (
+        StatusCode::NOT_FOUND,
+        format!("Vector store '{}' not found", id),
+    )
+}
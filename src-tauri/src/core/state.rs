@@ -1,24 +1,35 @@
 use std::{collections::HashMap, sync::Arc};
 
-use crate::core::downloads::models::DownloadManagerState;
+use crate::core::{
+    downloads::models::DownloadManagerState,
+    mcp::{
+        approval::PendingApprovals,
+        call_log::ToolCallLog,
+        client::{InFlightToolCalls, McpClientHandler},
+        status::ServerHealthMap,
+    },
+};
 use rmcp::{
-    model::{CallToolRequestParam, CallToolResult, InitializeRequestParam, Tool},
-    service::RunningService,
+    model::{
+        CallToolRequest, CallToolRequestParam, CallToolResult, ClientRequest, Meta, NumberOrString,
+        ProgressToken, ServerResult, Tool,
+    },
+    service::{Peer, PeerRequestOptions, RequestHandle, RunningService},
     RoleClient, ServiceError,
 };
 use tokio::sync::{Mutex, oneshot};
-use tokio::task::JoinHandle;
 
 /// Server handle type for managing the proxy server lifecycle
-pub type ServerHandle = JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>>;
+pub type ServerHandle = crate::core::server::proxy::RunningServer;
 
+/// A connected MCP server; stdio servers skip the client info handshake details
 pub enum RunningServiceEnum {
-    NoInit(RunningService<RoleClient, ()>),
-    WithInit(RunningService<RoleClient, InitializeRequestParam>),
+    NoInit(RunningService<RoleClient, McpClientHandler>),
+    WithInit(RunningService<RoleClient, McpClientHandler>),
 }
 pub type SharedMcpServers = Arc<Mutex<HashMap<String, RunningServiceEnum>>>;
 
-/// Tool list cached permanently per server (fetched once on startup, never expires)
+/// Tool list cached per server, refreshed on connect, on `tools/list_changed` and on demand
 pub type ToolCache = Arc<Mutex<HashMap<String, Vec<Tool>>>>;
 
 #[derive(Default)]
@@ -31,8 +42,16 @@ pub struct AppState {
     pub mcp_successfully_connected: Arc<Mutex<HashMap<String, bool>>>,
     pub server_handle: Arc<Mutex<Option<ServerHandle>>>,
     pub tool_call_cancellations: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>,
-    /// Cache of tool lists per server with timestamp for TTL-based expiration (5 min)
+    /// Cache of tool lists per server, see `ToolCache`
     pub mcp_tool_cache: ToolCache,
+    /// Tool calls waiting for the user's approval
+    pub mcp_pending_approvals: PendingApprovals,
+    /// Appends every tool call to `logs/mcp_tool_calls.jsonl`
+    pub mcp_tool_call_log: Arc<ToolCallLog>,
+    /// Tool calls with a cancellation token, for routing their progress notifications
+    pub mcp_tool_calls_in_flight: InFlightToolCalls,
+    /// Connection state, errors and call latency per server, see `get_mcp_server_status`
+    pub mcp_server_health: ServerHealthMap,
 }
 
 impl RunningServiceEnum {
@@ -42,13 +61,44 @@ impl RunningServiceEnum {
             Self::WithInit(s) => s.list_all_tools().await,
         }
     }
-    pub async fn call_tool(
-        &self,
-        params: CallToolRequestParam,
-    ) -> Result<CallToolResult, ServiceError> {
+    /// A handle for sending requests to the server that does not borrow the service, so
+    /// callers can release the server map before awaiting a response
+    pub fn peer(&self) -> Peer<RoleClient> {
         match self {
-            Self::NoInit(s) => s.call_tool(params).await,
-            Self::WithInit(s) => s.call_tool(params).await,
+            Self::NoInit(s) => s.peer().clone(),
+            Self::WithInit(s) => s.peer().clone(),
         }
     }
 }
+
+/// Sends a tool call without waiting for its result. The handle's request id lets the
+/// call be cancelled on the server; with a progress token, the server's progress
+/// notifications can be matched to the call.
+pub async fn send_tool_call(
+    peer: &Peer<RoleClient>,
+    params: CallToolRequestParam,
+    progress_token: Option<&str>,
+) -> Result<RequestHandle<RoleClient>, ServiceError> {
+    let mut options = PeerRequestOptions::no_options();
+    if let Some(token) = progress_token {
+        let mut meta = Meta::new();
+        meta.set_progress_token(ProgressToken(NumberOrString::String(token.into())));
+        options.meta = Some(meta);
+    }
+    let request = ClientRequest::CallToolRequest(CallToolRequest {
+        method: Default::default(),
+        params,
+        extensions: Default::default(),
+    });
+    peer.send_cancellable_request(request, options).await
+}
+
+/// Waits for the result of a call sent with `send_tool_call`
+pub async fn tool_call_result(
+    handle: RequestHandle<RoleClient>,
+) -> Result<CallToolResult, ServiceError> {
+    match handle.await_response().await? {
+        ServerResult::CallToolResult(result) => Ok(result),
+        _ => Err(ServiceError::UnexpectedResponse),
+    }
+}
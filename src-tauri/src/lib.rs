@@ -78,13 +78,30 @@ pub fn run() {
             core::server::commands::start_server,
             core::server::commands::stop_server,
             core::server::commands::get_server_status,
+            core::server::commands::update_server_config,
+            core::server::commands::replay_proxy_request,
+            core::server::commands::get_proxy_audit_log,
             // MCP commands
             core::mcp::commands::get_tools,
+            core::mcp::commands::refresh_mcp_tools,
             core::mcp::commands::call_tool,
             core::mcp::commands::cancel_tool_call,
+            core::mcp::commands::list_tool_calls,
+            core::mcp::commands::list_pending_tool_calls,
+            core::mcp::commands::approve_tool_call,
+            core::mcp::commands::reject_tool_call,
+            core::mcp::commands::get_mcp_tool_policy,
+            core::mcp::commands::save_mcp_tool_policy,
+            core::mcp::commands::list_mcp_resources,
+            core::mcp::commands::read_mcp_resource,
+            core::mcp::commands::subscribe_mcp_resource,
+            core::mcp::commands::unsubscribe_mcp_resource,
+            core::mcp::commands::list_mcp_prompts,
+            core::mcp::commands::get_mcp_prompt,
             core::mcp::commands::restart_mcp_servers,
             // core::mcp::commands::reinitialize_mcp_servers,
             core::mcp::commands::get_connected_servers,
+            core::mcp::commands::get_mcp_server_status,
             core::mcp::commands::save_mcp_configs,
             core::mcp::commands::get_mcp_configs,
             core::mcp::commands::activate_mcp_server,
@@ -116,6 +133,10 @@ pub fn run() {
             server_handle: Arc::new(Mutex::new(None)),
             tool_call_cancellations: Arc::new(Mutex::new(HashMap::new())),
             mcp_tool_cache: Arc::new(Mutex::new(HashMap::new())),
+            mcp_pending_approvals: Arc::new(Mutex::new(HashMap::new())),
+            mcp_tool_call_log: Arc::new(core::mcp::call_log::ToolCallLog::default()),
+            mcp_tool_calls_in_flight: Arc::new(Mutex::new(HashMap::new())),
+            mcp_server_health: Arc::new(Mutex::new(HashMap::new())),
         })
         .setup(|app| {
             app.handle().plugin(
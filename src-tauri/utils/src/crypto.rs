@@ -29,6 +29,12 @@ pub fn generate_api_key(model_id: String, api_secret: String) -> Result<String,
     Ok(hash)
 }
 
+/// Derives a short, non-reversible identifier for an API key so it can be logged safely
+pub fn api_key_id(api_key: &str) -> String {
+    let digest = Sha256::digest(api_key.as_bytes());
+    format!("{:x}", digest)[..12].to_string()
+}
+
 /// Compute SHA256 hash of a file with cancellation support by chunking the file
 pub async fn compute_file_sha256_with_cancellation(
     file_path: &Path,
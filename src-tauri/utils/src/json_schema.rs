@@ -0,0 +1,351 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A single place where a JSON value does not match its schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaViolation {
+    /// Location of the offending value, e.g. `$.contacts[0].email`
+    pub path: String,
+    pub message: String,
+}
+
+/// Resolves a local `$ref` such as `#/$defs/Contact` against the root schema
+pub fn resolve_schema_ref<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    root.pointer(pointer)
+}
+
+/// Name of the JSON Schema type a value belongs to
+pub fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn matches_type(value: &Value, expected: &str) -> bool {
+    match (expected, value) {
+        ("integer", Value::Number(n)) => {
+            n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        ("number", Value::Number(_)) => true,
+        _ => json_type_name(value) == expected,
+    }
+}
+
+/// Validates `value` against a JSON Schema. Supports the commonly used subset:
+/// `type`, `enum`, `const`, `properties`, `required`, `additionalProperties`,
+/// `items`, `minItems`/`maxItems`, `minLength`/`maxLength`, numeric bounds,
+/// `anyOf`/`oneOf`/`allOf` and local `$ref`s. Unknown keywords are ignored.
+pub fn validate_json_schema(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    validate_at(schema, schema, value, "$", &[], &mut violations);
+    violations
+}
+
+fn violation(violations: &mut Vec<SchemaViolation>, path: &str, message: String) {
+    violations.push(SchemaViolation {
+        path: path.to_string(),
+        message,
+    });
+}
+
+/// `refs` are the references already followed for this value, so a schema that refers
+/// back to itself without descending into the value is reported instead of recursing forever
+fn validate_at(
+    root: &Value,
+    schema: &Value,
+    value: &Value,
+    path: &str,
+    refs: &[&str],
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let Some(schema) = schema.as_object() else {
+        // `true` accepts everything, `false` nothing
+        if schema == &Value::Bool(false) {
+            violation(violations, path, "no value is allowed here".to_string());
+        }
+        return;
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(|r| r.as_str()) {
+        if refs.contains(&reference) {
+            violation(
+                violations,
+                path,
+                format!("circular schema reference '{}'", reference),
+            );
+            return;
+        }
+        match resolve_schema_ref(root, reference) {
+            Some(target) => {
+                let refs = [refs, &[reference]].concat();
+                validate_at(root, target, value, path, &refs, violations)
+            }
+            None => violation(
+                violations,
+                path,
+                format!("unresolvable schema reference '{}'", reference),
+            ),
+        }
+        return;
+    }
+
+    match schema.get("type") {
+        Some(Value::String(expected)) if !matches_type(value, expected) => {
+            violation(
+                violations,
+                path,
+                format!("expected {}, got {}", expected, json_type_name(value)),
+            );
+            return;
+        }
+        Some(Value::Array(types)) => {
+            let allowed: Vec<&str> = types.iter().filter_map(|t| t.as_str()).collect();
+            if !allowed.iter().any(|t| matches_type(value, t)) {
+                violation(
+                    violations,
+                    path,
+                    format!(
+                        "expected one of [{}], got {}",
+                        allowed.join(", "),
+                        json_type_name(value)
+                    ),
+                );
+                return;
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            violation(violations, path, format!("must equal {}", expected));
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !options.contains(value) {
+            violation(
+                violations,
+                path,
+                format!("must be one of {}", Value::Array(options.clone())),
+            );
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+                for name in required.iter().filter_map(|r| r.as_str()) {
+                    if !map.contains_key(name) {
+                        violation(
+                            violations,
+                            path,
+                            format!("missing required property '{}'", name),
+                        );
+                    }
+                }
+            }
+            for (key, item) in map {
+                let item_path = format!("{}.{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(property_schema) => {
+                        validate_at(root, property_schema, item, &item_path, &[], violations)
+                    }
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            violation(violations, path, format!("unexpected property '{}'", key))
+                        }
+                        Some(additional @ Value::Object(_)) => {
+                            validate_at(root, additional, item, &item_path, &[], violations)
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(|m| m.as_u64()) {
+                if (items.len() as u64) < min {
+                    violation(
+                        violations,
+                        path,
+                        format!("must have at least {} items", min),
+                    );
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(|m| m.as_u64()) {
+                if items.len() as u64 > max {
+                    violation(violations, path, format!("must have at most {} items", max));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    let item_path = format!("{}[{}]", path, index);
+                    validate_at(root, item_schema, item, &item_path, &[], violations);
+                }
+            }
+        }
+        Value::String(text) => {
+            let length = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(|m| m.as_u64()) {
+                if length < min {
+                    violation(
+                        violations,
+                        path,
+                        format!("must be at least {} characters", min),
+                    );
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(|m| m.as_u64()) {
+                if length > max {
+                    violation(
+                        violations,
+                        path,
+                        format!("must be at most {} characters", max),
+                    );
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            let bound = |key: &str| schema.get(key).and_then(|b| b.as_f64());
+            if let Some(min) = bound("minimum").filter(|min| number < *min) {
+                violation(violations, path, format!("must be >= {}", min));
+            }
+            if let Some(max) = bound("maximum").filter(|max| number > *max) {
+                violation(violations, path, format!("must be <= {}", max));
+            }
+            if let Some(min) = bound("exclusiveMinimum").filter(|min| number <= *min) {
+                violation(violations, path, format!("must be > {}", min));
+            }
+            if let Some(max) = bound("exclusiveMaximum").filter(|max| number >= *max) {
+                violation(violations, path, format!("must be < {}", max));
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(all_of) = schema.get("allOf").and_then(|a| a.as_array()) {
+        for sub_schema in all_of {
+            validate_at(root, sub_schema, value, path, refs, violations);
+        }
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(options) = schema.get(keyword).and_then(|a| a.as_array()) {
+            let matching = options
+                .iter()
+                .filter(|option| {
+                    let mut nested = Vec::new();
+                    validate_at(root, option, value, path, refs, &mut nested);
+                    nested.is_empty()
+                })
+                .count();
+            if matching == 0 || (keyword == "oneOf" && matching > 1) {
+                violation(
+                    violations,
+                    path,
+                    format!(
+                        "must match {} of the {} schemas in {}",
+                        if keyword == "oneOf" {
+                            "exactly one"
+                        } else {
+                            "at least one"
+                        },
+                        options.len(),
+                        keyword
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Converts strings to the number, integer or boolean their schema asks for, e.g. `"5"`
+/// for `{"type": "integer"}`, following `properties`, `items` and local `$ref`s.
+/// Returns how many values were converted.
+pub fn coerce_json_types(schema: &Value, value: &mut Value) -> usize {
+    coerce_at(schema, schema, value, &[])
+}
+
+/// `refs` guards against circular references the same way as in `validate_at`
+fn coerce_at(root: &Value, schema: &Value, value: &mut Value, refs: &[&str]) -> usize {
+    let Some(schema) = schema.as_object() else {
+        return 0;
+    };
+    if let Some(reference) = schema.get("$ref").and_then(|r| r.as_str()) {
+        if refs.contains(&reference) {
+            return 0;
+        }
+        return resolve_schema_ref(root, reference).map_or(0, |target| {
+            coerce_at(root, target, value, &[refs, &[reference]].concat())
+        });
+    }
+
+    match value {
+        Value::String(s) => {
+            let types: Vec<&str> = match schema.get("type") {
+                Some(Value::String(t)) => vec![t.as_str()],
+                Some(Value::Array(types)) => types.iter().filter_map(|t| t.as_str()).collect(),
+                _ => return 0,
+            };
+            if types.contains(&"string") {
+                return 0;
+            }
+            match coerce_string(s, &types) {
+                Some(coerced) => {
+                    *value = coerced;
+                    1
+                }
+                None => 0,
+            }
+        }
+        Value::Object(fields) => {
+            schema
+                .get("properties")
+                .and_then(|p| p.as_object())
+                .map_or(0, |properties| {
+                    properties
+                        .iter()
+                        .filter_map(|(key, property)| {
+                            fields
+                                .get_mut(key)
+                                .map(|v| coerce_at(root, property, v, &[]))
+                        })
+                        .sum()
+                })
+        }
+        Value::Array(items) => schema.get("items").map_or(0, |item_schema| {
+            items
+                .iter_mut()
+                .map(|item| coerce_at(root, item_schema, item, &[]))
+                .sum()
+        }),
+        _ => 0,
+    }
+}
+
+fn coerce_string(s: &str, types: &[&str]) -> Option<Value> {
+    let s = s.trim();
+    types.iter().find_map(|t| match *t {
+        "integer" => s.parse::<i64>().ok().map(Value::from),
+        "number" => s.parse::<i64>().ok().map(Value::from).or_else(|| {
+            s.parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number)
+        }),
+        "boolean" => match s.to_ascii_lowercase().as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    })
+}
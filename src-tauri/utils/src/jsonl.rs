@@ -0,0 +1,169 @@
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use chrono::{DateTime, FixedOffset};
+use serde::de::DeserializeOwned;
+
+/// An append-only JSONL log rotated by size: `name.jsonl` is the active file and
+/// `name.1.jsonl` ... `name.{max_files - 1}.jsonl` hold older entries. Files are
+/// created readable by the owner only.
+#[derive(Debug, Clone)]
+pub struct RotatingJsonl {
+    dir: PathBuf,
+    file_name: String,
+    max_file_bytes: u64,
+    max_files: usize,
+}
+
+impl RotatingJsonl {
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        file_name: &str,
+        max_file_bytes: u64,
+        max_files: usize,
+    ) -> Self {
+        Self {
+            dir: dir.into(),
+            file_name: file_name.to_string(),
+            max_file_bytes,
+            max_files: max_files.max(1),
+        }
+    }
+
+    /// Path of the n-th rotated file (0 is the active file)
+    pub fn path(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            self.dir.join(&self.file_name)
+        } else {
+            let stem = self.file_name.trim_end_matches(".jsonl");
+            self.dir.join(format!("{}.{}.jsonl", stem, index))
+        }
+    }
+
+    /// Appends one line, rotating first when it would grow the active file past `max_file_bytes`
+    pub fn append_line(&self, line: &str) -> Result<(), String> {
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+
+        let path = self.path(0);
+        let current_size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if current_size > 0 && current_size + line.len() as u64 + 1 > self.max_file_bytes {
+            self.rotate()?;
+        }
+
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path).map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| e.to_string())
+    }
+
+    /// Runs `append_line` on the blocking thread pool
+    pub async fn append_line_blocking(&self, line: String) -> Result<(), String> {
+        let log = self.clone();
+        tokio::task::spawn_blocking(move || log.append_line(&line))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// Shifts name.jsonl -> name.1.jsonl -> ... and drops the oldest file
+    fn rotate(&self) -> Result<(), String> {
+        let oldest = self.path(self.max_files - 1);
+        if oldest.exists() {
+            fs::remove_file(&oldest).map_err(|e| e.to_string())?;
+        }
+        for index in (0..self.max_files - 1).rev() {
+            let from = self.path(index);
+            if from.exists() {
+                fs::rename(&from, self.path(index + 1)).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls `visit` with every well-formed entry, oldest file first
+    fn scan<T: DeserializeOwned>(&self, mut visit: impl FnMut(T)) -> Result<(), String> {
+        for index in (0..self.max_files).rev() {
+            let path = self.path(index);
+            if !path.exists() {
+                continue;
+            }
+            let reader = BufReader::new(File::open(&path).map_err(|e| e.to_string())?);
+            for line in reader.lines() {
+                let line = line.map_err(|e| e.to_string())?;
+                match serde_json::from_str::<T>(&line) {
+                    Ok(entry) => visit(entry),
+                    Err(_e) => {
+                        #[cfg(feature = "logging")]
+                        log::debug!("Skipping malformed line in {}: {}", path.display(), _e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns up to `limit` entries accepted by `filter`, newest first
+    pub fn read_newest<T: DeserializeOwned>(
+        &self,
+        limit: usize,
+        mut filter: impl FnMut(&T) -> bool,
+    ) -> Result<Vec<T>, String> {
+        let limit = limit.max(1);
+        let mut newest = VecDeque::with_capacity(limit);
+        self.scan(|entry: T| {
+            if filter(&entry) {
+                if newest.len() == limit {
+                    newest.pop_front();
+                }
+                newest.push_back(entry);
+            }
+        })?;
+        Ok(newest.into_iter().rev().collect())
+    }
+
+    /// Returns the most recently written entry accepted by `filter`
+    pub fn find_latest<T: DeserializeOwned>(
+        &self,
+        filter: impl FnMut(&T) -> bool,
+    ) -> Result<Option<T>, String> {
+        Ok(self.read_newest(1, filter)?.pop())
+    }
+}
+
+/// Inclusive `since`/`until` bounds on RFC 3339 timestamps
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeRange {
+    since: Option<DateTime<FixedOffset>>,
+    until: Option<DateTime<FixedOffset>>,
+}
+
+impl TimeRange {
+    pub fn parse(since: Option<&str>, until: Option<&str>) -> Result<Self, String> {
+        Ok(Self {
+            since: since.map(parse_timestamp).transpose()?,
+            until: until.map(parse_timestamp).transpose()?,
+        })
+    }
+
+    /// Whether `timestamp` falls in the range; unparseable timestamps only match an open range
+    pub fn contains(&self, timestamp: &str) -> bool {
+        if self.since.is_none() && self.until.is_none() {
+            return true;
+        }
+        let Ok(timestamp) = parse_timestamp(timestamp) else {
+            return false;
+        };
+        !self.since.is_some_and(|since| timestamp < since)
+            && !self.until.is_some_and(|until| timestamp > until)
+    }
+}
+
+fn parse_timestamp(value: &str) -> Result<DateTime<FixedOffset>, String> {
+    DateTime::parse_from_rfc3339(value).map_err(|e| format!("Invalid timestamp '{}': {}", value, e))
+}
@@ -3,9 +3,12 @@ pub mod config;
 pub mod crypto;
 pub mod fs;
 pub mod http;
+pub mod json_schema;
+pub mod jsonl;
 pub mod math;
 pub mod network;
 pub mod path;
+pub mod redact;
 pub mod string;
 pub mod system;
 
@@ -15,8 +18,11 @@ pub use config::*;
 pub use crypto::*;
 pub use fs::*;
 pub use http::*;
+pub use json_schema::*;
+pub use jsonl::*;
 pub use math::*;
 pub use network::*;
 pub use path::*;
+pub use redact::*;
 pub use string::*;
 pub use system::*;
@@ -0,0 +1,23 @@
+use serde_json::Value;
+
+/// Written in place of redacted values
+pub const REDACTED_VALUE: &str = "[REDACTED]";
+
+/// Recursively replaces values whose key matches one of `fields` (case-insensitive)
+pub fn redact_json(value: &Value, fields: &[String]) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, v)| {
+                    if fields.iter().any(|f| f.eq_ignore_ascii_case(key)) {
+                        (key.clone(), Value::String(REDACTED_VALUE.to_string()))
+                    } else {
+                        (key.clone(), redact_json(v, fields))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(|v| redact_json(v, fields)).collect()),
+        other => other.clone(),
+    }
+}
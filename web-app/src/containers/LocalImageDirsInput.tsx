@@ -0,0 +1,45 @@
+import { Input } from '@/components/ui/input'
+import { useLocalApiServer } from '@/hooks/useLocalApiServer'
+import { useState, useEffect } from 'react'
+import { useTranslation } from '@/i18n/react-i18next-compat'
+
+export function LocalImageDirsInput() {
+  const { localImageDirs, setLocalImageDirs } = useLocalApiServer()
+  const [inputValue, setInputValue] = useState(localImageDirs.join(', '))
+  const { t } = useTranslation()
+
+  // Update input value when localImageDirs changes externally
+  useEffect(() => {
+    setInputValue(localImageDirs.join(', '))
+  }, [localImageDirs])
+
+  const handleChange = (e: React.ChangeEvent<HTMLInputElement>) => {
+    setInputValue(e.target.value)
+  }
+
+  const handleBlur = () => {
+    // Split by comma and drop empty and duplicate entries
+    const dirs = [
+      ...new Set(
+        inputValue
+          .split(',')
+          .map((dir) => dir.trim())
+          .filter((dir) => dir.length > 0)
+      ),
+    ]
+
+    setLocalImageDirs(dirs)
+    setInputValue(dirs.join(', '))
+  }
+
+  return (
+    <Input
+      type="text"
+      value={inputValue}
+      onChange={handleChange}
+      onBlur={handleBlur}
+      className="w-full h-8 text-sm"
+      placeholder={t('common:enterLocalImageDirs')}
+    />
+  )
+}
@@ -32,6 +32,8 @@ describe('useLocalApiServer', () => {
     store.setVerboseLogs(true)
     store.setTrustedHosts([])
     store.setApiKey('')
+    store.setInlineImages(false)
+    store.setLocalImageDirs([])
   })
 
   it('should initialize with default values', () => {
@@ -45,6 +47,8 @@ describe('useLocalApiServer', () => {
     expect(result.current.verboseLogs).toBe(true)
     expect(result.current.trustedHosts).toEqual([])
     expect(result.current.apiKey).toBe('')
+    expect(result.current.inlineImages).toBe(false)
+    expect(result.current.localImageDirs).toEqual([])
   })
 
   describe('enableOnStartup', () => {
@@ -157,6 +161,20 @@ describe('useLocalApiServer', () => {
     })
   })
 
+  describe('image inlining', () => {
+    it('should toggle inline images and set local image directories', () => {
+      const { result } = renderHook(() => useLocalApiServer())
+
+      act(() => {
+        result.current.setInlineImages(true)
+        result.current.setLocalImageDirs(['/Users/rep/Pictures'])
+      })
+
+      expect(result.current.inlineImages).toBe(true)
+      expect(result.current.localImageDirs).toEqual(['/Users/rep/Pictures'])
+    })
+  })
+
   describe('verboseLogs', () => {
     it('should toggle verbose logs', () => {
       const { result } = renderHook(() => useLocalApiServer())
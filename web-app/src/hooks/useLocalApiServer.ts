@@ -28,6 +28,12 @@ type LocalApiServerState = {
   addTrustedHost: (host: string) => void
   removeTrustedHost: (host: string) => void
   setTrustedHosts: (hosts: string[]) => void
+  // Fetch image URLs and local image paths and send them to the model inline
+  inlineImages: boolean
+  setInlineImages: (value: boolean) => void
+  // Directories local image paths may be read from
+  localImageDirs: string[]
+  setLocalImageDirs: (dirs: string[]) => void
 }
 
 export const useLocalApiServer = create<LocalApiServerState>()(
@@ -55,6 +61,10 @@ export const useLocalApiServer = create<LocalApiServerState>()(
           trustedHosts: state.trustedHosts.filter((h) => h !== host),
         })),
       setTrustedHosts: (hosts) => set({ trustedHosts: hosts }),
+      inlineImages: false,
+      setInlineImages: (value) => set({ inlineImages: value }),
+      localImageDirs: [],
+      setLocalImageDirs: (dirs) => set({ localImageDirs: dirs }),
       apiKey: '',
       setApiKey: (value) => set({ apiKey: value }),
     }),
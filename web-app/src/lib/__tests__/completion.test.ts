@@ -153,6 +153,26 @@ describe('completion.ts', () => {
       const result = normalizeTools([])
       expect(result).toBeUndefined()
     })
+
+    it('should qualify tool names exposed by several servers', () => {
+      const tool = (server: string, name: string) => ({
+        name,
+        description: '',
+        inputSchema: {},
+        server,
+        qualifiedName: `${server}__${name}`,
+      })
+      const result = normalizeTools([
+        tool('crm', 'search'),
+        tool('web', 'search'),
+        tool('web', 'fetch'),
+      ])
+      expect(result?.map((t) => t.function.name)).toEqual([
+        'crm__search',
+        'web__search',
+        'fetch',
+      ])
+    })
   })
 
   describe('extractToolCall', () => {
@@ -367,10 +367,17 @@ export const normalizeTools = (
   tools: MCPTool[]
 ): ChatCompletionTool[] | Tool[] | undefined => {
   if (tools.length === 0) return undefined
+  // Names exposed by several servers are given to the model qualified as
+  // `server__tool`, so each call is dispatched to the server that owns the tool
+  const isAmbiguous = (name: string) =>
+    tools.filter((tool) => tool.name === name).length > 1
   return tools.map((tool) => ({
     type: 'function',
     function: {
-      name: tool.name,
+      name:
+        isAmbiguous(tool.name) && tool.qualifiedName
+          ? tool.qualifiedName
+          : tool.name,
       description: tool.description?.slice(0, 1024),
       parameters: tool.inputSchema,
       strict: false,
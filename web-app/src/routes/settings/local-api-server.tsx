@@ -10,6 +10,7 @@ import { ServerHostSwitcher } from '@/containers/ServerHostSwitcher'
 import { PortInput } from '@/containers/PortInput'
 import { ApiPrefixInput } from '@/containers/ApiPrefixInput'
 import { TrustedHostsInput } from '@/containers/TrustedHostsInput'
+import { LocalImageDirsInput } from '@/containers/LocalImageDirsInput'
 import { useLocalApiServer } from '@/hooks/useLocalApiServer'
 import { WebviewWindow } from '@tauri-apps/api/webviewWindow'
 import { useAppState } from '@/hooks/useAppState'
@@ -42,6 +43,9 @@ function LocalAPIServer() {
     apiPrefix,
     apiKey,
     trustedHosts,
+    inlineImages,
+    setInlineImages,
+    localImageDirs,
   } = useLocalApiServer()
 
   const { serverStatus, setServerStatus } = useAppState()
@@ -53,8 +57,8 @@ function LocalAPIServer() {
 
   useEffect(() => {
     const checkServerStatus = async () => {
-      invoke('get_server_status').then((running) => {
-        if (running) {
+      invoke<{ running: boolean }>('get_server_status').then((status) => {
+        if (status.running) {
           setServerStatus('running')
         }
       })
@@ -148,6 +152,13 @@ function LocalAPIServer() {
             trustedHosts,
             isCorsEnabled: corsEnabled,
             isVerboseEnabled: verboseLogs,
+            options: {
+              multimodal: {
+                inline_images: inlineImages,
+                allow_local_files: localImageDirs.length > 0,
+                local_image_dirs: localImageDirs,
+              },
+            },
           })
         })
         .then(() => {
@@ -357,6 +368,30 @@ function LocalAPIServer() {
                   />
                 }
               />
+              <CardItem
+                title={t('settings:localApiServer.inlineImages')}
+                description={t('settings:localApiServer.inlineImagesDesc')}
+                className={cn(
+                  isServerRunning && 'opacity-50 pointer-events-none'
+                )}
+                actions={
+                  <Switch
+                    checked={inlineImages}
+                    onCheckedChange={setInlineImages}
+                  />
+                }
+              />
+              <CardItem
+                title={t('settings:localApiServer.localImageDirs')}
+                description={t('settings:localApiServer.localImageDirsDesc')}
+                className={cn(
+                  'flex-col sm:flex-row items-start sm:items-center sm:justify-between gap-y-2',
+                  (isServerRunning || !inlineImages) &&
+                    'opacity-50 pointer-events-none'
+                )}
+                classNameWrapperAction="w-full sm:w-auto"
+                actions={<LocalImageDirsInput />}
+              />
               <CardItem
                 title={t('settings:localApiServer.verboseLogs')}
                 description={t('settings:localApiServer.verboseLogsDesc')}
@@ -7,4 +7,6 @@ export type MCPTool = {
   description: string
   inputSchema: Record<string, unknown>
   server: string
+  // `server__tool`, sent to the model when several servers expose `name`
+  qualifiedName?: string
 }
@@ -2,4 +2,12 @@ export enum SystemEvent {
   MCP_UPDATE = 'mcp-update',
   KILL_SIDECAR = 'kill-sidecar',
   MCP_ERROR = 'mcp-error',
+  MCP_TOOL_COLLISION = 'mcp-tool-collision',
+  MCP_TOOL_APPROVAL_REQUESTED = 'mcp-tool-approval-requested',
+  MCP_TOOL_APPROVAL_RESOLVED = 'mcp-tool-approval-resolved',
+  MCP_RESOURCE_UPDATED = 'mcp-resource-updated',
+  MCP_RESOURCES_CHANGED = 'mcp-resources-changed',
+  MCP_PROMPTS_CHANGED = 'mcp-prompts-changed',
+  MCP_TOOL_PROGRESS = 'mcp-tool-progress',
+  MCP_TOOL_LOG = 'mcp-tool-log',
 }